@@ -0,0 +1,76 @@
+use prose_parser::Node;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub file: String,
+    pub title: String,
+    pub episode: Option<u32>,
+    pub document: Node,
+}
+
+/// Parses episode sources and orders them by the `episode` of their front
+/// matter. Episodes without a number follow the numbered ones in input order.
+///
+/// The title is taken from the front matter, then the first heading, then the
+/// file name.
+pub fn chapters(sources: &[(&str, &str)]) -> Result<Vec<Chapter>, String> {
+    let mut ret = sources
+        .iter()
+        .map(|(file, source)| {
+            let document =
+                prose_parser::parse(source).map_err(|err| format!("{}: {}", file, err))?;
+            let metadata = document.metadata().cloned().unwrap_or_default();
+            let title = metadata
+                .title
+                .or_else(|| first_heading(&document))
+                .unwrap_or_else(|| file.to_string());
+
+            Ok(Chapter {
+                file: file.to_string(),
+                title,
+                episode: metadata.episode,
+                document,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    ret.sort_by_key(|item| item.episode.map_or((1, 0), |episode| (0, episode)));
+
+    Ok(ret)
+}
+
+fn first_heading(document: &Node) -> Option<String> {
+    document
+        .children()
+        .iter()
+        .find(|item| item.name() == Some("heading"))
+        .map(|item| item.text().trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chapters() {
+        let actual = chapters(&[
+            ("extra.pd", "# おまけ\n"),
+            ("2.pd", "---\ntitle: 再会\nepisode: 2\n---\n# 第二話\n"),
+            ("1.pd", "---\nepisode: 1\n---\n# 出会い\n"),
+            ("memo.pd", "メモ"),
+        ])
+        .unwrap()
+        .iter()
+        .map(|item| (item.file.clone(), item.title.clone(), item.episode))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            actual,
+            vec![
+                ("1.pd".to_string(), "出会い".to_string(), Some(1)),
+                ("2.pd".to_string(), "再会".to_string(), Some(2)),
+                ("extra.pd".to_string(), "おまけ".to_string(), None),
+                ("memo.pd".to_string(), "memo.pd".to_string(), None),
+            ]
+        );
+    }
+}
@@ -1,11 +1,11 @@
-#![feature(generators, generator_trait)]
-
 #[macro_use]
 extern crate diesel;
 
+mod chapters;
 mod schemas;
 mod use_cases;
 
+pub use chapters::{chapters, Chapter};
 use use_cases::books;
 
 #[cfg(test)]
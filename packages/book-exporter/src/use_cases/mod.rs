@@ -32,9 +32,6 @@ pub struct EpisodeSourceNarou {
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 
-use std::ops::{Generator, GeneratorState};
-use std::pin::Pin;
-
 pub fn establish_connection() -> SqliteConnection {
     // dotenv().ok();
 
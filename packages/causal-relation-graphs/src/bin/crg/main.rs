@@ -1,18 +1,272 @@
 extern crate causal_relation_graphs;
 
+mod repl;
+
+use causal_relation_graphs::{
+    check, check_file, format, graph, parse, run, run_file, run_file_with_outputs, run_traced,
+    run_with_outputs, Error, ErrorKind,
+};
 use std::env;
 use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "usage: crg <command> [options] [FILE]
+
+commands:
+  run [-I DIR]... FILE              check and evaluate FILE, print the result as JSON
+      --outputs                     print every `Output` with the result
+  check [-I DIR]... FILE            check FILE without evaluating it
+  parse [--format json|yaml] FILE   print the syntax tree of FILE
+  fmt FILE                          print FILE formatted
+  graph [--format dot|mermaid] FILE print the causal graph of FILE
+  trace [--format log|json] FILE    evaluate FILE and print every step it takes
+  repl                              start an interactive session
+
+FILE is read from stdin when it is `-` or missing.
+imports are looked up next to the importing file, then in each DIR given
+with `-I` or `--path`.
+exits with 1 when FILE has an error and 2 on usage or I/O errors.";
+
+/// The input has a syntax, type or evaluation error.
+const EXIT_DIAGNOSTIC: i32 = 1;
+/// The command line is wrong or the input cannot be read.
+const EXIT_USAGE: i32 = 2;
+
+enum Command {
+    /// Whether every `Output` is printed with the value.
+    Run(bool),
+    Check,
+    Parse(ParseFormat),
+    Fmt,
+    Graph(GraphFormat),
+    Trace(TraceFormat),
+    Repl,
+}
+
+enum ParseFormat {
+    Json,
+    Yaml,
+}
+
+enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+enum TraceFormat {
+    Log,
+    Json,
+}
 
 fn main() {
-    let args = env::args();
-    let filename = args.last().expect("a");
-    let file = fs::read_to_string(filename).expect("");
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (command, filename, search_path) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("crg: {}\n\n{}", message, USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    if let Command::Repl = command {
+        repl::run();
+        return;
+    }
+
+    let (name, source) = match read(filename.as_deref()) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("crg: {}", message);
+            process::exit(EXIT_USAGE);
+        }
+    };
 
-    let result = causal_relation_graphs::run(&file);
+    // the trace is printed even when the evaluation fails
+    if let Command::Trace(format) = &command {
+        let (output, result) = trace(format, &source);
+        print!("{}", output);
+        if let Err(err) = result {
+            fail(&name, &source, &err);
+        }
+        return;
+    }
+
+    let result = match (&command, &filename) {
+        (Command::Run(false), Some(filename)) => {
+            run_file(Path::new(filename), &search_path).map(|json| format!("{}\n", json))
+        }
+        (Command::Run(true), Some(filename)) => {
+            run_file_with_outputs(Path::new(filename), &search_path)
+                .map(|json| format!("{}\n", json))
+        }
+        (Command::Check, Some(filename)) => {
+            check_file(Path::new(filename), &search_path).map(|_| String::new())
+        }
+        _ => execute(&command, &source),
+    };
     match result {
-        Ok(result) => println!("{}", result),
-        Err(err) => println!("error, {}", err),
+        Ok(output) => print!("{}", output),
+        Err(err) => fail(&name, &source, &err),
     }
+}
+
+fn fail(name: &str, source: &str, err: &Error) -> ! {
+    // errors in imported files are shown with their own source
+    let imported = err
+        .file
+        .as_ref()
+        .filter(|file| file.as_path() != Path::new(name))
+        .and_then(|file| {
+            fs::read_to_string(file)
+                .ok()
+                .map(|source| (file.display().to_string(), source))
+        });
+    let (name, source) = match &imported {
+        Some((name, source)) => (name.as_str(), source.as_str()),
+        None => (name, source),
+    };
+
+    eprintln!("{}:{}", name, err.render(source));
+    process::exit(EXIT_DIAGNOSTIC);
+}
 
-    ()
+/// The command, the file it reads, `None` for stdin, and where imports are
+/// looked up. A lone file name is `run`, as before subcommands existed.
+fn parse_args(args: &[String]) -> Result<(Command, Option<String>, Vec<PathBuf>), String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err("no command given".to_string()),
+    };
+
+    let mut format = None;
+    let mut outputs = false;
+    let mut files = Vec::new();
+    let mut search_path = Vec::new();
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--format" | "-f" => match rest.next() {
+                Some(value) => format = Some(value.as_str()),
+                None => return Err(format!("`{}` needs a value", arg)),
+            },
+            "--path" | "-I" => match rest.next() {
+                Some(value) => search_path.push(PathBuf::from(value)),
+                None => return Err(format!("`{}` needs a value", arg)),
+            },
+            "--outputs" => outputs = true,
+            "-" => files.push(arg.clone()),
+            option if option.starts_with('-') => {
+                return Err(format!("unknown option `{}`", option))
+            }
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    let command = match (command, format) {
+        ("run", None) => Command::Run(outputs),
+        ("check", None) => Command::Check,
+        ("parse", None | Some("json")) => Command::Parse(ParseFormat::Json),
+        ("parse", Some("yaml")) => Command::Parse(ParseFormat::Yaml),
+        ("fmt", None) => Command::Fmt,
+        ("graph", None | Some("dot")) => Command::Graph(GraphFormat::Dot),
+        ("graph", Some("mermaid")) => Command::Graph(GraphFormat::Mermaid),
+        ("trace", None | Some("log")) => Command::Trace(TraceFormat::Log),
+        ("trace", Some("json")) => Command::Trace(TraceFormat::Json),
+        ("repl", None) if files.is_empty() => Command::Repl,
+        ("parse" | "graph" | "trace", Some(format)) => {
+            return Err(format!("unknown format `{}` for `{}`", format, command))
+        }
+        ("run" | "check" | "fmt" | "repl", Some(_)) => {
+            return Err(format!("`{}` has no `--format`", command))
+        }
+        ("repl", None) => return Err("`repl` takes no file".to_string()),
+        (file, None) if args.len() == 1 && !file.starts_with('-') => {
+            return Ok((Command::Run(false), Some(file.to_string()), search_path))
+        }
+        (command, _) => return Err(format!("unknown command `{}`", command)),
+    };
+    if !search_path.is_empty() && !matches!(command, Command::Run(_) | Command::Check) {
+        return Err(format!("`{}` has no `--path`", args[0]));
+    }
+    if outputs && !matches!(command, Command::Run(_)) {
+        return Err(format!("`{}` has no `--outputs`", args[0]));
+    }
+
+    // imports are resolved next to the file, which stdin does not have
+    if !search_path.is_empty() && files.iter().all(|file| file == "-") {
+        return Err("`--path` needs a file, not stdin".to_string());
+    }
+
+    match files.as_slice() {
+        [] => Ok((command, None, search_path)),
+        [file] if file == "-" => Ok((command, None, search_path)),
+        [file] => Ok((command, Some(file.clone()), search_path)),
+        _ => Err("only one file can be given".to_string()),
+    }
+}
+
+/// The name to report diagnostics with and the source.
+fn read(filename: Option<&str>) -> Result<(String, String), String> {
+    match filename {
+        Some(filename) => fs::read_to_string(filename)
+            .map(|source| (filename.to_string(), source))
+            .map_err(|err| format!("{}: {}", filename, err)),
+        None => {
+            let mut source = String::new();
+            io::stdin()
+                .read_to_string(&mut source)
+                .map(|_| ("<stdin>".to_string(), source))
+                .map_err(|err| format!("<stdin>: {}", err))
+        }
+    }
+}
+
+/// What the command prints for `source`.
+fn execute(command: &Command, source: &str) -> Result<String, Error> {
+    match command {
+        Command::Run(false) => run(source).map(|json| format!("{}\n", json)),
+        Command::Run(true) => run_with_outputs(source).map(|json| format!("{}\n", json)),
+        Command::Check => check(&parse(source)?).map(|_| String::new()),
+        Command::Parse(format) => {
+            let ast = parse(source)?;
+            let output = match format {
+                ParseFormat::Json => ast.to_json().map(|json| format!("{}\n", json)),
+                ParseFormat::Yaml => ast.to_yaml(),
+            };
+
+            output.map_err(|err| Error::new(ErrorKind::Evaluation, &err, Default::default()))
+        }
+        Command::Fmt => format(source),
+        Command::Graph(format) => {
+            let ast = parse(source)?;
+            check(&ast)?;
+            let graph = graph(&ast)?;
+
+            Ok(match format {
+                GraphFormat::Dot => graph.to_dot(),
+                GraphFormat::Mermaid => graph.to_mermaid(),
+            })
+        }
+        Command::Trace(format) => trace(format, source).1.map(|_| String::new()),
+        Command::Repl => Ok(String::new()),
+    }
+}
+
+/// The trace of evaluating `source` and whether the evaluation succeeded.
+fn trace(format: &TraceFormat, source: &str) -> (String, Result<(), Error>) {
+    let (result, trace) = run_traced(source);
+    let output = match format {
+        TraceFormat::Log => Ok(trace.render(source)),
+        TraceFormat::Json => trace.to_json().map(|json| format!("{}\n", json)),
+    };
+
+    match output {
+        Ok(output) => (output, result.map(|_| ())),
+        Err(err) => (
+            String::new(),
+            Err(Error::new(ErrorKind::Evaluation, &err, Default::default())),
+        ),
+    }
 }
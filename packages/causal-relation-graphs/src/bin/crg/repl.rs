@@ -0,0 +1,116 @@
+use causal_relation_graphs::Session;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = ":type EXPR   show the type of EXPR without evaluating it
+:env         list the bindings
+:load FILE   evaluate FILE in the session
+:reset       forget every binding
+:help        show this help
+:quit        leave the REPL";
+
+enum Outcome {
+    Print(String),
+    Error(String),
+    Nothing,
+    Quit,
+}
+
+/// Reads inputs from stdin until EOF or `:quit`. An input with unclosed
+/// brackets continues on the next line.
+pub fn run() {
+    let stdin = io::stdin();
+    let mut session = Session::new();
+    let mut input = String::new();
+
+    loop {
+        prompt(if input.is_empty() { "crg> " } else { "...> " });
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => input.push_str(&line),
+        }
+        if depth(&input) > 0 {
+            continue;
+        }
+
+        match eval(&mut session, std::mem::take(&mut input).trim()) {
+            Outcome::Print(text) => println!("{}", text),
+            Outcome::Error(text) => eprintln!("{}", text),
+            Outcome::Nothing => {}
+            Outcome::Quit => break,
+        }
+    }
+}
+
+fn prompt(text: &str) {
+    print!("{}", text);
+    let _ = io::stdout().flush();
+}
+
+fn eval(session: &mut Session, input: &str) -> Outcome {
+    let (command, rest) = match input.strip_prefix(':') {
+        Some(command) => {
+            let (command, rest) = command
+                .split_once(char::is_whitespace)
+                .unwrap_or((command, ""));
+            (Some(command), rest.trim())
+        }
+        None => (None, input),
+    };
+
+    match command {
+        None if input.is_empty() => Outcome::Nothing,
+        None => match session.eval(input) {
+            Ok(Some(value)) => Outcome::Print(value),
+            Ok(None) => Outcome::Nothing,
+            Err(err) => Outcome::Error(err.render(input)),
+        },
+        Some("type" | "t") => match session.type_of(rest) {
+            Ok(ty) => Outcome::Print(ty),
+            Err(err) => Outcome::Error(err.render(rest)),
+        },
+        Some("env") => match session.bindings().as_slice() {
+            [] => Outcome::Nothing,
+            bindings => Outcome::Print(
+                bindings
+                    .iter()
+                    .map(|binding| {
+                        format!("{} :: {} = {}", binding.name, binding.kind, binding.value)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        },
+        Some("load" | "l") => match fs::read_to_string(rest) {
+            Ok(file) => match session.eval(&file) {
+                Ok(Some(value)) => Outcome::Print(value),
+                Ok(None) => Outcome::Nothing,
+                Err(err) => Outcome::Error(format!("{}:{}", rest, err.render(&file))),
+            },
+            Err(err) => Outcome::Error(format!("{}: {}", rest, err)),
+        },
+        Some("reset") => {
+            *session = Session::new();
+            Outcome::Nothing
+        }
+        Some("help" | "h") => Outcome::Print(HELP.to_string()),
+        Some("quit" | "q") => Outcome::Quit,
+        Some(command) => Outcome::Error(format!("unknown command `:{}`, try :help", command)),
+    }
+}
+
+/// How many brackets are left open, ignoring comments and state labels.
+fn depth(input: &str) -> i64 {
+    input
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .flat_map(|line| line.split('`').step_by(2))
+        .flat_map(str::chars)
+        .map(|c| match c {
+            '(' | '[' | '{' => 1,
+            ')' | ']' | '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
@@ -0,0 +1,989 @@
+use crate::error::{Error, ErrorKind, Span};
+use crate::module::Exports;
+use crate::parser::{
+    Assignable, BindKeywords, Branch, ContextEffect, ContextLabel, ContextType, Effect, EffectType,
+    Expr, FunctionSignature, FunctionSignatureItem, Import, LetExpr, Node, Op, Pattern,
+    ScopeInnerValue, ScopeValue, Slice, SliceItem, SnapshotValue, SnapshotValueItem,
+    SnapshotValueItemValue, StateLabel, Stmt, TransitionEffect, TypeExpr, TypeKind, TypeValue,
+    Value, ValueSymbol,
+};
+use crate::types::{self, Machine, Normal};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::mem;
+
+/// Checks `ast` before it is evaluated.
+///
+/// Types and contexts are visible in the whole document, like in `symbols`,
+/// while `let` bindings follow the scopes of the VM. Imports are only
+/// resolved by `check_file`, which knows where the document is.
+pub fn check(ast: &Node) -> Result<(), Error> {
+    let mut checker = Checker::default();
+    match ast {
+        Node::Root(scope) => {
+            checker.declare_types(scope)?;
+            checker.declare_contexts(scope)?;
+            checker.scope(scope)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The static type of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Type {
+    /// `Id`, `Empty` and values that are only known once evaluated.
+    Any,
+    Effect(EffectShape),
+    Context(String),
+    ContextEffect(String),
+    /// The contexts the slice has effects on.
+    Slice(BTreeSet<String>),
+    /// The contexts the snapshot has values for.
+    Snapshot(BTreeSet<String>),
+    Int,
+    /// The types of the arguments left to apply and of the result. `Any`
+    /// where the function was not declared with a signature.
+    Function(Vec<Type>, Box<Type>),
+    /// The types of the alternatives of `|`.
+    Choice(Vec<Type>),
+    /// The contexts of the snapshots reached by `reduceAll`.
+    Routes(BTreeSet<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EffectShape {
+    /// `Id` and `Empty`, which fit every context.
+    Neutral,
+    Add,
+    /// The states the transition goes through.
+    Transition(Vec<StateLabel>),
+}
+
+/// What the effects on a context act on.
+#[derive(Debug, Clone, PartialEq)]
+enum ContextKind {
+    Int,
+    /// The machine, or `None` when its states are not declared.
+    StateMachine(Option<Machine>),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "a value"),
+            Type::Effect(EffectShape::Neutral) => write!(f, "an effect"),
+            Type::Effect(EffectShape::Add) => write!(f, "an add effect"),
+            Type::Effect(EffectShape::Transition(_)) => write!(f, "a transition"),
+            Type::Context(_) => write!(f, "a context"),
+            Type::ContextEffect(_) => write!(f, "a context effect"),
+            Type::Slice(_) => write!(f, "a slice"),
+            Type::Snapshot(_) => write!(f, "a snapshot"),
+            Type::Int => write!(f, "an Int"),
+            Type::Function(..) => write!(f, "a function"),
+            Type::Choice(_) => write!(f, "a choice"),
+            Type::Routes(_) => write!(f, "routes"),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Checker {
+    types: BTreeMap<String, (TypeKind, Option<TypeValue>)>,
+    contexts: BTreeMap<String, ContextKind>,
+    scopes: Vec<Vec<(String, Type)>>,
+    /// The namespaces of the imports bound by `import`.
+    namespaces: BTreeSet<String>,
+}
+
+impl Type {
+    /// The type with the contexts it has, for `:type` in the REPL.
+    pub(crate) fn describe(&self) -> String {
+        let contexts = |contexts: &BTreeSet<String>| {
+            contexts
+                .iter()
+                .map(|context| format!("`{}`", context))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Type::ContextEffect(context) => format!("a context effect on `{}`", context),
+            Type::Slice(items) if !items.is_empty() => format!("a slice on {}", contexts(items)),
+            Type::Snapshot(items) if !items.is_empty() => {
+                format!("a snapshot of {}", contexts(items))
+            }
+            Type::Routes(items) if !items.is_empty() => format!("routes of {}", contexts(items)),
+            Type::Function(params, _) => format!("a function of {} arguments", params.len()),
+            Type::Choice(types) => types
+                .iter()
+                .map(Type::describe)
+                .collect::<Vec<_>>()
+                .join(" | "),
+            ty => ty.to_string(),
+        }
+    }
+}
+
+impl Checker {
+    /// Checks one input of a REPL on top of the inputs before it. The
+    /// bindings of `scope` stay visible to the next inputs.
+    pub(crate) fn input(&mut self, scope: &ScopeValue) -> Result<Type, Error> {
+        self.declare_types(scope)?;
+        self.declare_contexts(scope)?;
+        if self.scopes.is_empty() {
+            self.scopes.push(Vec::new());
+        }
+
+        scope.0.iter().try_fold(Type::Any, |ty, item| match item {
+            ScopeInnerValue::Comment(_) => Ok(ty),
+            ScopeInnerValue::Stmt(stmt) => {
+                self.stmt(stmt)?;
+
+                Ok(ty)
+            }
+            ScopeInnerValue::Expr(expr) => self.expr(expr),
+        })
+    }
+
+    /// Binds the exports of a file imported by `import`, already qualified
+    /// with its namespace, before the importing file is checked.
+    pub(crate) fn import(&mut self, import: &Import, exports: &Exports) -> Result<(), Error> {
+        if !self.namespaces.insert(import.namespace.0.clone()) {
+            return Err(Error::new(
+                ErrorKind::DuplicateSymbol,
+                &format!("`{}` is already imported", import.namespace.0),
+                import.location.0,
+            ));
+        }
+
+        exports.types.iter().for_each(|(symbol, value)| {
+            let value = match value {
+                TypeValue::Empty => None,
+                value => Some(value.clone()),
+            };
+            self.types
+                .insert(symbol.name.0.clone(), (symbol.kind.clone(), value));
+        });
+        exports
+            .types
+            .iter()
+            .try_for_each(|(symbol, value)| match value {
+                TypeValue::Empty => Ok(()),
+                value => {
+                    self.declare_defined(&symbol.name.0, &symbol.kind, value, import.location.0)
+                }
+            })
+            // the contexts are declared in the other file
+            .map_err(|err| Error::new(err.kind, &err.message, import.location.0))?;
+
+        if self.scopes.is_empty() {
+            self.scopes.push(Vec::new());
+        }
+        exports.values.iter().for_each(|(symbol, _, ty)| {
+            self.scopes[0].push((symbol.name.0.clone(), ty.clone()));
+        });
+
+        Ok(())
+    }
+
+    /// The type of `name` in the outermost scope, for the exports of a file.
+    pub(crate) fn value_type(&self, name: &str) -> Option<Type> {
+        self.scopes.first().and_then(|scope| {
+            scope
+                .iter()
+                .rev()
+                .find(|(bound, _)| bound == name)
+                .map(|(_, ty)| ty.clone())
+        })
+    }
+
+    /// Fails unless the statement at `span` is outside of every scope.
+    fn top_level(&self, what: &str, span: Span) -> Result<(), Error> {
+        match self.scopes.len() > 1 {
+            true => Err(Error::new(
+                ErrorKind::Unsupported,
+                &format!("{} only at the top of a file", what),
+                span,
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn declare_types(&mut self, scope: &ScopeValue) -> Result<(), Error> {
+        let mut defined = Vec::new();
+
+        scope.0.iter().try_for_each(|item| match item {
+            ScopeInnerValue::Stmt(Stmt::TypeStmt(TypeExpr::Bind(symbol))) => {
+                self.types
+                    .insert(symbol.name.0.clone(), (symbol.kind.clone(), None));
+
+                Ok(())
+            }
+            ScopeInnerValue::Stmt(Stmt::TypeStmt(TypeExpr::Assign { symbol, value })) => {
+                if !symbol.assignable(value) {
+                    return Err(Error::new(
+                        ErrorKind::Type,
+                        &format!(
+                            "`{}` is declared as {} but defined as {}",
+                            symbol.name.0,
+                            kind_name(&symbol.kind),
+                            type_value_name(value)
+                        ),
+                        symbol.location.0,
+                    ));
+                }
+                if defined.contains(&&symbol.name.0) {
+                    return Err(Error::new(
+                        ErrorKind::DuplicateSymbol,
+                        &format!("type `{}` is already defined in this scope", symbol.name.0),
+                        symbol.location.0,
+                    ));
+                }
+                defined.push(&symbol.name.0);
+                self.types.insert(
+                    symbol.name.0.clone(),
+                    (symbol.kind.clone(), Some(value.clone())),
+                );
+
+                Ok(())
+            }
+            _ => scopes_of(item)
+                .into_iter()
+                .try_for_each(|scope| self.declare_types(scope)),
+        })
+    }
+
+    fn declare_contexts(&mut self, scope: &ScopeValue) -> Result<(), Error> {
+        scope.0.iter().try_for_each(|item| match item {
+            ScopeInnerValue::Stmt(Stmt::TypeStmt(TypeExpr::Assign { symbol, value })) => {
+                self.declare_defined(&symbol.name.0, &symbol.kind, value, symbol.location.0)
+            }
+            _ => scopes_of(item)
+                .into_iter()
+                .try_for_each(|scope| self.declare_contexts(scope)),
+        })
+    }
+
+    /// Normalizes the definition of type `name` and declares the contexts
+    /// it has.
+    fn declare_defined(
+        &mut self,
+        name: &str,
+        kind: &TypeKind,
+        value: &TypeValue,
+        span: Span,
+    ) -> Result<(), Error> {
+        if let TypeValue::Id | TypeValue::Int | TypeValue::Empty | TypeValue::TypeSymbol(_) = value
+        {
+            return Ok(());
+        }
+
+        match self.normalize(name, kind, value, span)? {
+            Normal::Context(context) => self.declare_context(&context),
+            Normal::Snapshot(contexts) => contexts
+                .iter()
+                .try_for_each(|context| self.declare_context(context)),
+            Normal::StateMachine(_) => Ok(()),
+        }
+    }
+
+    fn normalize(
+        &self,
+        name: &str,
+        kind: &TypeKind,
+        value: &TypeValue,
+        span: Span,
+    ) -> Result<Normal, Error> {
+        types::normalize(name, kind, value, span, &|name| {
+            self.types.get(name).cloned()
+        })
+    }
+
+    fn declare_context(
+        &mut self,
+        ContextType(label, effect_type): &ContextType,
+    ) -> Result<(), Error> {
+        let kind = match effect_type {
+            EffectType::Int => ContextKind::Int,
+            EffectType::StateMachine | EffectType::Id | EffectType::Empty => {
+                ContextKind::StateMachine(None)
+            }
+            EffectType::TypeSymbolReference(name) => match self.types.get(&name.0) {
+                Some((TypeKind::StateMachine, Some(value))) => {
+                    match self.normalize(&name.0, &TypeKind::StateMachine, value, label.1 .0)? {
+                        Normal::StateMachine(machine) => ContextKind::StateMachine(Some(machine)),
+                        _ => ContextKind::StateMachine(None),
+                    }
+                }
+                Some((TypeKind::StateMachine, None)) => ContextKind::StateMachine(None),
+                Some((kind, _)) => {
+                    return Err(Error::new(
+                        ErrorKind::Type,
+                        &format!(
+                            "context `{}` must be Int or a state machine, `{}` is a {}",
+                            label.0,
+                            name.0,
+                            kind_name(kind)
+                        ),
+                        label.1 .0,
+                    ))
+                }
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::UnknownSymbol,
+                        &format!("unknown type `{}`", name.0),
+                        label.1 .0,
+                    ))
+                }
+            },
+        };
+
+        match self.contexts.get(&label.0) {
+            Some(declared) if *declared != kind => Err(Error::new(
+                ErrorKind::DuplicateSymbol,
+                &format!(
+                    "context `{}` is already declared with another type",
+                    label.0
+                ),
+                label.1 .0,
+            )),
+            _ => {
+                self.contexts.insert(label.0.clone(), kind);
+
+                Ok(())
+            }
+        }
+    }
+
+    fn scope(&mut self, scope: &ScopeValue) -> Result<Type, Error> {
+        self.scopes.push(Vec::new());
+        let ty = scope.0.iter().try_fold(Type::Any, |ty, item| match item {
+            ScopeInnerValue::Comment(_) => Ok(ty),
+            ScopeInnerValue::Stmt(stmt) => {
+                self.stmt(stmt)?;
+
+                Ok(ty)
+            }
+            ScopeInnerValue::Expr(expr) => self.expr(expr),
+        });
+        self.scopes.pop();
+
+        ty
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        match stmt {
+            Stmt::Expr(expr) => self.expr(expr).map(|_| ()),
+            Stmt::TypeStmt(TypeExpr::Bind(symbol) | TypeExpr::Assign { symbol, .. })
+                if symbol.exported =>
+            {
+                self.top_level("types can be exported", symbol.location.0)
+            }
+            Stmt::TypeStmt(_) => Ok(()),
+            Stmt::LetStmt(LetExpr::Bind(symbol) | LetExpr::Assign { symbol, .. })
+                if symbol.exported && self.scopes.len() > 1 =>
+            {
+                self.top_level("values can be exported", symbol.location.0)
+            }
+            Stmt::LetStmt(LetExpr::Bind(symbol)) => {
+                let ty = self.declared(symbol)?;
+                self.bind(symbol, ty);
+
+                Ok(())
+            }
+            Stmt::LetStmt(LetExpr::Assign { symbol, expr }) => {
+                let ty = self.expr(expr)?;
+                let ty = self.fit(symbol, ty)?;
+                self.bind(symbol, ty);
+
+                Ok(())
+            }
+            Stmt::ScopeStmt(scope) => self.scope(scope).map(|_| ()),
+            Stmt::Import(import) => {
+                self.top_level("files can be imported", import.location.0)?;
+                match self.namespaces.contains(&import.namespace.0) {
+                    true => Ok(()),
+                    false => Err(Error::new(
+                        ErrorKind::Import,
+                        &format!(
+                            "`{}` can only be imported when the document is checked as a file",
+                            import.path
+                        ),
+                        import.location.0,
+                    )),
+                }
+            }
+        }
+    }
+
+    fn bind(&mut self, symbol: &ValueSymbol, ty: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((symbol.name.0.clone(), ty));
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Type, Error> {
+        match expr {
+            Expr::Id(value) => self.value(value),
+            Expr::Reference(reference) => self
+                .scopes
+                .iter()
+                .rev()
+                .flat_map(|scope| scope.iter().rev())
+                .find(|(name, _)| *name == (reference.0).0)
+                .map(|(_, ty)| ty.clone())
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::UnknownSymbol,
+                        &format!("unknown symbol `{}`", (reference.0).0),
+                        reference.1 .0,
+                    )
+                }),
+            Expr::Call(callee, args, location) => {
+                let callee = self.expr(callee)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.expr(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                call(callee, &args).map_err(|err| Error::new(ErrorKind::Type, &err, location.0))
+            }
+            Expr::Output(_, expr, _) => self.expr(expr),
+            Expr::Op(op, lhs, rhs, location) => {
+                let lhs = self.expr(lhs)?;
+                let rhs = self.expr(rhs)?;
+
+                self.op(op, lhs, rhs)
+                    .map_err(|err| Error::new(ErrorKind::Type, &err, location.0))
+            }
+        }
+    }
+
+    fn op(&self, op: &Op, lhs: Type, rhs: Type) -> Result<Type, String> {
+        match (op, lhs, rhs) {
+            (Op::Or, lhs, rhs) => choice(lhs, rhs),
+            (op, Type::Choice(types), rhs) => types
+                .into_iter()
+                .map(|ty| self.op(op, ty, rhs.clone()))
+                .collect::<Result<_, _>>()
+                .map(Type::Choice),
+            (op, lhs, Type::Choice(types)) => types
+                .into_iter()
+                .map(|ty| self.op(op, lhs.clone(), ty))
+                .collect::<Result<_, _>>()
+                .map(Type::Choice),
+            (Op::Compose, Type::Any, ty) | (Op::Compose, ty, Type::Any) => Ok(ty),
+            (Op::Compose, Type::Effect(lhs), Type::Effect(rhs)) => {
+                compose(lhs, rhs).map(Type::Effect)
+            }
+            (Op::Compose, Type::Slice(mut lhs), Type::Slice(rhs)) => {
+                lhs.extend(rhs);
+
+                Ok(Type::Slice(lhs))
+            }
+            (Op::Compose, Type::ContextEffect(lhs), Type::ContextEffect(rhs)) if lhs == rhs => {
+                Ok(Type::ContextEffect(lhs))
+            }
+            (Op::Compose, Type::ContextEffect(lhs), Type::ContextEffect(rhs)) => Err(format!(
+                "cannot compose effects on different contexts `{}` and `{}`",
+                lhs, rhs
+            )),
+            (Op::Apply, Type::Context(label), Type::Effect(effect)) => {
+                self.fits_context(&label, &effect)?;
+
+                Ok(Type::ContextEffect(label))
+            }
+            (Op::Reduce, Type::Slice(slice), Type::Snapshot(mut snapshot)) => {
+                snapshot.extend(slice);
+
+                Ok(Type::Snapshot(snapshot))
+            }
+            (Op::ReduceAll, Type::Slice(slice), Type::Snapshot(mut snapshot)) => {
+                snapshot.extend(slice);
+
+                Ok(Type::Routes(snapshot))
+            }
+            (Op::Push, Type::Slice(mut slice), Type::ContextEffect(label)) => {
+                slice.insert(label);
+
+                Ok(Type::Slice(slice))
+            }
+            (Op::Add, Type::Int, Type::Int) => Ok(Type::Int),
+            (Op::Add, Type::Effect(EffectShape::Add), Type::Effect(EffectShape::Add)) => {
+                Ok(Type::Effect(EffectShape::Add))
+            }
+            (Op::Apply | Op::Reduce | Op::ReduceAll | Op::Push | Op::Add, Type::Any, _)
+            | (Op::Apply | Op::Reduce | Op::ReduceAll | Op::Push | Op::Add, _, Type::Any) => {
+                Ok(Type::Any)
+            }
+            (Op::Compose, lhs, rhs) => Err(format!("cannot compose {} with {}", lhs, rhs)),
+            (Op::Apply, lhs, rhs) => Err(format!(
+                "`apply` expects a context and an effect, found {} and {}",
+                lhs, rhs
+            )),
+            (Op::Reduce, lhs, rhs) => Err(format!(
+                "`reduce` expects a slice and a snapshot, found {} and {}",
+                lhs, rhs
+            )),
+            (Op::ReduceAll, lhs, rhs) => Err(format!(
+                "`reduceAll` expects a slice and a snapshot, found {} and {}",
+                lhs, rhs
+            )),
+            (Op::Push, lhs, rhs) => Err(format!(
+                "`<<` expects a slice and a context effect, found {} and {}",
+                lhs, rhs
+            )),
+            (Op::Add, lhs, rhs) => Err(format!(
+                "`+` expects two Ints or two add effects, found {} and {}",
+                lhs, rhs
+            )),
+        }
+    }
+
+    fn value(&mut self, value: &Value) -> Result<Type, Error> {
+        match value {
+            Value::Id | Value::Empty => Ok(Type::Any),
+            Value::Effect(effect) => Ok(Type::Effect(shape(effect))),
+            Value::Context(label) => {
+                self.context(label)?;
+
+                Ok(Type::Context(label.0.clone()))
+            }
+            Value::ContextEffect(context_effect) => {
+                self.context_effect(context_effect).map(Type::ContextEffect)
+            }
+            Value::Slice(slice) => self.slice(slice).map(Type::Slice),
+            Value::Snapshot(snapshot) => self.snapshot(snapshot),
+            Value::Scope(scope) => self.scope(scope),
+            Value::Int(_) => Ok(Type::Int),
+            Value::Function(closure) => {
+                closure.alternatives.iter().try_for_each(|function| {
+                    self.scopes.push(
+                        function
+                            .args
+                            .iter()
+                            .filter_map(|pattern| match pattern {
+                                Pattern::Symbol(name) => Some((name.0.clone(), Type::Any)),
+                                _ => None,
+                            })
+                            .collect(),
+                    );
+                    let ty = self.scope(&function.scope);
+                    self.scopes.pop();
+
+                    ty.map(|_| ())
+                })?;
+
+                Ok(Type::Function(
+                    vec![Type::Any; closure.arity()],
+                    Box::new(Type::Any),
+                ))
+            }
+            Value::Choice(values) => values
+                .iter()
+                .map(|value| self.value(value))
+                .collect::<Result<_, _>>()
+                .map(Type::Choice),
+            Value::Routes(routes) => Ok(Type::Routes(
+                routes
+                    .iter()
+                    .flat_map(|route| route.snapshot.0.iter())
+                    .map(|SnapshotValueItem(label, _)| label.0.clone())
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Checks the effects of a slice, including every route of its branches,
+    /// and returns the contexts they act on.
+    fn slice(&self, Slice(items): &Slice) -> Result<BTreeSet<String>, Error> {
+        items.iter().try_fold(BTreeSet::new(), |mut acc, item| {
+            match item {
+                SliceItem::ContextEffect(context_effect) => {
+                    acc.insert(self.context_effect(context_effect)?);
+                }
+                SliceItem::Branch(Branch { routes }) => {
+                    let mut labels = BTreeSet::new();
+                    for (label, slice) in routes {
+                        if !labels.insert(&label.0) {
+                            return Err(Error::new(
+                                ErrorKind::DuplicateSymbol,
+                                &format!("route `{}` is already in this branch", label.0),
+                                label.1 .0,
+                            ));
+                        }
+                        acc.extend(self.slice(slice)?);
+                    }
+                }
+            }
+
+            Ok(acc)
+        })
+    }
+
+    fn context(&self, label: &ContextLabel) -> Result<&ContextKind, Error> {
+        self.contexts.get(&label.0).ok_or_else(|| {
+            Error::new(
+                ErrorKind::UnknownSymbol,
+                &format!("unknown context `{}`", label.0),
+                label.1 .0,
+            )
+        })
+    }
+
+    /// Checks a context effect literal and returns its context.
+    fn context_effect(
+        &self,
+        ContextEffect(label, effect): &ContextEffect,
+    ) -> Result<String, Error> {
+        self.context(label)?;
+        self.fits_context(&label.0, &shape(effect))
+            .map_err(|err| Error::new(ErrorKind::Type, &err, label.1 .0))?;
+
+        Ok(label.0.clone())
+    }
+
+    fn snapshot(&self, SnapshotValue(items): &SnapshotValue) -> Result<Type, Error> {
+        items
+            .iter()
+            .map(|SnapshotValueItem(label, value)| {
+                let fits = match (self.context(label)?, value) {
+                    (_, SnapshotValueItemValue::Id | SnapshotValueItemValue::Empty) => true,
+                    (ContextKind::Int, SnapshotValueItemValue::Int(_)) => true,
+                    (
+                        ContextKind::StateMachine(states),
+                        SnapshotValueItemValue::StateLabel(state),
+                    ) => match states {
+                        Some(machine) => machine.contains(state),
+                        None => true,
+                    },
+                    _ => false,
+                };
+
+                match fits {
+                    true => Ok(label.0.clone()),
+                    false => Err(Error::new(
+                        ErrorKind::Type,
+                        &format!("the value does not fit context `{}`", label.0),
+                        label.1 .0,
+                    )),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Type::Snapshot)
+    }
+
+    /// `+n` only on `Int` contexts, transitions only on state machines that
+    /// have the states they go through, each reachable from the one before.
+    fn fits_context(&self, label: &str, effect: &EffectShape) -> Result<(), String> {
+        match (self.contexts.get(label), effect) {
+            (None, _) | (_, EffectShape::Neutral) => Ok(()),
+            (Some(ContextKind::Int), EffectShape::Add) => Ok(()),
+            (Some(ContextKind::Int), EffectShape::Transition(_)) => Err(format!(
+                "a transition does not fit context `{}`, which is Int",
+                label
+            )),
+            (Some(ContextKind::StateMachine(_)), EffectShape::Add) => Err(format!(
+                "an add effect does not fit context `{}`, which is a state machine",
+                label
+            )),
+            (Some(ContextKind::StateMachine(None)), EffectShape::Transition(_)) => Ok(()),
+            (Some(ContextKind::StateMachine(Some(machine))), EffectShape::Transition(targets)) => {
+                if let Some(target) = targets.iter().find(|target| !machine.contains(target)) {
+                    return Err(format!("{} is not a state of context `{}`", target, label));
+                }
+                match targets
+                    .windows(2)
+                    .find(|pair| !machine.reachable(&pair[0], &pair[1]))
+                {
+                    Some(pair) => Err(format!(
+                        "{} is not reachable from {} in context `{}`",
+                        pair[1], pair[0], label
+                    )),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// The type of `symbol` when it is bound without a value.
+    fn declared(&self, symbol: &ValueSymbol) -> Result<Type, Error> {
+        match &symbol.kind {
+            BindKeywords::Slice => Ok(Type::Slice(BTreeSet::new())),
+            BindKeywords::Snapshot => Ok(Type::Snapshot(BTreeSet::new())),
+            BindKeywords::Int => Ok(Type::Int),
+            BindKeywords::Function(signature) => match self.snapshot_type(symbol, signature)? {
+                Some(contexts) => Ok(Type::Snapshot(contexts)),
+                None => match signature.0.split_last() {
+                    Some((result, params)) if !params.is_empty() => {
+                        let item = |item: &FunctionSignatureItem| {
+                            self.declared(&ValueSymbol {
+                                kind: item.kind(),
+                                ..symbol.clone()
+                            })
+                        };
+
+                        Ok(Type::Function(
+                            params.iter().map(item).collect::<Result<_, _>>()?,
+                            Box::new(item(result)?),
+                        ))
+                    }
+                    _ => Ok(Type::Any),
+                },
+            },
+            _ => Ok(Type::Any),
+        }
+    }
+
+    /// Whether the value of a `let` matches its declared kind. Returns the
+    /// type the name is bound to.
+    fn fit(&self, symbol: &ValueSymbol, ty: Type) -> Result<Type, Error> {
+        let mismatch = |ty: &Type| {
+            Err(Error::new(
+                ErrorKind::Type,
+                &format!(
+                    "`{}` is declared as {} but its value is {}",
+                    symbol.name.0, symbol.kind, ty
+                ),
+                symbol.location.0,
+            ))
+        };
+
+        match (&symbol.kind, &ty) {
+            (_, Type::Any) => self.declared(symbol),
+            (_, Type::Choice(types)) => {
+                types
+                    .iter()
+                    .try_for_each(|ty| self.fit(symbol, ty.clone()).map(|_| ()))?;
+
+                Ok(ty)
+            }
+            (BindKeywords::Slice, Type::Slice(_))
+            | (BindKeywords::Effect, Type::Effect(_))
+            | (BindKeywords::ContextEffect, Type::ContextEffect(_))
+            | (BindKeywords::Context, Type::Context(_))
+            | (BindKeywords::Snapshot, Type::Snapshot(_))
+            | (BindKeywords::Int, Type::Int) => Ok(ty),
+            // fewer patterns than arguments when the function returns a
+            // function for the rest
+            (BindKeywords::Function(FunctionSignature(items)), Type::Function(params, _))
+                if params.len() < items.len() =>
+            {
+                self.declared(symbol)
+            }
+            (BindKeywords::Function(signature), Type::Snapshot(contexts)) => {
+                match self.snapshot_type(symbol, signature)? {
+                    Some(expected) => {
+                        if let Some(context) = expected.difference(contexts).next() {
+                            return Err(Error::new(
+                                ErrorKind::Type,
+                                &format!(
+                                    "`{}` is missing context `{}` of {}",
+                                    symbol.name.0, context, symbol.kind
+                                ),
+                                symbol.location.0,
+                            ));
+                        }
+                        if let Some(context) = contexts.difference(&expected).next() {
+                            return Err(Error::new(
+                                ErrorKind::Type,
+                                &format!("context `{}` is not part of {}", context, symbol.kind),
+                                symbol.location.0,
+                            ));
+                        }
+
+                        Ok(ty)
+                    }
+                    None => mismatch(&ty),
+                }
+            }
+            _ => mismatch(&ty),
+        }
+    }
+
+    /// The contexts of the `Snapshot` type named by a single item signature.
+    /// `Some` of every declared context when the type has no definition.
+    fn snapshot_type(
+        &self,
+        symbol: &ValueSymbol,
+        FunctionSignature(items): &FunctionSignature,
+    ) -> Result<Option<BTreeSet<String>>, Error> {
+        let name = match items.as_slice() {
+            [FunctionSignatureItem::Reference(name)] => name,
+            _ => return Ok(None),
+        };
+
+        match self.types.get(&name.0) {
+            Some((TypeKind::Snapshot, Some(value))) => {
+                match self.normalize(&name.0, &TypeKind::Snapshot, value, symbol.location.0)? {
+                    Normal::Snapshot(contexts) => Ok(Some(
+                        contexts
+                            .iter()
+                            .map(|ContextType(label, _)| label.0.clone())
+                            .collect(),
+                    )),
+                    _ => Ok(Some(self.contexts.keys().cloned().collect())),
+                }
+            }
+            Some((TypeKind::Snapshot, _)) => Ok(Some(self.contexts.keys().cloned().collect())),
+            Some((kind, _)) => Err(Error::new(
+                ErrorKind::Type,
+                &format!(
+                    "`{}` is a {} and cannot be the type of a value",
+                    name.0,
+                    kind_name(kind)
+                ),
+                symbol.location.0,
+            )),
+            None => Err(Error::new(
+                ErrorKind::UnknownSymbol,
+                &format!("unknown type `{}`", name.0),
+                symbol.location.0,
+            )),
+        }
+    }
+}
+
+/// The type of calling a function of type `callee` with `args`.
+fn call(callee: Type, args: &[Type]) -> Result<Type, String> {
+    let (params, result) = match callee {
+        Type::Any => return Ok(Type::Any),
+        Type::Function(params, result) => (params, result),
+        ty => return Err(format!("{} cannot be called", ty)),
+    };
+    if let Some((index, (param, arg))) = params
+        .iter()
+        .zip(args)
+        .enumerate()
+        .find(|(_, (param, arg))| !admits(param, arg))
+    {
+        return Err(format!(
+            "argument {} is {}, but the function takes {}",
+            index + 1,
+            arg,
+            param
+        ));
+    }
+
+    match args.len().cmp(&params.len()) {
+        Ordering::Less => Ok(Type::Function(params[args.len()..].to_vec(), result)),
+        Ordering::Equal => Ok(*result),
+        Ordering::Greater => call(*result, &args[params.len()..]),
+    }
+}
+
+/// Whether a value of type `ty` can be passed where `expected` is taken.
+/// Only the kinds are compared, the contexts are checked when evaluated.
+fn admits(expected: &Type, ty: &Type) -> bool {
+    match (expected, ty) {
+        (Type::Any, _) | (_, Type::Any) => true,
+        (expected, Type::Choice(types)) => types.iter().all(|ty| admits(expected, ty)),
+        (expected, ty) => mem::discriminant(expected) == mem::discriminant(ty),
+    }
+}
+
+fn compose(lhs: EffectShape, rhs: EffectShape) -> Result<EffectShape, String> {
+    match (lhs, rhs) {
+        (EffectShape::Neutral, shape) | (shape, EffectShape::Neutral) => Ok(shape),
+        (EffectShape::Add, EffectShape::Add) => Ok(EffectShape::Add),
+        (EffectShape::Transition(mut lhs), EffectShape::Transition(rhs)) => {
+            lhs.extend(rhs);
+
+            Ok(EffectShape::Transition(lhs))
+        }
+        (lhs, rhs) => Err(format!(
+            "cannot compose {} with {}",
+            Type::Effect(lhs),
+            Type::Effect(rhs)
+        )),
+    }
+}
+
+/// `lhs | rhs`, where every alternative is an effect or every one is a slice.
+fn choice(lhs: Type, rhs: Type) -> Result<Type, String> {
+    let alternatives = |ty: &Type| match ty {
+        Type::Choice(types) => types.clone(),
+        ty => vec![ty.clone()],
+    };
+    let types = [alternatives(&lhs), alternatives(&rhs)].concat();
+
+    let effects = types
+        .iter()
+        .all(|ty| matches!(ty, Type::Any | Type::Effect(_)));
+    let slices = types
+        .iter()
+        .all(|ty| matches!(ty, Type::Any | Type::Slice(_)));
+
+    match effects || slices {
+        true => Ok(Type::Choice(types)),
+        false => Err(format!(
+            "`|` expects two effects or two slices, found {} and {}",
+            lhs, rhs
+        )),
+    }
+}
+
+fn shape(effect: &Effect) -> EffectShape {
+    match effect {
+        Effect::Id | Effect::Empty => EffectShape::Neutral,
+        Effect::AddEffect(_) => EffectShape::Add,
+        Effect::TransitionEffect(TransitionEffect::StateLabel(state)) => {
+            EffectShape::Transition(vec![state.clone()])
+        }
+        Effect::TransitionEffect(_) => EffectShape::Transition(Vec::new()),
+        // steps are only kept when their kinds compose
+        Effect::Steps(steps) => steps
+            .iter()
+            .map(shape)
+            .try_fold(EffectShape::Neutral, compose)
+            .unwrap_or(EffectShape::Neutral),
+    }
+}
+
+/// Scopes nested in a scope item, for the passes that ignore `let`s.
+pub(crate) fn scopes_of(item: &ScopeInnerValue) -> Vec<&ScopeValue> {
+    fn expr_scopes<'a>(expr: &'a Expr, acc: &mut Vec<&'a ScopeValue>) {
+        match expr {
+            Expr::Id(Value::Scope(scope)) => acc.push(scope),
+            Expr::Op(_, lhs, rhs, _) => {
+                expr_scopes(lhs, acc);
+                expr_scopes(rhs, acc);
+            }
+            _ => {}
+        }
+    }
+
+    let mut acc = Vec::new();
+    match item {
+        ScopeInnerValue::Comment(_)
+        | ScopeInnerValue::Stmt(Stmt::TypeStmt(_))
+        | ScopeInnerValue::Stmt(Stmt::Import(_)) => {}
+        ScopeInnerValue::Stmt(Stmt::ScopeStmt(scope)) => acc.push(scope),
+        ScopeInnerValue::Stmt(Stmt::Expr(expr))
+        | ScopeInnerValue::Stmt(Stmt::LetStmt(LetExpr::Assign { expr, .. }))
+        | ScopeInnerValue::Expr(expr) => expr_scopes(expr, &mut acc),
+        ScopeInnerValue::Stmt(Stmt::LetStmt(LetExpr::Bind(_))) => {}
+    }
+
+    acc
+}
+
+pub(crate) fn kind_name(kind: &TypeKind) -> &'static str {
+    match kind {
+        TypeKind::StateMachine => "StateMachine",
+        TypeKind::Snapshot => "Snapshot",
+        TypeKind::Context => "Context",
+    }
+}
+
+fn type_value_name(value: &TypeValue) -> &'static str {
+    match value {
+        TypeValue::StateMachine(_) => "a state machine",
+        TypeValue::Snapshot(_) => "a snapshot type",
+        TypeValue::Context(_) => "a context",
+        _ => "a type",
+    }
+}
@@ -0,0 +1,162 @@
+use crate::parser::Rule;
+use pest::iterators::Pair;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use syntax_error::SyntaxError;
+
+/// Byte range in the source, end exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Whether `offset` is inside the span or right at its end.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+
+    pub(crate) fn of(pair: &Pair<'_, Rule>) -> Self {
+        Self::new(pair.as_span().start(), pair.as_span().end())
+    }
+
+    /// One-based line and column of the start of the span in `source`.
+    pub fn position(&self, source: &str) -> (usize, usize) {
+        let offset = self.start.min(source.len());
+        let before = source.get(..offset).unwrap_or_default();
+        let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+}
+
+/// Where an AST node came from. Ignored by equality, hashing and
+/// serialization, so nodes still compare by what they mean.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Location(pub Span);
+
+impl Location {
+    pub(crate) fn of(pair: &Pair<'_, Rule>) -> Self {
+        Self(Span::of(pair))
+    }
+}
+
+impl PartialEq for Location {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Location {}
+
+impl Hash for Location {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    Syntax,
+    /// Valid syntax the evaluator does not handle yet.
+    Unsupported,
+    UnknownSymbol,
+    DuplicateSymbol,
+    Type,
+    Evaluation,
+    /// An imported file that cannot be found or read, or imports that go
+    /// around in a cycle.
+    Import,
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::UnknownSymbol => "unknown-symbol",
+            ErrorKind::DuplicateSymbol => "duplicate-symbol",
+            ErrorKind::Type => "type",
+            ErrorKind::Evaluation => "evaluation",
+            ErrorKind::Import => "import",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Span,
+    /// The file the span is in, for errors of `run_file` and `check_file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: &str, span: Span) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+            span,
+            file: None,
+        }
+    }
+
+    /// The error with its span in `path`, unless it is already known to be
+    /// in another file.
+    pub(crate) fn in_file(mut self, path: &Path) -> Self {
+        if self.file.is_none() {
+            self.file = Some(path.to_path_buf());
+        }
+
+        self
+    }
+
+    pub(crate) fn unsupported(pair: &Pair<'_, Rule>) -> Self {
+        Self::new(
+            ErrorKind::Unsupported,
+            &format!("unsupported {:?}", pair.as_rule()),
+            Span::of(pair),
+        )
+    }
+
+    /// One-based line and column of the start of the error in `source`.
+    pub fn position(&self, source: &str) -> (usize, usize) {
+        self.span.position(source)
+    }
+
+    /// The message prefixed with its position in `source`.
+    pub fn render(&self, source: &str) -> String {
+        let (line, column) = self.position(source);
+
+        format!("{}:{}: {}", line, column, self.message)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<pest::error::Error<Rule>> for Error {
+    fn from(err: pest::error::Error<Rule>) -> Self {
+        let err = SyntaxError::from(&err);
+
+        Error::new(
+            ErrorKind::Syntax,
+            &err.message,
+            Span::new(err.start, err.end),
+        )
+    }
+}
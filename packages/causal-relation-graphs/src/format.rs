@@ -0,0 +1,412 @@
+use crate::error::Error;
+use crate::parser::{self, DocumentParser, Rule};
+use pest::iterators::Pair;
+use pest::Parser;
+
+/// Slices and branches that would go past this column are printed one item
+/// per line.
+const WIDTH: usize = 80;
+const INDENT: usize = 2;
+
+/// Prints `source` in the canonical layout. Comments and single blank lines
+/// between items are kept, and the output parses to the same AST.
+///
+/// The printer walks the syntax tree rather than the AST, which drops the
+/// types written on contexts and where comments were.
+pub fn format(source: &str) -> Result<String, Error> {
+    // only sources the parser accepts are formatted, so nothing is lost
+    parser::parse(source)?;
+
+    let document = DocumentParser::parse(Rule::document, source)?
+        .next()
+        .unwrap();
+    let root = document.into_inner().next().unwrap();
+    let inner = root.into_inner().next().unwrap();
+
+    let mut printer = Printer::new(source, WIDTH);
+    if !printer.items(&inner) {
+        printer.out.push('\n');
+    }
+
+    Ok(printer.out)
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    out: String,
+    indent: usize,
+    width: usize,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, width: usize) -> Self {
+        Self {
+            source,
+            out: String::new(),
+            indent: 0,
+            width,
+        }
+    }
+
+    /// Prints `pair` on one line, however long it gets.
+    fn flat(&self, pair: &Pair<'_, Rule>) -> String {
+        let mut printer = Printer {
+            source: self.source,
+            out: String::new(),
+            indent: self.indent,
+            width: usize::MAX,
+        };
+        printer.node(pair);
+
+        printer.out
+    }
+
+    fn column(&self) -> usize {
+        let line = match self.out.rfind('\n') {
+            Some(idx) => &self.out[idx + 1..],
+            None => &self.out,
+        };
+
+        line.chars().count()
+    }
+
+    /// Whether `text` fits on the current line, `extra` being what follows
+    /// it there.
+    fn fits(&self, text: &str, extra: usize) -> bool {
+        !text.contains('\n')
+            && self
+                .column()
+                .saturating_add(text.chars().count())
+                .saturating_add(extra)
+                <= self.width
+    }
+
+    fn indentation(&mut self) {
+        self.out.push_str(&" ".repeat(self.indent));
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.indentation();
+    }
+
+    /// Prints the items of a scope, one per line. Returns whether the last
+    /// one ended its line, which only comments do.
+    fn items(&mut self, inner: &Pair<'_, Rule>) -> bool {
+        let mut prev: Option<(usize, bool)> = None;
+        for item in inner.clone().into_inner() {
+            let (start, end) = self.bounds(&item);
+            match prev {
+                None => self.indentation(),
+                Some((prev_end, ended)) => {
+                    let gap = &self.source[prev_end..start];
+                    let breaks = gap.matches('\n').count().max(gap.matches('\r').count());
+                    if item.as_rule() == Rule::comment && breaks == 0 {
+                        // a comment after an item on the same line
+                        self.out.push(' ');
+                    } else {
+                        if !ended {
+                            self.out.push('\n');
+                        }
+                        if breaks > 1 {
+                            self.out.push('\n');
+                        }
+                        self.indentation();
+                    }
+                }
+            }
+
+            let ended = match item.as_rule() {
+                Rule::comment => {
+                    // the text keeps its line break, as the AST does
+                    self.out.push_str("//");
+                    self.out.push_str(item.as_str());
+                    true
+                }
+                Rule::stmt => {
+                    self.node(&item.clone().into_inner().next().unwrap());
+                    self.out.push(';');
+                    false
+                }
+                _ => {
+                    self.node(&item);
+                    false
+                }
+            };
+            prev = Some((end, ended));
+        }
+
+        matches!(prev, Some((_, true)))
+    }
+
+    /// Where an item starts and ends without the whitespace around it.
+    fn bounds(&self, item: &Pair<'_, Rule>) -> (usize, usize) {
+        let span = item.as_span();
+        let text = span.as_str();
+        let is_space = |c: char| c == ' ' || c == '\n' || c == '\r';
+
+        match item.as_rule() {
+            // `//` is outside of the comment
+            Rule::comment => (
+                span.start() - 2,
+                span.start() + text.trim_end_matches(is_space).len(),
+            ),
+            _ => (
+                span.end() - text.trim_start_matches(is_space).len(),
+                span.start() + text.trim_end_matches(is_space).len(),
+            ),
+        }
+    }
+
+    fn scope(&mut self, inner: &Pair<'_, Rule>) {
+        let items = inner.clone().into_inner().collect::<Vec<_>>();
+        if let [item] = items.as_slice() {
+            if item.as_rule() == Rule::expr {
+                let flat = self.flat(item);
+                if self.fits(&flat, 4) {
+                    self.out.push_str("{ ");
+                    self.out.push_str(&flat);
+                    self.out.push_str(" }");
+                    return;
+                }
+            }
+        }
+
+        self.out.push_str("{\n");
+        self.indent += INDENT;
+        if !self.items(inner) {
+            self.out.push('\n');
+        }
+        self.indent -= INDENT;
+        self.indentation();
+        self.out.push('}');
+    }
+
+    fn slice(&mut self, pair: &Pair<'_, Rule>) {
+        let items = pair.clone().into_inner().collect::<Vec<_>>();
+        if items.is_empty() {
+            self.out.push_str("[]");
+            return;
+        }
+
+        let flat = self.joined(&items, ", ");
+        if self.fits(&flat, 2) {
+            self.out.push('[');
+            self.out.push_str(&flat);
+            self.out.push(']');
+            return;
+        }
+
+        self.out.push('[');
+        self.indent += INDENT;
+        for (idx, item) in items.iter().enumerate() {
+            if idx > 0 {
+                self.out.push(',');
+            }
+            self.newline();
+            self.node(item);
+        }
+        self.indent -= INDENT;
+        self.newline();
+        self.out.push(']');
+    }
+
+    fn branch(&mut self, pair: &Pair<'_, Rule>) {
+        let routes = pair.clone().into_inner().collect::<Vec<_>>();
+        let flat = self.joined(&routes, " | ");
+        if self.fits(&flat, 2) {
+            self.out.push('(');
+            self.out.push_str(&flat);
+            self.out.push(')');
+            return;
+        }
+
+        self.out.push('(');
+        self.indent += INDENT;
+        for (idx, route) in routes.iter().enumerate() {
+            self.newline();
+            if idx > 0 {
+                self.out.push_str("| ");
+            }
+            self.node(route);
+        }
+        self.indent -= INDENT;
+        self.newline();
+        self.out.push(')');
+    }
+
+    /// `pairs` printed flat with `separator` between them.
+    fn joined(&self, pairs: &[Pair<'_, Rule>], separator: &str) -> String {
+        pairs
+            .iter()
+            .map(|pair| self.flat(pair))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    fn list(&mut self, pair: &Pair<'_, Rule>, separator: &str) {
+        for (idx, item) in pair.clone().into_inner().enumerate() {
+            if idx > 0 {
+                self.out.push_str(separator);
+            }
+            self.node(&item);
+        }
+    }
+
+    fn node(&mut self, pair: &Pair<'_, Rule>) {
+        let mut inner = pair.clone().into_inner();
+
+        match pair.as_rule() {
+            Rule::typeStmt => {
+                self.out.push_str("type ");
+                self.node(&inner.next().unwrap());
+            }
+            Rule::letStmt => {
+                self.out.push_str("let ");
+                self.node(&inner.next().unwrap());
+            }
+            Rule::importStmt => {
+                let namespace = inner.next().unwrap();
+                let path = inner.next().unwrap();
+                self.out.push_str(&format!(
+                    "import {} from \"{}\"",
+                    namespace.as_str(),
+                    path.as_str()
+                ));
+            }
+            Rule::exportStmt => {
+                self.out.push_str("export ");
+                self.node(&inner.next().unwrap());
+            }
+            Rule::bindTypeExpr | Rule::bindExpr => self.list(pair, " :: "),
+            Rule::assignTypeExpr | Rule::assignExpr => self.list(pair, " = "),
+            Rule::typeExpr => {
+                for (idx, item) in inner.enumerate() {
+                    match item.as_rule() {
+                        // only a type in parentheses comes first as a typeExpr
+                        Rule::typeExpr if idx == 0 => {
+                            self.out.push('(');
+                            self.node(&item);
+                            self.out.push(')');
+                        }
+                        _ => self.node(&item),
+                    }
+                }
+            }
+            Rule::typeOp | Rule::op => {
+                self.out.push(' ');
+                self.out.push_str(inner.next().unwrap().as_str());
+                self.out.push(' ');
+            }
+            Rule::stateMachineTypeLiteral => self.list(pair, " => "),
+            Rule::fnTypeLiteral => self.list(pair, " -> "),
+            Rule::contextTypeLiteral
+            | Rule::snapshotTypeItemLiteral
+            | Rule::snapshotItemLiteral
+            | Rule::routeLiteral => self.list(pair, ": "),
+            Rule::snapshotTypeLiteral | Rule::snapshotLiteral => {
+                if inner.next().is_none() {
+                    self.out.push_str("{}");
+                } else {
+                    self.out.push_str("{ ");
+                    self.list(pair, ", ");
+                    self.out.push_str(" }");
+                }
+            }
+            Rule::expr => self.list(pair, ""),
+            Rule::outputExpr => {
+                self.out.push_str("Output ");
+                let mut items = inner.collect::<Vec<_>>();
+                let expr = items.pop().unwrap();
+                for label in items {
+                    self.node(&label);
+                    self.out.push(' ');
+                }
+                self.out.push_str("<< ");
+                self.node(&expr);
+            }
+            Rule::term => {
+                let value = inner.next().unwrap();
+                if value.as_rule() == Rule::expr {
+                    self.out.push('(');
+                    self.node(&value);
+                    self.out.push(')');
+                } else {
+                    self.node(&value);
+                }
+                inner.for_each(|call| self.node(&call));
+            }
+            Rule::call => {
+                self.out.push('(');
+                self.list(pair, ", ");
+                self.out.push(')');
+            }
+            Rule::fnExpr => self.list(pair, " | "),
+            Rule::fnLiteral => self.list(pair, " "),
+            Rule::patternExpr => {
+                let item = inner.next().unwrap();
+                if item.as_rule() == Rule::patternExpr {
+                    self.out.push('(');
+                    self.node(&item);
+                    self.out.push(')');
+                } else {
+                    self.node(&item);
+                }
+            }
+            Rule::scopeExpr | Rule::scopeLiteral | Rule::scopeStmt => {
+                let item = inner.next().unwrap();
+                if item.as_rule() == Rule::scopeInner {
+                    self.scope(&item);
+                } else {
+                    self.node(&item);
+                }
+            }
+            Rule::sliceLiteral => self.slice(pair),
+            Rule::branchLiteral => self.branch(pair),
+            Rule::contextEffectLiteral => {
+                self.out.push('(');
+                self.list(pair, ", ");
+                self.out.push(')');
+            }
+            Rule::addEffectLiteral => {
+                let value = inner.next().unwrap();
+                let value = value.as_str();
+                if value.starts_with('-') {
+                    self.out.push_str(&format!("+({})", value));
+                } else {
+                    self.out.push('+');
+                    self.out.push_str(value);
+                }
+            }
+            Rule::transitionEffectExpr => {
+                self.out.push_str("=>");
+                self.node(&inner.next().unwrap());
+            }
+            Rule::calcStmt
+            | Rule::typeLiteral
+            | Rule::stateMachineTypeExpr
+            | Rule::contextedTypeExpr
+            | Rule::snapshotTypeExpr
+            | Rule::stateMachineExpr
+            | Rule::effectTypeExpr
+            | Rule::effectTypeLiteral
+            | Rule::typeKeywords
+            | Rule::fnTypeExpr
+            | Rule::bindValueTypeExpr
+            | Rule::bindKeywords
+            | Rule::contextExpr
+            | Rule::contextLiteral
+            | Rule::contextEffectExpr
+            | Rule::effectExpr
+            | Rule::effectLiteral
+            | Rule::addEffectExpr
+            | Rule::transitionEffectLiteral
+            | Rule::stateExpr
+            | Rule::snapshotValueExpr
+            | Rule::snapshotValueLiteral
+            | Rule::matchExpr => self.node(&inner.next().unwrap()),
+            // literals and names are printed as written
+            _ => self.out.push_str(pair.as_str().trim()),
+        }
+    }
+}
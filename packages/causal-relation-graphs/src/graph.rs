@@ -0,0 +1,422 @@
+use crate::checker::scopes_of;
+use crate::error::{Error, Span};
+use crate::parser::{
+    Branch, ContextEffect, ContextType, Effect, EffectType, Node, ScopeInnerValue,
+    ScopeValue, Slice, SliceItem, SnapshotValue, SnapshotValueItem, SnapshotValueItemValue,
+    StateLabel, Stmt, TransitionEffect, TypeExpr, TypeKind, TypeValue, Value, Vm,
+};
+use crate::types::{self, Normal};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The causal relations of an evaluated document.
+///
+/// Contexts and the states of their state machines are nodes, the effects of
+/// `let` bound slices are edges between them and `let` bound snapshots are
+/// checkpoints pointing at their values.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+    pub kind: NodeKind,
+    /// The context a context or state node belongs to.
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum NodeKind {
+    Context,
+    State,
+    Checkpoint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub label: Option<String>,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EdgeKind {
+    /// A transition the state machine allows.
+    Machine,
+    /// An effect of a slice.
+    Effect,
+    /// A value of a checkpoint.
+    Value,
+}
+
+/// Evaluates `ast` and builds the graph of its types and `let` bindings.
+pub fn graph(ast: &Node) -> Result<Graph, Error> {
+    let (scope, vm) = match ast {
+        Node::Root(scope) => (scope, scope.eval(&Vm::new())?.0),
+    };
+
+    let mut definitions = Vec::new();
+    declare(scope, &mut definitions);
+    let lookup = |name: &str| {
+        definitions
+            .iter()
+            .rev()
+            .find(|(defined, _, _)| defined == name)
+            .map(|(_, kind, value)| (kind.clone(), Some(value.clone())))
+    };
+    let normal = |name: &str| -> Option<Normal> {
+        let (kind, value) = lookup(name)?;
+        types::normalize(name, &kind, value.as_ref()?, Span::default(), &lookup).ok()
+    };
+
+    let mut contexts: Vec<(String, Option<String>)> = Vec::new();
+    definitions
+        .iter()
+        .for_each(|(name, _, _)| match normal(name) {
+            Some(Normal::Context(context)) => declare_context(&context, &mut contexts),
+            Some(Normal::Snapshot(items)) => items
+                .iter()
+                .for_each(|item| declare_context(item, &mut contexts)),
+            _ => {}
+        });
+
+    let mut graph = Graph::default();
+    contexts.iter().for_each(|(context, machine)| {
+        graph.context(context);
+        if let Some(Normal::StateMachine(machine)) = machine.as_deref().and_then(normal) {
+            machine.transitions().iter().for_each(|(from, to)| {
+                let from = match from {
+                    StateLabel::Label(label) => graph.state(context, label),
+                    _ => graph.context(context),
+                };
+                if let StateLabel::Label(label) = to {
+                    let to = graph.state(context, label);
+                    graph.edge(from, to, None, EdgeKind::Machine);
+                }
+            });
+        }
+    });
+
+    vm.bindings()
+        .iter()
+        .for_each(|(name, value)| graph.binding(name, value));
+
+    Ok(graph)
+}
+
+/// Collects the types by name with their kinds and definitions, in the
+/// order they are defined.
+fn declare(scope: &ScopeValue, definitions: &mut Vec<(String, TypeKind, TypeValue)>) {
+    scope.0.iter().for_each(|item| match item {
+        ScopeInnerValue::Stmt(Stmt::TypeStmt(TypeExpr::Assign { symbol, value })) => {
+            definitions.push((symbol.name.0.clone(), symbol.kind.clone(), value.clone()))
+        }
+        _ => scopes_of(item)
+            .into_iter()
+            .for_each(|scope| declare(scope, definitions)),
+    })
+}
+
+fn declare_context(
+    ContextType(label, effect_type): &ContextType,
+    contexts: &mut Vec<(String, Option<String>)>,
+) {
+    if contexts.iter().all(|(name, _)| *name != label.0) {
+        let machine = match effect_type {
+            EffectType::TypeSymbolReference(name) => Some(name.0.clone()),
+            _ => None,
+        };
+        contexts.push((label.0.clone(), machine));
+    }
+}
+
+impl Graph {
+    /// The id of the node for `context`, added on first use.
+    fn context(&mut self, context: &str) -> String {
+        self.node(context, NodeKind::Context, Some(context))
+    }
+
+    fn state(&mut self, context: &str, state: &str) -> String {
+        self.node(state, NodeKind::State, Some(context))
+    }
+
+    fn node(&mut self, label: &str, kind: NodeKind, context: Option<&str>) -> String {
+        let found = self.nodes.iter().find(|node| {
+            node.label == label && node.kind == kind && node.context.as_deref() == context
+        });
+        if let Some(node) = found {
+            return node.id.clone();
+        }
+
+        let id = format!("n{}", self.nodes.len());
+        self.nodes.push(GraphNode {
+            id: id.clone(),
+            label: label.to_string(),
+            kind,
+            context: context.map(str::to_string),
+        });
+
+        id
+    }
+
+    fn edge(&mut self, from: String, to: String, label: Option<String>, kind: EdgeKind) {
+        let edge = GraphEdge {
+            from,
+            to,
+            label,
+            kind,
+        };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    fn binding(&mut self, name: &str, value: &Value) {
+        match value {
+            Value::Slice(slice) => self.slice(name, slice, &mut BTreeMap::new()),
+            Value::ContextEffect(context_effect) => {
+                self.context_effect(name, context_effect, &mut BTreeMap::new())
+            }
+            Value::Snapshot(snapshot) => self.checkpoint(name, snapshot),
+            // a route that stopped at an invalid effect reaches no checkpoint
+            Value::Routes(routes) => routes
+                .iter()
+                .filter(|route| route.error.is_none())
+                .for_each(|route| {
+                    let name = [name.to_string()]
+                        .into_iter()
+                        .chain(route.path.iter().cloned())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    self.checkpoint(&name, &route.snapshot);
+                }),
+            Value::Choice(values) => values
+                .iter()
+                .enumerate()
+                .for_each(|(idx, value)| self.binding(&format!("{}#{}", name, idx + 1), value)),
+            _ => {}
+        }
+    }
+
+    /// Adds the effects of `slice` in causal order. `current` is the node of
+    /// the state each context is in so far, which branches continue from.
+    fn slice(&mut self, name: &str, Slice(items): &Slice, current: &mut BTreeMap<String, String>) {
+        items.iter().for_each(|item| match item {
+            SliceItem::ContextEffect(context_effect) => {
+                self.context_effect(name, context_effect, current)
+            }
+            SliceItem::Branch(Branch { routes }) => routes.iter().for_each(|(label, slice)| {
+                let name = format!("{}/{}", name, label.0);
+                self.slice(&name, slice, &mut current.clone());
+            }),
+        })
+    }
+
+    fn context_effect(
+        &mut self,
+        name: &str,
+        ContextEffect(label, effect): &ContextEffect,
+        current: &mut BTreeMap<String, String>,
+    ) {
+        let context = label.0.as_str();
+        let from = match current.get(context) {
+            Some(from) => from.clone(),
+            None => self.context(context),
+        };
+        let to = match effect {
+            Effect::Id => return,
+            Effect::TransitionEffect(TransitionEffect::StateLabel(StateLabel::Label(state))) => {
+                self.state(context, state)
+            }
+            _ => from.clone(),
+        };
+
+        current.insert(context.to_string(), to.clone());
+        self.edge(
+            from,
+            to,
+            Some(format!("{}: {}", name, effect)),
+            EdgeKind::Effect,
+        );
+    }
+
+    fn checkpoint(&mut self, name: &str, SnapshotValue(items): &SnapshotValue) {
+        let checkpoint = self.node(name, NodeKind::Checkpoint, None);
+        items.iter().for_each(|SnapshotValueItem(label, value)| {
+            let context = label.0.as_str();
+            let (to, label) = match value {
+                SnapshotValueItemValue::StateLabel(StateLabel::Label(state)) => {
+                    (self.state(context, state), None)
+                }
+                SnapshotValueItemValue::StateLabel(state) => {
+                    (self.context(context), Some(state.to_string()))
+                }
+                SnapshotValueItemValue::Int(value) => {
+                    (self.context(context), Some(value.0.to_string()))
+                }
+                SnapshotValueItemValue::Id => (self.context(context), Some("Id".to_string())),
+                SnapshotValueItemValue::Empty => (self.context(context), Some("Empty".to_string())),
+            };
+
+            self.edge(checkpoint.clone(), to, label, EdgeKind::Value);
+        });
+    }
+
+    /// Graphviz DOT, with the nodes of each context in a cluster.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph crg {\n  rankdir=LR;\n");
+        let node = |out: &mut String, indent: &str, node: &GraphNode| {
+            let shape = match node.kind {
+                NodeKind::Context => "ellipse",
+                NodeKind::State => "circle",
+                NodeKind::Checkpoint => "box",
+            };
+            let _ = writeln!(
+                out,
+                "{}{} [label=\"{}\", shape={}];",
+                indent,
+                node.id,
+                dot_escape(&node.label),
+                shape
+            );
+        };
+
+        self.clusters()
+            .iter()
+            .enumerate()
+            .for_each(|(idx, (context, nodes))| {
+                let _ = writeln!(out, "  subgraph cluster_{} {{", idx);
+                let _ = writeln!(out, "    label=\"{}\";", dot_escape(context));
+                nodes.iter().for_each(|item| node(&mut out, "    ", item));
+                out.push_str("  }\n");
+            });
+        self.loose_nodes()
+            .into_iter()
+            .for_each(|item| node(&mut out, "  ", item));
+
+        self.edges.iter().for_each(|edge| {
+            let mut attrs = Vec::new();
+            if let Some(label) = &edge.label {
+                attrs.push(format!("label=\"{}\"", dot_escape(label)));
+            }
+            match edge.kind {
+                EdgeKind::Machine => attrs.push("style=dashed".to_string()),
+                EdgeKind::Effect => {}
+                EdgeKind::Value => attrs.push("style=dotted, arrowhead=none".to_string()),
+            }
+            let _ = match attrs.is_empty() {
+                true => writeln!(out, "  {} -> {};", edge.from, edge.to),
+                false => writeln!(
+                    out,
+                    "  {} -> {} [{}];",
+                    edge.from,
+                    edge.to,
+                    attrs.join(", ")
+                ),
+            };
+        });
+        out.push_str("}\n");
+
+        out
+    }
+
+    /// A Mermaid flowchart, with the nodes of each context in a subgraph.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        let node = |out: &mut String, indent: &str, node: &GraphNode| {
+            let label = mermaid_escape(&node.label);
+            let _ = match node.kind {
+                NodeKind::Context => writeln!(out, "{}{}([\"{}\"])", indent, node.id, label),
+                NodeKind::State => writeln!(out, "{}{}((\"{}\"))", indent, node.id, label),
+                NodeKind::Checkpoint => writeln!(out, "{}{}[\"{}\"]", indent, node.id, label),
+            };
+        };
+
+        self.clusters()
+            .iter()
+            .enumerate()
+            .for_each(|(idx, (context, nodes))| {
+                let _ = writeln!(out, "  subgraph c{} [\"{}\"]", idx, mermaid_escape(context));
+                nodes.iter().for_each(|item| node(&mut out, "    ", item));
+                out.push_str("  end\n");
+            });
+        self.loose_nodes()
+            .into_iter()
+            .for_each(|item| node(&mut out, "  ", item));
+
+        self.edges.iter().for_each(|edge| {
+            let arrow = match edge.kind {
+                EdgeKind::Machine => "-.->",
+                EdgeKind::Effect => "-->",
+                EdgeKind::Value => "-.-",
+            };
+            let _ = match &edge.label {
+                Some(label) => writeln!(
+                    out,
+                    "  {} {}|\"{}\"| {}",
+                    edge.from,
+                    arrow,
+                    mermaid_escape(label),
+                    edge.to
+                ),
+                None => writeln!(out, "  {} {} {}", edge.from, arrow, edge.to),
+            };
+        });
+
+        out
+    }
+
+    /// The nodes of each context that has states, in the order the contexts
+    /// appear.
+    fn clusters(&self) -> Vec<(&str, Vec<&GraphNode>)> {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind == NodeKind::State)
+            .filter_map(|node| node.context.as_deref())
+            .fold(Vec::<&str>::new(), |mut acc, context| {
+                if !acc.contains(&context) {
+                    acc.push(context);
+                }
+                acc
+            })
+            .into_iter()
+            .map(|context| {
+                let nodes = self
+                    .nodes
+                    .iter()
+                    .filter(|node| node.context.as_deref() == Some(context))
+                    .collect();
+
+                (context, nodes)
+            })
+            .collect()
+    }
+
+    /// The nodes outside of clusters.
+    fn loose_nodes(&self) -> Vec<&GraphNode> {
+        let clusters = self.clusters();
+
+        self.nodes
+            .iter()
+            .filter(|node| {
+                clusters
+                    .iter()
+                    .all(|(_, nodes)| !nodes.iter().any(|item| item.id == node.id))
+            })
+            .collect()
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
@@ -1,26 +1,171 @@
 extern crate pest;
 #[macro_use]
 extern crate pest_derive;
-
+extern crate prose_parser;
+extern crate syntax_error;
+
+mod checker;
+mod error;
+mod format;
+mod graph;
+mod module;
 mod parser;
+mod session;
+mod symbols;
+mod syntax;
+mod trace;
+mod types;
+
+pub use checker::check;
+pub use error::{Error, ErrorKind, Span};
+pub use format::format;
+pub use graph::{graph, EdgeKind, Graph, GraphEdge, GraphNode, NodeKind};
+use module::Modules;
+use parser::{Node, Value, Vm};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+pub use session::{Session, SessionBinding};
+pub use symbols::{symbols, Declaration, EffectKind, Reference, SymbolKind, Symbols};
+pub use syntax::{query, syntax_tree, SyntaxMatch, SyntaxNode};
+pub use trace::{Trace, TraceEvent};
+
+pub fn parse(document: &str) -> Result<Node, Error> {
+    parser::parse(document)
+}
 
-use parser::{Node, Vm};
+/// Parses, checks and evaluates `document` and returns its value as JSON.
+/// Documents with `import` need `run_file`.
+pub fn run(document: &str) -> Result<String, Error> {
+    let ast = parse(document)?;
+    check(&ast)?;
 
-pub fn parse(document: &str) -> Result<Node, String> {
-    parser::parse(document)
+    execute_ast(&ast)
+}
+
+/// Like `run`, and the JSON is a report of every `Output` next to the value.
+pub fn run_with_outputs(document: &str) -> Result<String, Error> {
+    let ast = parse(document)?;
+    check(&ast)?;
+    let (vm, value) = evaluate(&ast, &Vm::new())?;
+
+    report(&vm, &value)
+}
+
+/// Like `run`, for the file at `path` and the files it imports. Imports are
+/// looked up next to the importing file, then in each directory of
+/// `search_path`. Only the `Output`s of `path` are reported.
+pub fn run_file(path: &Path, search_path: &[PathBuf]) -> Result<String, Error> {
+    let module = Modules::new(search_path).check(path)?;
+    let (_, value, _) = module.eval(&Vm::new())?;
+
+    to_json(&value).map_err(|err| err.in_file(path))
+}
+
+/// Like `run_with_outputs`, for the file at `path` and the files it imports.
+pub fn run_file_with_outputs(path: &Path, search_path: &[PathBuf]) -> Result<String, Error> {
+    let module = Modules::new(search_path).check(path)?;
+    let (vm, value, _) = module.eval(&Vm::new())?;
+
+    report(&vm, &value).map_err(|err| err.in_file(path))
+}
+
+/// Like `check`, for the file at `path`. The files it imports are
+/// evaluated, for the values they export.
+pub fn check_file(path: &Path, search_path: &[PathBuf]) -> Result<(), Error> {
+    Modules::new(search_path).check(path).map(|_| ())
+}
+
+pub fn execute_ast(ast: &Node) -> Result<String, Error> {
+    to_json(&evaluate(ast, &Vm::new())?.1)
+}
+
+/// Like `run`, and also returns the trace of the evaluation, which goes as
+/// far as the evaluation did when it fails. Documents that do not parse or
+/// check have an empty trace.
+pub fn run_traced(document: &str) -> (Result<String, Error>, Trace) {
+    let vm = Vm::with_trace();
+    let result = parse(document).and_then(|ast| {
+        check(&ast)?;
+
+        to_json(&evaluate(&ast, &vm)?.1)
+    });
+
+    (result, vm.trace())
+}
+
+/// Like `run_traced`, for the file at `path` and the files it imports. The
+/// trace has the evaluation of each imported file before that of `path`.
+pub fn run_file_traced(path: &Path, search_path: &[PathBuf]) -> (Result<String, Error>, Trace) {
+    let vm = Vm::with_trace();
+    let result = Modules::with_vm(search_path, &vm)
+        .check(path)
+        .and_then(|module| module.eval(&vm))
+        .and_then(|(_, value, _)| to_json(&value).map_err(|err| err.in_file(path)));
+
+    (result, vm.trace())
+}
+
+/// What `run_with_outputs` returns: every output in evaluation order, then
+/// the value of the document.
+#[derive(Serialize)]
+struct Report<'a> {
+    outputs: Vec<ReportOutput<'a>>,
+    value: &'a Value,
+}
+
+#[derive(Serialize)]
+struct ReportOutput<'a> {
+    label: &'a str,
+    value: &'a Value,
+}
+
+fn evaluate(ast: &Node, vm: &Vm) -> Result<(Vm, Value), Error> {
+    match ast {
+        Node::Root(scope) => scope.eval(vm),
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, Error> {
+    serde_json::to_string_pretty(value)
+        .map_err(|err| Error::new(ErrorKind::Evaluation, &err.to_string(), Span::default()))
+}
+
+/// `value` and the outputs of `vm` as JSON, in a `Report`.
+fn report(vm: &Vm, value: &Value) -> Result<String, Error> {
+    let outputs = vm.outputs();
+
+    to_json(&Report {
+        outputs: outputs
+            .iter()
+            .map(|(label, value)| ReportOutput { label, value })
+            .collect(),
+        value,
+    })
 }
 
-pub fn run(document: &str) -> Result<String, String> {
-    execute_ast(&parse(document)?)
+/// A `let` binding and the value it evaluated to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Binding {
+    pub name: String,
+    pub value: serde_json::Value,
 }
 
-pub fn execute_ast(ast: &Node) -> Result<String, String> {
-    let mut vm = Vm::new();
-    let value = match ast {
-        Node::Root(scope) => scope.eval(&mut vm),
+/// Evaluates `ast` and returns its `let` bindings in evaluation order.
+pub fn bindings(ast: &Node) -> Result<Vec<Binding>, Error> {
+    let vm = match ast {
+        Node::Root(scope) => scope.eval(&Vm::new())?.0,
     };
 
-    serde_json::to_string_pretty(&value.1).map_err(|err| err.to_string())
+    vm.bindings()
+        .into_iter()
+        .map(|(name, value)| {
+            serde_json::to_value(&value)
+                .map(|value| Binding { name, value })
+                .map_err(|err| {
+                    Error::new(ErrorKind::Evaluation, &err.to_string(), Span::default())
+                })
+        })
+        .collect()
 }
 
 // #[derive(Debug, Clone)]
@@ -0,0 +1,335 @@
+use crate::checker::{Checker, Type};
+use crate::error::{Error, ErrorKind, Span};
+use crate::parser::{
+    self, ContextType, EffectType, Import, Node, ScopeInnerValue, ScopeValue, SnapshotType, Stmt,
+    TypeExpr, TypeSymbol, TypeSymbolName, TypeSymbolReference, TypeValue, Value, ValueSymbol,
+    ValueSymbolName, Vm,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+/// What a file makes visible to the files importing it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Exports {
+    pub(crate) types: Vec<(TypeSymbol, TypeValue)>,
+    pub(crate) values: Vec<(ValueSymbol, Value, Type)>,
+}
+
+impl Exports {
+    /// The exports named `namespace.name`, with the types they refer to
+    /// named the same way.
+    fn qualified(&self, namespace: &str) -> Exports {
+        let qualify = |name: &str| format!("{}.{}", namespace, name);
+        let qualify_context = |ContextType(label, effect_type): &ContextType| {
+            let effect_type = match effect_type {
+                EffectType::TypeSymbolReference(name) => {
+                    EffectType::TypeSymbolReference(TypeSymbolName(qualify(&name.0)))
+                }
+                effect_type => effect_type.clone(),
+            };
+
+            ContextType(label.clone(), effect_type)
+        };
+
+        fn qualify_value(
+            value: &TypeValue,
+            qualify: &dyn Fn(&str) -> String,
+            qualify_context: &dyn Fn(&ContextType) -> ContextType,
+        ) -> TypeValue {
+            let qualify_items = |items: &[TypeValue]| {
+                items
+                    .iter()
+                    .map(|item| qualify_value(item, qualify, qualify_context))
+                    .collect()
+            };
+
+            match value {
+                TypeValue::Context(context) => TypeValue::Context(qualify_context(context)),
+                TypeValue::Snapshot(SnapshotType(contexts)) => TypeValue::Snapshot(SnapshotType(
+                    contexts.iter().map(qualify_context).collect(),
+                )),
+                TypeValue::Reference(TypeSymbolReference(name, location)) => TypeValue::Reference(
+                    TypeSymbolReference(TypeSymbolName(qualify(&name.0)), *location),
+                ),
+                TypeValue::Intersection(items, location) => {
+                    TypeValue::Intersection(qualify_items(items), *location)
+                }
+                TypeValue::Union(items, location) => {
+                    TypeValue::Union(qualify_items(items), *location)
+                }
+                value => value.clone(),
+            }
+        }
+
+        Exports {
+            types: self
+                .types
+                .iter()
+                .map(|(symbol, value)| {
+                    let value = qualify_value(value, &qualify, &qualify_context);
+                    let symbol = TypeSymbol {
+                        name: TypeSymbolName(qualify(&symbol.name.0)),
+                        ..symbol.clone()
+                    };
+
+                    (symbol, value)
+                })
+                .collect(),
+            values: self
+                .values
+                .iter()
+                .map(|(symbol, value, ty)| {
+                    let symbol = ValueSymbol {
+                        name: ValueSymbolName(qualify(&symbol.name.0)),
+                        ..symbol.clone()
+                    };
+
+                    (symbol, value.clone(), ty.clone())
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Loads files and the files they import. Each file is evaluated once, the
+/// first time it is imported, however many files import it.
+pub(crate) struct Modules {
+    search_path: Vec<PathBuf>,
+    loaded: BTreeMap<PathBuf, Rc<Exports>>,
+    /// Imported files are evaluated in it, so they share its trace.
+    vm: Vm,
+    /// The files being loaded, each one imported by the one before it.
+    loading: Vec<(PathBuf, PathBuf)>,
+}
+
+/// A checked file, with the exports of the files it imports.
+pub(crate) struct Module {
+    path: PathBuf,
+    scope: ScopeValue,
+    imports: Vec<Exports>,
+    checker: Checker,
+}
+
+impl Modules {
+    /// Imports are looked up next to the importing file, then in each
+    /// directory of `search_path` in order.
+    pub(crate) fn new(search_path: &[PathBuf]) -> Self {
+        Self::with_vm(search_path, &Vm::new())
+    }
+
+    /// Like `new`, evaluating imported files in `vm`.
+    pub(crate) fn with_vm(search_path: &[PathBuf], vm: &Vm) -> Self {
+        Self {
+            search_path: search_path.to_vec(),
+            loaded: BTreeMap::new(),
+            vm: vm.clone(),
+            loading: Vec::new(),
+        }
+    }
+
+    /// Parses and checks the file at `path`, loading the files it imports
+    /// first.
+    pub(crate) fn check(&mut self, path: &Path) -> Result<Module, Error> {
+        let canonical = path.canonicalize().map_err(|err| {
+            Error::new(
+                ErrorKind::Import,
+                &format!("cannot read `{}`: {}", path.display(), err),
+                Span::default(),
+            )
+            .in_file(path)
+        })?;
+        self.loading.push((canonical, path.to_path_buf()));
+        let module = self.check_loading(path);
+        self.loading.pop();
+
+        module
+    }
+
+    fn check_loading(&mut self, path: &Path) -> Result<Module, Error> {
+        let source = fs::read_to_string(path).map_err(|err| {
+            Error::new(
+                ErrorKind::Import,
+                &format!("cannot read `{}`: {}", path.display(), err),
+                Span::default(),
+            )
+            .in_file(path)
+        })?;
+        let Node::Root(scope) = parser::parse(&source).map_err(|err| err.in_file(path))?;
+
+        let mut checker = Checker::default();
+        let mut imports = Vec::new();
+        for item in &scope.0 {
+            if let ScopeInnerValue::Stmt(Stmt::Import(import)) = item {
+                let exports = self.load(path, import)?.qualified(&import.namespace.0);
+                checker
+                    .import(import, &exports)
+                    .map_err(|err| err.in_file(path))?;
+                imports.push(exports);
+            }
+        }
+        checker.input(&scope).map_err(|err| err.in_file(path))?;
+        check_exports(&scope).map_err(|err| err.in_file(path))?;
+
+        Ok(Module {
+            path: path.to_path_buf(),
+            scope,
+            imports,
+            checker,
+        })
+    }
+
+    /// The exports of the file `import` in `importer` refers to, evaluating
+    /// it unless it has been already.
+    fn load(&mut self, importer: &Path, import: &Import) -> Result<Rc<Exports>, Error> {
+        let path = self.resolve(importer, import)?;
+        let canonical = path.canonicalize().map_err(|err| {
+            Error::new(
+                ErrorKind::Import,
+                &format!("cannot read `{}`: {}", path.display(), err),
+                import.location.0,
+            )
+            .in_file(importer)
+        })?;
+
+        if let Some(exports) = self.loaded.get(&canonical) {
+            return Ok(exports.clone());
+        }
+        if let Some(idx) = self
+            .loading
+            .iter()
+            .position(|(loading, _)| *loading == canonical)
+        {
+            let cycle = self.loading[idx..]
+                .iter()
+                .map(|(_, path)| path.display().to_string())
+                .chain([path.display().to_string()])
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(Error::new(
+                ErrorKind::Import,
+                &format!("imports go around in a cycle: {}", cycle),
+                import.location.0,
+            )
+            .in_file(importer));
+        }
+
+        let module = self.check(&path)?;
+        let (_, _, exports) = module.eval(&self.vm)?;
+        let exports = Rc::new(exports);
+        self.loaded.insert(canonical, exports.clone());
+
+        Ok(exports)
+    }
+
+    /// The path of the file `import` refers to, relative to the directory
+    /// of `importer` or of the search path.
+    fn resolve(&self, importer: &Path, import: &Import) -> Result<PathBuf, Error> {
+        let dir = importer.parent().unwrap_or_else(|| Path::new(""));
+
+        [dir]
+            .into_iter()
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| {
+                dir.join(&import.path)
+                    .components()
+                    .filter(|component| *component != Component::CurDir)
+                    .collect::<PathBuf>()
+            })
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Import,
+                    &format!("cannot find `{}`", import.path),
+                    import.location.0,
+                )
+                .in_file(importer)
+            })
+    }
+}
+
+impl Module {
+    /// Evaluates the file with its imports bound. Returns the `Vm` after the
+    /// file, its value and what it exports.
+    pub(crate) fn eval(&self, vm: &Vm) -> Result<(Vm, Value, Exports), Error> {
+        let mut vm = vm.clone();
+        vm.push_stack();
+        self.imports.iter().for_each(|exports| vm.import(exports));
+
+        let (mut vm, value) = self
+            .scope
+            .eval_in(&vm)
+            .map_err(|err| err.in_file(&self.path))?;
+
+        let mut exports = vm.exported();
+        exports.values.iter_mut().for_each(|(symbol, _, ty)| {
+            if let Some(checked) = self.checker.value_type(&symbol.name.0) {
+                *ty = checked;
+            }
+        });
+        vm.pop_stack();
+
+        Ok((vm, value, exports))
+    }
+}
+
+/// Exported types may only refer to other exported types of the same file,
+/// so that the files importing them can name every type they use.
+fn check_exports(scope: &ScopeValue) -> Result<(), Error> {
+    let exported = scope
+        .0
+        .iter()
+        .filter_map(|item| match item {
+            ScopeInnerValue::Stmt(Stmt::TypeStmt(
+                TypeExpr::Bind(symbol) | TypeExpr::Assign { symbol, .. },
+            )) if symbol.exported => Some(symbol.name.0.as_str()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+
+    scope.0.iter().try_for_each(|item| match item {
+        ScopeInnerValue::Stmt(Stmt::TypeStmt(TypeExpr::Assign { symbol, value }))
+            if symbol.exported =>
+        {
+            let mut used = Vec::new();
+            uses(value, &mut used);
+
+            used.into_iter()
+                .try_for_each(|name| match exported.contains(name) {
+                    true => Ok(()),
+                    false => Err(Error::new(
+                        ErrorKind::Type,
+                        &format!(
+                            "exported type `{}` uses `{}`, which is not exported",
+                            symbol.name.0, name
+                        ),
+                        symbol.location.0,
+                    )),
+                })
+        }
+        _ => Ok(()),
+    })
+}
+
+/// The names of the types `value` refers to.
+fn uses<'a>(value: &'a TypeValue, used: &mut Vec<&'a str>) {
+    let context = |ContextType(_, effect_type): &'a ContextType, used: &mut Vec<&'a str>| {
+        if let EffectType::TypeSymbolReference(name) = effect_type {
+            used.push(name.0.as_str());
+        }
+    };
+
+    match value {
+        TypeValue::Context(item) => context(item, used),
+        TypeValue::Snapshot(SnapshotType(items)) => {
+            items.iter().for_each(|item| context(item, used))
+        }
+        TypeValue::Reference(TypeSymbolReference(name, _)) => used.push(name.0.as_str()),
+        TypeValue::Intersection(items, _) | TypeValue::Union(items, _) => {
+            items.iter().for_each(|item| uses(item, used))
+        }
+        _ => {}
+    }
+}
@@ -1,20 +1,29 @@
+use crate::checker::Type;
+use crate::error::{Error, ErrorKind, Location, Span};
+use crate::module::Exports;
+use crate::trace::{Trace, TraceEvent, Tracer};
+use crate::types::{self, Machine, Normal};
 use pest::iterators::Pair;
 use pest::Parser;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Parser)]
 #[grammar = "crg.pest"]
 pub struct DocumentParser;
 
-pub fn parse(document: &str) -> Result<Node, String> {
-    DocumentParser::parse(Rule::document, document)
-        .map(|pairs| {
-            let mut nodes = pairs.map(|item| Node::from(&item)).collect::<Vec<Node>>();
+pub fn parse(document: &str) -> Result<Node, Error> {
+    let mut pairs = DocumentParser::parse(Rule::document, document)?;
 
-            nodes.pop().unwrap().clone()
-        })
-        .map_err(|err| err.to_string())
+    to_node(&pairs.next().unwrap())
+}
+
+/// Parses one input of a REPL, which may end with a statement.
+pub fn parse_input(input: &str) -> Result<ScopeValue, Error> {
+    let mut pairs = DocumentParser::parse(Rule::replInput, input)?;
+    let pair = pairs.next().unwrap().into_inner().next().unwrap();
+
+    parse_scope_inner(&pair)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -40,6 +49,17 @@ pub enum Stmt {
     TypeStmt(TypeExpr),
     LetStmt(LetExpr),
     ScopeStmt(ScopeValue),
+    Import(Import),
+}
+
+/// `import namespace from "path"`, which makes the exports of the file at
+/// `path` visible as `namespace.name`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Import {
+    pub(crate) namespace: ValueSymbolName,
+    pub(crate) path: String,
+    #[serde(skip)]
+    pub(crate) location: Location,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -50,13 +70,19 @@ pub enum LetExpr {
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ValueSymbol {
-    name: ValueSymbolName,
-    kind: BindKeywords,
+    pub(crate) name: ValueSymbolName,
+    pub(crate) kind: BindKeywords,
+    /// Declared with `export`, so files importing this one can use it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) exported: bool,
+    #[serde(skip)]
+    pub(crate) location: Location,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BindKeywords {
     Function(FunctionSignature),
+    Int,
     Slice,
     Effect,
     ContextEffect,
@@ -64,16 +90,55 @@ pub enum BindKeywords {
     Snapshot,
 }
 
+impl fmt::Display for BindKeywords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindKeywords::Function(FunctionSignature(items)) => write!(
+                f,
+                "{}",
+                items
+                    .iter()
+                    .map(|FunctionSignatureItem::Reference(name)| name.0.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ),
+            BindKeywords::Int => write!(f, "Int"),
+            BindKeywords::Slice => write!(f, "Slice"),
+            BindKeywords::Effect => write!(f, "Effect"),
+            BindKeywords::ContextEffect => write!(f, "ContextEffect"),
+            BindKeywords::Context => write!(f, "Context"),
+            BindKeywords::Snapshot => write!(f, "Snapshot"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct FunctionSignature(Vec<FunctionSignatureItem>);
+pub struct FunctionSignature(pub(crate) Vec<FunctionSignatureItem>);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FunctionSignatureItem {
     Reference(TypeSymbolName),
 }
 
+impl FunctionSignatureItem {
+    /// What a value of this item is bound as, as if it were a `let` of it.
+    pub(crate) fn kind(&self) -> BindKeywords {
+        let FunctionSignatureItem::Reference(name) = self;
+
+        match name.0.as_str() {
+            "Slice" => BindKeywords::Slice,
+            "Effect" => BindKeywords::Effect,
+            "ContextEffect" => BindKeywords::ContextEffect,
+            "Context" => BindKeywords::Context,
+            "Snapshot" => BindKeywords::Snapshot,
+            "Int" => BindKeywords::Int,
+            _ => BindKeywords::Function(FunctionSignature(vec![self.clone()])),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ValueSymbolName(String);
+pub struct ValueSymbolName(pub(crate) String);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TypeExpr {
@@ -94,13 +159,19 @@ pub enum TypeValue {
     StateMachine(StateMachine),
     Snapshot(SnapshotType),
     Context(ContextType),
+    /// Another type by name, which may be the one being defined.
+    Reference(TypeSymbolReference),
+    /// `A & B`, what both types allow.
+    Intersection(Vec<TypeValue>, #[serde(skip)] Location),
+    /// `A | B`, what either type allows.
+    Union(Vec<TypeValue>, #[serde(skip)] Location),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct SnapshotType(Vec<ContextType>);
+pub struct SnapshotType(pub(crate) Vec<ContextType>);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct SnapshotValue(Vec<SnapshotValueItem>);
+pub struct SnapshotValue(pub(crate) Vec<SnapshotValueItem>);
 
 impl SnapshotValue {
     fn find_by_label(&self, label: &ContextLabel) -> Option<&SnapshotValueItem> {
@@ -115,7 +186,7 @@ impl SnapshotValue {
         let idx = self
             .0
             .iter()
-            .position(|SnapshotValueItem(label, _)| label == label);
+            .position(|SnapshotValueItem(item, _)| item == label);
         match idx {
             Some(idx) => {
                 ret.0[idx] = SnapshotValueItem(label.clone(), value.clone());
@@ -128,7 +199,7 @@ impl SnapshotValue {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct SnapshotValueItem(ContextLabel, SnapshotValueItemValue);
+pub struct SnapshotValueItem(pub(crate) ContextLabel, pub(crate) SnapshotValueItemValue);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SnapshotValueItemValue {
@@ -139,34 +210,64 @@ pub enum SnapshotValueItemValue {
 }
 
 impl SnapshotValueItemValue {
-    fn apply(&self, effect: &Effect) -> SnapshotValueItemValue {
-        match self {
-            SnapshotValueItemValue::Id => SnapshotValueItemValue::Empty,
+    /// Applies `effect` to the value of a context whose states are
+    /// `machine`, if it is a declared state machine.
+    fn apply(
+        &self,
+        effect: &Effect,
+        machine: Option<&Machine>,
+    ) -> Result<SnapshotValueItemValue, String> {
+        if let Effect::Steps(steps) = effect {
+            return steps
+                .iter()
+                .try_fold(self.clone(), |value, step| value.apply(step, machine));
+        }
+
+        let value = match self {
+            // a state machine starts at `Id`
+            SnapshotValueItemValue::Id => match effect {
+                Effect::TransitionEffect(_) => {
+                    SnapshotValueItemValue::StateLabel(StateLabel::Id).apply(effect, machine)?
+                }
+                _ => SnapshotValueItemValue::Empty,
+            },
             SnapshotValueItemValue::Empty => SnapshotValueItemValue::Empty,
             SnapshotValueItemValue::Int(v) => match effect {
                 Effect::Id => SnapshotValueItemValue::Int(IntLiteral(v.0)),
-                Effect::AddEffect(vv) => SnapshotValueItemValue::Int(IntLiteral(v.0 + vv.0)),
+                Effect::AddEffect(vv) => match v.0.checked_add(vv.0) {
+                    Some(sum) => SnapshotValueItemValue::Int(IntLiteral(sum)),
+                    None => return Err(format!("{} + {} overflows", v.0, vv.0)),
+                },
                 _ => SnapshotValueItemValue::Empty,
             },
             SnapshotValueItemValue::StateLabel(v) => match effect {
                 Effect::Id => SnapshotValueItemValue::StateLabel(v.clone()),
-                Effect::TransitionEffect(_vv) => {
-                    unimplemented!()
+                Effect::TransitionEffect(TransitionEffect::Id) => {
+                    SnapshotValueItemValue::StateLabel(v.clone())
                 }
+                Effect::TransitionEffect(TransitionEffect::StateLabel(to)) => match machine {
+                    Some(machine) if !machine.reachable(v, to) => {
+                        return Err(format!("{} cannot transition to {}", v, to))
+                    }
+                    _ => SnapshotValueItemValue::StateLabel(to.clone()),
+                },
                 _ => SnapshotValueItemValue::Empty,
             },
-        }
+        };
+
+        Ok(value)
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ContextType(ContextLabel, EffectType);
+pub struct ContextType(pub(crate) ContextLabel, pub(crate) EffectType);
 
 // #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 // pub struct ContextValue(ContextReference);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
-pub struct ContextLabel(String);
+#[serde(transparent)]
+pub struct ContextLabel(pub(crate) String, #[serde(skip)] pub(crate) Location);
 
 // #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 // pub struct ContextReference(String);
@@ -181,10 +282,25 @@ pub enum EffectType {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct TypeSymbolReference(String);
+#[serde(transparent)]
+pub struct TypeSymbolReference(
+    pub(crate) TypeSymbolName,
+    #[serde(skip)] pub(crate) Location,
+);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct StateMachine(Vec<StateLabel>);
+pub struct StateMachine(pub(crate) Vec<StateLabel>);
+
+impl StateMachine {
+    pub fn contains(&self, state: &StateLabel) -> bool {
+        self.0.contains(state)
+    }
+
+    /// Whether `to` can be reached from `from` in one or more transitions.
+    pub fn reachable(&self, from: &StateLabel, to: &StateLabel) -> bool {
+        Machine::from(self).reachable(from, to)
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StateLabel {
@@ -193,34 +309,75 @@ pub enum StateLabel {
     Label(String),
 }
 
+impl fmt::Display for StateLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateLabel::Id => write!(f, "Id"),
+            StateLabel::Empty => write!(f, "Empty"),
+            StateLabel::Label(label) => write!(f, "`{}`", label),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TypeSymbol {
-    name: TypeSymbolName,
-    kind: TypeKind,
+    pub(crate) name: TypeSymbolName,
+    pub(crate) kind: TypeKind,
+    /// Declared with `export`, so files importing this one can use it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) exported: bool,
+    #[serde(skip)]
+    pub(crate) location: Location,
 }
 
-trait Assignable<T> {
+pub(crate) trait Assignable<T> {
     fn assignable(&self, value: &T) -> bool;
 }
 
 impl Assignable<TypeValue> for TypeSymbol {
-    fn assignable(&self, _value: &TypeValue) -> bool {
-        // TODO: 実装
-
-        true
+    fn assignable(&self, value: &TypeValue) -> bool {
+        matches!(
+            (&self.kind, value),
+            (_, TypeValue::Id | TypeValue::Empty | TypeValue::TypeSymbol(_))
+                // the kinds of references and combined types are checked
+                // when they are normalized
+                | (
+                    _,
+                    TypeValue::Reference(_) | TypeValue::Intersection(..) | TypeValue::Union(..)
+                )
+                | (TypeKind::StateMachine, TypeValue::StateMachine(_))
+                | (TypeKind::Snapshot, TypeValue::Snapshot(_))
+                | (TypeKind::Context, TypeValue::Context(_))
+        )
     }
 }
 
 impl Assignable<Value> for ValueSymbol {
-    fn assignable(&self, _value: &Value) -> bool {
-        // TODO: 実装
-
-        true
+    fn assignable(&self, value: &Value) -> bool {
+        self.kind.assignable(value)
+    }
+}
+
+impl Assignable<Value> for BindKeywords {
+    fn assignable(&self, value: &Value) -> bool {
+        match (self, value) {
+            (_, Value::Id | Value::Empty) => true,
+            (BindKeywords::Slice, Value::Slice(_)) => true,
+            (BindKeywords::Effect, Value::Effect(_)) => true,
+            (BindKeywords::ContextEffect, Value::ContextEffect(_)) => true,
+            (BindKeywords::Context, Value::Context(_)) => true,
+            (BindKeywords::Snapshot, Value::Snapshot(_)) => true,
+            (BindKeywords::Int, Value::Int(_)) => true,
+            (_, Value::Choice(values)) => values.iter().all(|value| self.assignable(value)),
+            // named types are resolved by the checker
+            (BindKeywords::Function(_), _) => true,
+            _ => false,
+        }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct TypeSymbolName(String);
+pub struct TypeSymbolName(pub(crate) String);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TypeKind {
@@ -232,24 +389,35 @@ pub enum TypeKind {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Expr {
     Id(Value),
-    Op(Op, Box<Expr>, Box<Expr>),
+    Op(Op, Box<Expr>, Box<Expr>, #[serde(skip)] Location),
     Reference(ValueSymbolReference),
+    Call(Box<Expr>, Vec<Expr>, #[serde(skip)] Location),
+    /// `Output label << expr` adds the value of `expr` to the outputs of
+    /// the run and evaluates to it.
+    Output(Option<String>, Box<Expr>, #[serde(skip)] Location),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ValueSymbolReference(ValueSymbolName);
+#[serde(transparent)]
+pub struct ValueSymbolReference(
+    pub(crate) ValueSymbolName,
+    #[serde(skip)] pub(crate) Location,
+);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Op {
     Compose,
     Apply,
     Reduce,
+    ReduceAll,
     Push,
+    Add,
+    Or,
 }
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct AddEffect(i64);
+pub struct AddEffect(pub(crate) i64);
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct IntLiteral(i64);
+pub struct IntLiteral(pub(crate) i64);
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TransitionEffect {
     Id,
@@ -271,6 +439,12 @@ pub enum Value {
     Slice(Slice),
     Snapshot(SnapshotValue),
     Scope(ScopeValue),
+    Int(IntLiteral),
+    Function(Closure),
+    /// The alternatives of `|`, each one a possible outcome.
+    Choice(Vec<Value>),
+    /// The outcomes of `reduceAll`.
+    Routes(Vec<Route>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -280,213 +454,568 @@ pub enum Effect {
     Empty,
     AddEffect(AddEffect),
     TransitionEffect(TransitionEffect),
+    /// Effects composed without folding them into one, because the fold
+    /// would skip checking an earlier step, as in `=>`a` compose Empty`.
+    Steps(Vec<Effect>),
 }
 
 impl Effect {
-    pub fn compose(&self, rhs: &Effect) -> Effect {
+    pub fn compose(&self, rhs: &Effect) -> Result<Effect, String> {
         match (self, rhs) {
+            (lhs, Effect::Steps(steps)) => steps
+                .iter()
+                .try_fold(lhs.clone(), |effect, step| effect.compose(step)),
+            (Effect::Steps(steps), rhs) => {
+                let (last, steps) = steps.split_last().unwrap();
+                let last = match last.compose(rhs)? {
+                    Effect::Steps(last) => last,
+                    last => vec![last],
+                };
+
+                Ok(Effect::Steps([steps, &last].concat()))
+            }
             (Effect::AddEffect(lhs), rhs) => lhs.compose(rhs),
             (Effect::TransitionEffect(lhs), rhs) => lhs.compose(rhs),
-            (Effect::Id, rhs) => rhs.clone(),
-            _ => Effect::Empty,
+            (Effect::Id, rhs) => Ok(rhs.clone()),
+            _ => Ok(Effect::Empty),
+        }
+    }
+
+    /// Composes transitions only when `rhs` can be reached from `self` in
+    /// `machine`.
+    pub fn compose_on(
+        &self,
+        rhs: &Effect,
+        machine: Option<&Machine>,
+    ) -> Result<Effect, String> {
+        if let (
+            Some(machine),
+            Effect::TransitionEffect(TransitionEffect::StateLabel(from)),
+            Effect::TransitionEffect(TransitionEffect::StateLabel(to)),
+        ) = (machine, self.last_step(), rhs.first_step())
+        {
+            if !machine.reachable(from, to) {
+                return Err(format!("{} is not reachable from {}", to, from));
+            }
+        }
+
+        self.compose(rhs)
+    }
+
+    fn first_step(&self) -> &Effect {
+        match self {
+            Effect::Steps(steps) => steps.first().unwrap(),
+            effect => effect,
+        }
+    }
+
+    fn last_step(&self) -> &Effect {
+        match self {
+            Effect::Steps(steps) => steps.last().unwrap(),
+            effect => effect,
         }
     }
 
-    pub fn apply(&self, value: &SnapshotValueItemValue) -> SnapshotValueItemValue {
-        value.apply(self)
+    pub fn apply(
+        &self,
+        value: &SnapshotValueItemValue,
+        machine: Option<&Machine>,
+    ) -> Result<SnapshotValueItemValue, String> {
+        value.apply(self, machine)
     }
 }
 
 impl AddEffect {
-    pub fn compose(&self, rhs: &Effect) -> Effect {
+    pub fn compose(&self, rhs: &Effect) -> Result<Effect, String> {
         match (self, rhs) {
-            (AddEffect(lhs), Effect::AddEffect(AddEffect(rhs))) => {
-                Effect::AddEffect(AddEffect(lhs + rhs))
-            }
-            (AddEffect(lhs), Effect::Id) => Effect::AddEffect(AddEffect(lhs.clone())),
-            _ => Effect::Empty,
+            (AddEffect(lhs), Effect::AddEffect(AddEffect(rhs))) => match lhs.checked_add(*rhs) {
+                Some(sum) => Ok(Effect::AddEffect(AddEffect(sum))),
+                None => Err(format!("+{} compose +{} overflows", lhs, rhs)),
+            },
+            (AddEffect(lhs), Effect::Id) => Ok(Effect::AddEffect(AddEffect(*lhs))),
+            _ => Ok(Effect::Empty),
         }
     }
 }
 
 impl TransitionEffect {
-    pub fn compose(&self, rhs: &Effect) -> Effect {
+    pub fn compose(&self, rhs: &Effect) -> Result<Effect, String> {
         match (self, rhs) {
+            // the first transition must still be checked against the state
+            // it is applied to
             (
-                TransitionEffect::StateLabel(_lhs),
-                Effect::TransitionEffect(TransitionEffect::StateLabel(rhs)),
-            ) => Effect::TransitionEffect(TransitionEffect::StateLabel(rhs.clone())),
-            (TransitionEffect::StateLabel(lhs), Effect::Id) => {
-                Effect::TransitionEffect(TransitionEffect::StateLabel(lhs.clone()))
+                TransitionEffect::StateLabel(_),
+                Effect::Empty
+                | Effect::TransitionEffect(TransitionEffect::Empty)
+                | Effect::TransitionEffect(TransitionEffect::StateLabel(_)),
+            ) => Ok(Effect::Steps(vec![
+                Effect::TransitionEffect(self.clone()),
+                rhs.clone(),
+            ])),
+            (TransitionEffect::StateLabel(_), Effect::Id)
+            | (TransitionEffect::StateLabel(_), Effect::TransitionEffect(TransitionEffect::Id)) => {
+                Ok(Effect::TransitionEffect(self.clone()))
+            }
+            (TransitionEffect::Id, rhs) => Ok(rhs.clone()),
+            (_, Effect::AddEffect(_)) => {
+                Err("a transition cannot be composed with an add effect".to_string())
             }
-            _ => unimplemented!(), // _ => Effect::Empty,
+            _ => Ok(Effect::Empty),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Slice(Vec<ContextEffect>);
+pub struct Slice(pub(crate) Vec<SliceItem>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SliceItem {
+    ContextEffect(ContextEffect),
+    Branch(Branch),
+}
+
+/// A branch point in a slice. Each route continues the slice on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Branch {
+    pub(crate) routes: Vec<(RouteLabel, Slice)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RouteLabel(pub(crate) String, #[serde(skip)] pub(crate) Location);
+
+/// A snapshot reached by `reduceAll` and the routes taken to reach it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Route {
+    pub(crate) path: Vec<String>,
+    pub(crate) snapshot: SnapshotValue,
+    /// Why the route stopped at `snapshot`, when it hit an invalid effect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ContextEffect(ContextLabel, Effect);
+pub struct ContextEffect(pub(crate) ContextLabel, pub(crate) Effect);
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Comment(String);
 
 impl Expr {
-    pub fn eval(&self, vm: &Vm) -> (Vm, Value) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, Value), Error> {
+        let (vm, value) = self.eval_value(vm)?;
+        let span = match self {
+            Expr::Id(_) => None,
+            Expr::Op(_, _, _, location)
+            | Expr::Call(_, _, location)
+            | Expr::Output(_, _, location) => Some(location.0),
+            Expr::Reference(reference) => Some(reference.1 .0),
+        };
+        if let Some(span) = span {
+            vm.tracer.record(|| TraceEvent::Eval {
+                depth: vm.stack.len(),
+                span,
+                expr: self.to_string(),
+                value: value.to_string(),
+            });
+        }
+
+        Ok((vm, value))
+    }
+
+    fn eval_value(&self, vm: &Vm) -> Result<(Vm, Value), Error> {
         match self {
             Expr::Id(v) => v.eval(vm),
-            Expr::Op(op, lhs, rhs) => {
-                let (vm, lhs_value) = lhs.eval(vm);
-                let (vm, rhs_value) = rhs.eval(&vm);
+            Expr::Op(op, lhs, rhs, location) => {
+                let (vm, lhs_value) = lhs.eval(vm)?;
+                let (vm, rhs_value) = rhs.eval(&vm)?;
+                let value = op
+                    .apply(&vm, lhs_value, rhs_value)
+                    .map_err(|err| Error::new(ErrorKind::Evaluation, &err, location.0))?;
+
+                Ok((vm, value))
+            }
+            Expr::Call(callee, args, location) => {
+                let (vm, callee) = callee.eval(vm)?;
+                let (vm, args) = args.iter().try_fold((vm, Vec::new()), |(vm, mut acc), arg| {
+                    let (vm, value) = arg.eval(&vm)?;
+                    acc.push(value);
+
+                    Ok::<_, Error>((vm, acc))
+                })?;
+
+                match callee {
+                    Value::Function(closure) => closure.call(&vm, args, location.0),
+                    _ => Err(Error::new(
+                        ErrorKind::Evaluation,
+                        "only functions can be called",
+                        location.0,
+                    )),
+                }
+            }
+            Expr::Reference(reference) => match vm.lookup_value_symbol(reference) {
+                Some((_, value)) => Ok((vm.clone(), value.clone())),
+                None => Err(Error::new(
+                    ErrorKind::UnknownSymbol,
+                    &format!("unknown symbol `{}`", (reference.0).0),
+                    reference.1 .0,
+                )),
+            },
+            Expr::Output(label, expr, _) => {
+                let (mut vm, value) = expr.eval(vm)?;
+                // without a label, the expression names its value
+                let label = label.clone().unwrap_or_else(|| expr.to_string());
+                vm.outputs.push((label, value.clone()));
 
-                (vm.clone(), op.apply(lhs_value, rhs_value))
+                Ok((vm, value))
             }
-            Expr::Reference(reference) => (
-                vm.clone(),
-                vm.lookup_value_symbol(reference).unwrap().1.clone(),
-            ),
         }
     }
 }
 
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Id => write!(f, "Id"),
+            Value::Empty => write!(f, "Empty"),
+            Value::Effect(effect) => write!(f, "{}", effect),
+            Value::Context(label) => write!(f, "{}", label.0),
+            Value::ContextEffect(context_effect) => write!(f, "{}", context_effect),
+            Value::Slice(slice) => write!(f, "{}", slice),
+            Value::Snapshot(snapshot) => write!(f, "{}", snapshot),
+            Value::Scope(_) => write!(f, "{{ ... }}"),
+            Value::Int(IntLiteral(value)) => write!(f, "{}", value),
+            Value::Function(closure) => {
+                match closure.arity().saturating_sub(closure.applied.len()) {
+                    1 => write!(f, "<function of 1 argument>"),
+                    arity => write!(f, "<function of {} arguments>", arity),
+                }
+            }
+            Value::Choice(values) => write!(f, "{}", join(values, " | ")),
+            Value::Routes(routes) => {
+                let routes = routes
+                    .iter()
+                    .map(|Route { path, snapshot, error }| {
+                        let path = match path.is_empty() {
+                            true => "(no branch)".to_string(),
+                            false => path.join("/"),
+                        };
+
+                        match error {
+                            Some(err) => format!("{}: {} at {}", path, err, snapshot),
+                            None => format!("{}: {}", path, snapshot),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                write!(f, "{}", routes.join("\n"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |expr: &Expr| match expr {
+            Expr::Op(..) => format!("({})", expr),
+            expr => expr.to_string(),
+        };
+
+        match self {
+            Expr::Id(value) => write!(f, "{}", value),
+            Expr::Op(op, lhs, rhs, _) => write!(f, "{} {} {}", operand(lhs), op, operand(rhs)),
+            Expr::Reference(reference) => write!(f, "{}", (reference.0).0),
+            Expr::Call(callee, args, _) => write!(f, "{}({})", operand(callee), join(args, ", ")),
+            Expr::Output(Some(label), expr, _) => write!(f, "Output {} << {}", label, expr),
+            Expr::Output(None, expr, _) => write!(f, "Output << {}", expr),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Compose => write!(f, "compose"),
+            Op::Apply => write!(f, "apply"),
+            Op::Reduce => write!(f, "reduce"),
+            Op::ReduceAll => write!(f, "reduceAll"),
+            Op::Push => write!(f, "<<"),
+            Op::Add => write!(f, "+"),
+            Op::Or => write!(f, "|"),
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Effect::Id => write!(f, "Id"),
+            Effect::Empty => write!(f, "Empty"),
+            Effect::AddEffect(AddEffect(value)) if *value < 0 => write!(f, "+({})", value),
+            Effect::AddEffect(AddEffect(value)) => write!(f, "+{}", value),
+            Effect::TransitionEffect(TransitionEffect::Id) => write!(f, "=>Id"),
+            Effect::TransitionEffect(TransitionEffect::Empty) => write!(f, "=>Empty"),
+            Effect::TransitionEffect(TransitionEffect::StateLabel(state)) => {
+                write!(f, "=>{}", state)
+            }
+            Effect::Steps(steps) => write!(f, "({})", join(steps, " compose ")),
+        }
+    }
+}
+
+impl fmt::Display for ContextEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", (self.0).0, self.1)
+    }
+}
+
+impl fmt::Display for Slice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", join(&self.0, ", "))
+    }
+}
+
+impl fmt::Display for SliceItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SliceItem::ContextEffect(context_effect) => write!(f, "{}", context_effect),
+            SliceItem::Branch(Branch { routes }) => {
+                let routes = routes
+                    .iter()
+                    .map(|(label, slice)| format!("{}: {}", label.0, slice))
+                    .collect::<Vec<_>>();
+
+                write!(f, "({})", routes.join(" | "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SnapshotValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.is_empty() {
+            true => write!(f, "{{}}"),
+            false => write!(f, "{{ {} }}", join(&self.0, ", ")),
+        }
+    }
+}
+
+impl fmt::Display for SnapshotValueItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", (self.0).0, self.1)
+    }
+}
+
+impl fmt::Display for SnapshotValueItemValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotValueItemValue::Id => write!(f, "Id"),
+            SnapshotValueItemValue::Empty => write!(f, "Empty"),
+            SnapshotValueItemValue::StateLabel(state) => write!(f, "{}", state),
+            SnapshotValueItemValue::Int(IntLiteral(value)) => write!(f, "{}", value),
+        }
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
 impl Value {
-    pub fn eval(&self, vm: &Vm) -> (Vm, Value) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, Value), Error> {
         match self {
-            Value::Id => (vm.clone(), Value::Id),
-            Value::Empty => (vm.clone(), Value::Empty),
+            Value::Id => Ok((vm.clone(), Value::Id)),
+            Value::Empty => Ok((vm.clone(), Value::Empty)),
             Value::Effect(value) => {
-                let (vm, value) = value.eval(vm);
+                let (vm, value) = value.eval(vm)?;
 
-                (vm, Value::Effect(value))
+                Ok((vm, Value::Effect(value)))
             }
             Value::Context(value) => {
-                let (vm, value) = value.eval(vm);
+                let (vm, value) = value.eval(vm)?;
 
-                (vm, Value::Context(value))
+                Ok((vm, Value::Context(value)))
             }
             Value::ContextEffect(value) => {
-                let (vm, value) = value.eval(vm);
+                let (vm, value) = value.eval(vm)?;
 
-                (vm, Value::ContextEffect(value))
+                Ok((vm, Value::ContextEffect(value)))
             }
             Value::Slice(value) => {
-                let (vm, value) = value.eval(vm);
+                let (vm, value) = value.eval(vm)?;
 
-                (vm, Value::Slice(value))
+                Ok((vm, Value::Slice(value)))
             }
             Value::Snapshot(value) => {
-                let (vm, value) = value.eval(vm);
+                let (vm, value) = value.eval(vm)?;
 
-                (vm, Value::Snapshot(value))
+                Ok((vm, Value::Snapshot(value)))
             }
             Value::Scope(value) => value.eval(vm),
+            Value::Int(value) => Ok((vm.clone(), Value::Int(value.clone()))),
+            Value::Function(closure) => Ok((
+                vm.clone(),
+                Value::Function(Closure {
+                    captured: vm.stack.clone(),
+                    ..closure.clone()
+                }),
+            )),
+            Value::Choice(values) => Ok((vm.clone(), Value::Choice(values.clone()))),
+            Value::Routes(routes) => Ok((vm.clone(), Value::Routes(routes.clone()))),
+        }
+    }
+
+    /// What kind of value this is, for error messages.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Value::Id => "Id",
+            Value::Empty => "Empty",
+            Value::Effect(Effect::AddEffect(_)) => "an add effect",
+            Value::Effect(Effect::TransitionEffect(_)) => "a transition",
+            Value::Effect(_) => "an effect",
+            Value::Context(_) => "a context",
+            Value::ContextEffect(_) => "a context effect",
+            Value::Slice(_) => "a slice",
+            Value::Snapshot(_) => "a snapshot",
+            Value::Scope(_) => "a scope",
+            Value::Int(_) => "an Int",
+            Value::Function(_) => "a function",
+            Value::Choice(_) => "a choice",
+            Value::Routes(_) => "routes",
         }
     }
 }
 
 impl Effect {
-    pub fn eval(&self, vm: &Vm) -> (Vm, Effect) {
-        return (vm.clone(), self.clone());
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, Effect), Error> {
+        Ok((vm.clone(), self.clone()))
     }
 }
 
 impl ContextLabel {
-    pub fn eval(&self, vm: &Vm) -> (Vm, ContextLabel) {
-        return (vm.clone(), self.clone());
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, ContextLabel), Error> {
+        Ok((vm.clone(), self.clone()))
     }
 }
 
 impl ContextEffect {
-    pub fn eval(&self, vm: &Vm) -> (Vm, ContextEffect) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, ContextEffect), Error> {
         match self {
             ContextEffect(label, effect) => {
-                let (vm, label) = label.eval(vm);
-                let (vm, effect) = effect.eval(&vm);
+                let (vm, label) = label.eval(vm)?;
+                let (vm, effect) = effect.eval(&vm)?;
 
-                (vm, ContextEffect(label, effect))
+                Ok((vm, ContextEffect(label, effect)))
             }
         }
     }
 }
 
 impl Slice {
-    pub fn eval(&self, vm: &Vm) -> (Vm, Slice) {
-        let (vm, vec) = self
-            .0
-            .iter()
-            .fold((vm.clone(), Vec::new()), |(vm, mut acc), item| {
-                let (vm, value) = item.eval(&vm);
-                acc.push(value);
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, Slice), Error> {
+        let (vm, vec) =
+            self.0
+                .iter()
+                .try_fold((vm.clone(), Vec::new()), |(vm, mut acc), item| {
+                    let (vm, value) = item.eval(&vm)?;
+                    acc.push(value);
 
-                (vm, acc)
-            });
+                    Ok::<_, Error>((vm, acc))
+                })?;
+
+        Ok((vm, Slice(vec)))
+    }
+}
 
-        (vm, Slice(vec))
+impl SliceItem {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, SliceItem), Error> {
+        match self {
+            SliceItem::ContextEffect(context_effect) => {
+                let (vm, context_effect) = context_effect.eval(vm)?;
+
+                Ok((vm, SliceItem::ContextEffect(context_effect)))
+            }
+            SliceItem::Branch(Branch { routes }) => {
+                let (vm, routes) = routes.iter().try_fold(
+                    (vm.clone(), Vec::new()),
+                    |(vm, mut acc), (label, slice)| {
+                        let (vm, slice) = slice.eval(&vm)?;
+                        acc.push((label.clone(), slice));
+
+                        Ok::<_, Error>((vm, acc))
+                    },
+                )?;
+
+                Ok((vm, SliceItem::Branch(Branch { routes })))
+            }
+        }
     }
 }
 
 impl SnapshotValue {
-    pub fn eval(&self, vm: &Vm) -> (Vm, SnapshotValue) {
-        let (vm, vec) = self
-            .0
-            .iter()
-            .fold((vm.clone(), Vec::new()), |(vm, mut acc), item| {
-                let (vm, value) = item.eval(&vm);
-                acc.push(value);
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, SnapshotValue), Error> {
+        let (vm, vec) =
+            self.0
+                .iter()
+                .try_fold((vm.clone(), Vec::new()), |(vm, mut acc), item| {
+                    let (vm, value) = item.eval(&vm)?;
+                    acc.push(value);
 
-                (vm, acc)
-            });
+                    Ok::<_, Error>((vm, acc))
+                })?;
 
-        (vm, SnapshotValue(vec))
+        Ok((vm, SnapshotValue(vec)))
     }
 }
 
 impl SnapshotValueItem {
-    pub fn eval(&self, vm: &Vm) -> (Vm, SnapshotValueItem) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, SnapshotValueItem), Error> {
         match self {
             SnapshotValueItem(label, value) => {
-                let (vm, label) = label.eval(vm);
-                let (vm, value) = value.eval(&vm);
+                let (vm, label) = label.eval(vm)?;
+                let (vm, value) = value.eval(&vm)?;
 
-                (vm, SnapshotValueItem(label, value))
+                Ok((vm, SnapshotValueItem(label, value)))
             }
         }
     }
 }
 
 impl SnapshotValueItemValue {
-    pub fn eval(&self, vm: &Vm) -> (Vm, SnapshotValueItemValue) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, SnapshotValueItemValue), Error> {
         match self {
-            SnapshotValueItemValue::Id => (vm.clone(), SnapshotValueItemValue::Id),
-            SnapshotValueItemValue::Empty => (vm.clone(), SnapshotValueItemValue::Empty),
+            SnapshotValueItemValue::Id => Ok((vm.clone(), SnapshotValueItemValue::Id)),
+            SnapshotValueItemValue::Empty => Ok((vm.clone(), SnapshotValueItemValue::Empty)),
             SnapshotValueItemValue::StateLabel(label) => {
-                let (vm, label) = label.eval(vm);
+                let (vm, label) = label.eval(vm)?;
 
-                (vm, SnapshotValueItemValue::StateLabel(label))
+                Ok((vm, SnapshotValueItemValue::StateLabel(label)))
             }
             SnapshotValueItemValue::Int(value) => {
-                let (vm, value) = value.eval(vm);
+                let (vm, value) = value.eval(vm)?;
 
-                (vm, SnapshotValueItemValue::Int(value))
+                Ok((vm, SnapshotValueItemValue::Int(value)))
             }
         }
     }
 }
 
 impl StateLabel {
-    pub fn eval(&self, vm: &Vm) -> (Vm, StateLabel) {
-        (vm.clone(), self.clone())
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, StateLabel), Error> {
+        Ok((vm.clone(), self.clone()))
     }
 }
 
 impl IntLiteral {
-    pub fn eval(&self, vm: &Vm) -> (Vm, IntLiteral) {
-        (vm.clone(), self.clone())
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, IntLiteral), Error> {
+        Ok((vm.clone(), self.clone()))
     }
 }
 
@@ -495,31 +1024,184 @@ pub struct VmScope {
     // TODO: VecをSymbolTableにする
     type_symbols: Vec<(TypeSymbol, TypeValue)>,
     value_symbols: Vec<(ValueSymbol, Value)>,
+    /// Names given a value by `let` in this scope. A name declared without
+    /// a value can be given one later, but only once.
+    assigned: Vec<String>,
+    /// Names given a definition by `type` in this scope, in the same way.
+    assigned_types: Vec<String>,
     return_value: Value,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Vm {
     stack: Vec<VmScope>,
+    /// Every `let` in evaluation order, with the value it was bound to.
+    bindings: Vec<(ValueSymbol, Value)>,
+    /// The labels and values of `Output` in evaluation order.
+    outputs: Vec<(String, Value)>,
+    #[serde(skip)]
+    tracer: Tracer,
 }
 
 impl Vm {
     pub fn new() -> Self {
-        Self { stack: Vec::new() }
+        Self {
+            stack: Vec::new(),
+            bindings: Vec::new(),
+            outputs: Vec::new(),
+            tracer: Tracer::default(),
+        }
+    }
+
+    /// A `Vm` that records a trace of what it evaluates.
+    pub fn with_trace() -> Self {
+        Self {
+            tracer: Tracer::on(),
+            ..Self::new()
+        }
+    }
+
+    /// What the `Vm` and its clones have done so far, empty unless it was
+    /// made by `with_trace`.
+    pub fn trace(&self) -> Trace {
+        self.tracer.trace()
+    }
+
+    /// The `let` bindings of the current scope with their declared kinds.
+    pub fn scope_bindings(&self) -> Vec<(String, BindKeywords, Value)> {
+        self.stack
+            .last()
+            .map(|scope| {
+                scope
+                    .value_symbols
+                    .iter()
+                    .map(|(symbol, value)| {
+                        (symbol.name.0.clone(), symbol.kind.clone(), value.clone())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn outputs(&self) -> Vec<(String, Value)> {
+        self.outputs.clone()
+    }
+
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        self.bindings
+            .iter()
+            .map(|(symbol, value)| (symbol.name.0.clone(), value.clone()))
+            .collect()
+    }
+
+    /// The exported types and values of the current scope, the values not
+    /// typed yet.
+    pub(crate) fn exported(&self) -> Exports {
+        match self.stack.last() {
+            Some(scope) => Exports {
+                types: scope
+                    .type_symbols
+                    .iter()
+                    .filter(|(symbol, _)| symbol.exported)
+                    .cloned()
+                    .collect(),
+                values: scope
+                    .value_symbols
+                    .iter()
+                    .filter(|(symbol, _)| symbol.exported)
+                    .map(|(symbol, value)| (symbol.clone(), value.clone(), Type::Any))
+                    .collect(),
+            },
+            None => Exports::default(),
+        }
+    }
+
+    /// Binds the exports of an imported file in the current scope, under
+    /// the names they are qualified with.
+    pub(crate) fn import(&mut self, exports: &Exports) {
+        let scope = self.current_scope();
+        scope.type_symbols.extend(exports.types.iter().cloned());
+        scope.value_symbols.extend(
+            exports
+                .values
+                .iter()
+                .map(|(symbol, value, _)| (symbol.clone(), value.clone())),
+        );
     }
 
     pub fn push_stack(&mut self) {
-        self.stack.push(VmScope::new())
+        self.stack.push(VmScope::new());
+        self.tracer.record(|| TraceEvent::PushScope {
+            depth: self.stack.len(),
+        });
     }
 
     pub fn pop_stack(&mut self) -> Option<VmScope> {
+        self.tracer.record(|| TraceEvent::PopScope {
+            depth: self.stack.len(),
+        });
         self.stack.pop()
     }
 
+    fn trace_bind(&self, name: &ValueSymbolName, value: &Value) {
+        self.tracer.record(|| TraceEvent::Bind {
+            depth: self.stack.len(),
+            name: name.0.clone(),
+            value: value.to_string(),
+        });
+    }
+
     pub fn current_scope(&mut self) -> &mut VmScope {
         self.stack.last_mut().unwrap()
     }
 
+    /// The declared states of `label`, when its type is a defined
+    /// `StateMachine`.
+    pub fn state_machine(&self, label: &ContextLabel) -> Option<Machine> {
+        let type_values = || {
+            self.stack
+                .iter()
+                .rev()
+                .flat_map(|scope| scope.type_symbols.iter().rev())
+        };
+        let lookup = |name: &str| {
+            type_values()
+                .find(|(symbol, _)| symbol.name.0 == name)
+                .map(|(symbol, value)| match value {
+                    TypeValue::Empty => (symbol.kind.clone(), None),
+                    value => (symbol.kind.clone(), Some(value.clone())),
+                })
+        };
+        let normalized = |name: &TypeSymbolName, kind: &TypeKind, value: &TypeValue| {
+            types::normalize(&name.0, kind, value, Span::default(), &lookup).ok()
+        };
+
+        // the checker has reported the types that do not normalize
+        let effect_type = type_values().find_map(|(symbol, value)| {
+            match normalized(&symbol.name, &symbol.kind, value)? {
+                Normal::Context(ContextType(context, effect_type)) if context == *label => {
+                    Some(effect_type)
+                }
+                Normal::Snapshot(contexts) => contexts
+                    .into_iter()
+                    .find(|ContextType(context, _)| *context == *label)
+                    .map(|ContextType(_, effect_type)| effect_type),
+                _ => None,
+            }
+        })?;
+
+        match effect_type {
+            EffectType::TypeSymbolReference(name) => match lookup(&name.0)? {
+                (kind, Some(value)) => match normalized(&name, &kind, &value)? {
+                    Normal::StateMachine(machine) => Some(machine),
+                    _ => None,
+                },
+                (_, None) => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn lookup_value_symbol(
         &self,
         reference: &ValueSymbolReference,
@@ -542,27 +1224,46 @@ impl VmScope {
         Self {
             type_symbols: Vec::new(),
             value_symbols: Vec::new(),
+            assigned: Vec::new(),
+            assigned_types: Vec::new(),
             return_value: Value::Empty,
         }
     }
 
-    pub fn push_type_symbol(&mut self, symbol: &TypeSymbol) {
+    pub fn push_type_symbol(&mut self, symbol: &TypeSymbol) -> Result<(), Error> {
         if self
             .type_symbols
             .iter()
-            .find(|item| item.0.name.0 == symbol.name.0)
-            .is_some()
+            .any(|item| item.0.name.0 == symbol.name.0)
         {
-            panic!()
+            return Err(Error::new(
+                ErrorKind::DuplicateSymbol,
+                &format!("type `{}` is already declared in this scope", symbol.name.0),
+                symbol.location.0,
+            ));
         }
 
         self.type_symbols.push((symbol.clone(), TypeValue::Empty));
+
+        Ok(())
     }
 
-    pub fn assign_type_symbol(&mut self, symbol: &TypeSymbol, value: &TypeValue) {
+    pub fn assign_type_symbol(&mut self, symbol: &TypeSymbol, value: &TypeValue) -> Result<(), Error> {
         if !symbol.assignable(value) {
-            panic!()
+            return Err(Error::new(
+                ErrorKind::Type,
+                &format!("the value does not fit type `{}`", symbol.name.0),
+                symbol.location.0,
+            ));
         }
+        if self.assigned_types.contains(&symbol.name.0) {
+            return Err(Error::new(
+                ErrorKind::DuplicateSymbol,
+                &format!("type `{}` is already defined in this scope", symbol.name.0),
+                symbol.location.0,
+            ));
+        }
+        self.assigned_types.push(symbol.name.0.clone());
 
         if let Some(sym) = self
             .type_symbols
@@ -573,25 +1274,44 @@ impl VmScope {
         } else {
             self.type_symbols.push((symbol.clone(), value.clone()));
         }
+
+        Ok(())
     }
 
-    pub fn push_value_symbol(&mut self, symbol: &ValueSymbol) {
+    pub fn push_value_symbol(&mut self, symbol: &ValueSymbol) -> Result<(), Error> {
         if self
             .value_symbols
             .iter()
-            .find(|item| item.0.name.0 == symbol.name.0)
-            .is_some()
+            .any(|item| item.0.name.0 == symbol.name.0)
         {
-            panic!()
+            return Err(Error::new(
+                ErrorKind::DuplicateSymbol,
+                &format!("`{}` is already bound in this scope", symbol.name.0),
+                symbol.location.0,
+            ));
         }
 
         self.value_symbols.push((symbol.clone(), Value::Empty));
+
+        Ok(())
     }
 
-    pub fn assign_value_symbol(&mut self, symbol: &ValueSymbol, value: &Value) {
+    pub fn assign_value_symbol(&mut self, symbol: &ValueSymbol, value: &Value) -> Result<(), Error> {
         if !symbol.assignable(value) {
-            panic!()
+            return Err(Error::new(
+                ErrorKind::Type,
+                &format!("the value does not fit the type of `{}`", symbol.name.0),
+                symbol.location.0,
+            ));
         }
+        if self.assigned.contains(&symbol.name.0) {
+            return Err(Error::new(
+                ErrorKind::DuplicateSymbol,
+                &format!("`{}` is already bound in this scope", symbol.name.0),
+                symbol.location.0,
+            ));
+        }
+        self.assigned.push(symbol.name.0.clone());
 
         if let Some(sym) = self
             .value_symbols
@@ -602,6 +1322,31 @@ impl VmScope {
         } else {
             self.value_symbols.push((symbol.clone(), value.clone()));
         }
+
+        Ok(())
+    }
+
+    /// Binds a function argument, which has the kind of its value.
+    pub fn bind_argument(&mut self, name: &ValueSymbolName, value: &Value) {
+        let kind = match value {
+            Value::Function(_) => BindKeywords::Function(FunctionSignature(Vec::new())),
+            Value::Int(_) => BindKeywords::Int,
+            Value::Context(_) => BindKeywords::Context,
+            Value::ContextEffect(_) => BindKeywords::ContextEffect,
+            Value::Slice(_) => BindKeywords::Slice,
+            Value::Snapshot(_) => BindKeywords::Snapshot,
+            _ => BindKeywords::Effect,
+        };
+
+        self.value_symbols.push((
+            ValueSymbol {
+                name: name.clone(),
+                kind,
+                exported: false,
+                location: Location::default(),
+            },
+            value.clone(),
+        ));
     }
 
     pub fn push_return_value(&mut self, value: &Value) {
@@ -624,134 +1369,223 @@ impl VmScope {
                 },
                 _,
             )| match reference {
-                ValueSymbolReference(ValueSymbolName(ref_name)) => sym_name == ref_name,
+                ValueSymbolReference(ValueSymbolName(ref_name), _) => sym_name == ref_name,
             },
         )
     }
 }
 
 impl TypeExpr {
-    pub fn eval(&self, vm: &Vm) -> (Vm, TypeValue) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, TypeValue), Error> {
         match self {
             Self::Bind(symbol) => {
                 let mut vm = vm.clone();
-                vm.current_scope().push_type_symbol(&symbol);
+                vm.current_scope().push_type_symbol(symbol)?;
 
-                (vm.clone(), TypeValue::TypeSymbol(symbol.clone()))
+                Ok((vm, TypeValue::TypeSymbol(symbol.clone())))
             }
             Self::Assign { symbol, value } => {
                 let mut vm = vm.clone();
-                vm.current_scope().assign_type_symbol(symbol, value);
+                vm.current_scope().assign_type_symbol(symbol, value)?;
 
-                (vm.clone(), value.clone())
+                Ok((vm, value.clone()))
             }
-            Self::Id(value) => (vm.clone(), value.clone()),
+            Self::Id(value) => Ok((vm.clone(), value.clone())),
         }
     }
 }
 
 impl LetExpr {
-    pub fn eval(&self, vm: &Vm) -> (Vm, Value) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, Value), Error> {
         match self {
             Self::Bind(symbol) => {
                 let mut vm = vm.clone();
-                vm.current_scope().push_value_symbol(&symbol);
+                vm.current_scope().push_value_symbol(symbol)?;
+                vm.bindings.push((symbol.clone(), Value::Empty));
+                vm.trace_bind(&symbol.name, &Value::Empty);
 
-                (vm.clone(), Value::Empty)
+                Ok((vm, Value::Empty))
             }
             Self::Assign { symbol, expr } => {
-                let (mut vm, value) = expr.eval(&vm);
-                vm.current_scope().assign_value_symbol(symbol, &value);
+                let (mut vm, value) = expr.eval(vm)?;
+                let value = match (&symbol.kind, value) {
+                    (BindKeywords::Function(signature), Value::Function(closure))
+                        if signature.0.len() > 1 =>
+                    {
+                        Value::Function(closure.with_signature(signature))
+                    }
+                    (_, value) => value,
+                };
+                vm.current_scope().assign_value_symbol(symbol, &value)?;
+                vm.bindings.push((symbol.clone(), value.clone()));
+                vm.trace_bind(&symbol.name, &value);
 
-                (vm.clone(), value.clone())
+                Ok((vm, value))
             }
         }
     }
 }
 
 impl ScopeInnerValue {
-    pub fn eval(&self, vm: &Vm) -> (Vm, Value) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, Value), Error> {
         match self {
-            ScopeInnerValue::Comment(_) => (vm.clone(), Value::Empty),
-            ScopeInnerValue::Stmt(value) => (value.eval(vm), Value::Empty),
+            ScopeInnerValue::Comment(_) => Ok((vm.clone(), Value::Empty)),
+            ScopeInnerValue::Stmt(value) => Ok((value.eval(vm)?, Value::Empty)),
             ScopeInnerValue::Expr(value) => {
-                let (mut vm, value) = value.eval(vm);
+                let (mut vm, value) = value.eval(vm)?;
                 vm.current_scope().push_return_value(&value);
 
-                (vm, value)
+                Ok((vm, value))
             }
         }
     }
 }
 
 impl Stmt {
-    pub fn eval(&self, vm: &Vm) -> Vm {
+    pub fn eval(&self, vm: &Vm) -> Result<Vm, Error> {
         let vm = match self {
-            Stmt::Expr(expr) => {
-                let (vm, _) = expr.eval(vm);
-
-                vm
-            }
-            Stmt::TypeStmt(expr) => {
-                let (vm, _) = expr.eval(vm);
-
-                vm
-            }
-            Stmt::LetStmt(expr) => {
-                let (vm, _) = expr.eval(vm);
-
-                vm
-            }
-            Stmt::ScopeStmt(scope) => {
-                let (vm, _) = scope.eval(vm);
-
-                vm
-            }
+            Stmt::Expr(expr) => expr.eval(vm)?.0,
+            Stmt::TypeStmt(expr) => expr.eval(vm)?.0,
+            Stmt::LetStmt(expr) => expr.eval(vm)?.0,
+            Stmt::ScopeStmt(scope) => scope.eval(vm)?.0,
+            // the module loader binds the exports before the file is evaluated
+            Stmt::Import(_) => vm.clone(),
         };
 
-        vm
+        Ok(vm)
     }
 }
 
 impl ScopeValue {
-    pub fn eval(&self, vm: &Vm) -> (Vm, Value) {
+    pub fn eval(&self, vm: &Vm) -> Result<(Vm, Value), Error> {
         let mut vm = vm.clone();
         vm.push_stack();
 
         let (mut vm, value) = self
             .0
             .iter()
-            .fold((vm, Value::Empty), |(vm, _value), stmt| stmt.eval(&vm));
+            .try_fold((vm, Value::Empty), |(vm, _value), stmt| stmt.eval(&vm))?;
 
-        // let scope = vm.pop_stack();
         vm.pop_stack();
 
-        (vm, value)
+        Ok((vm, value))
+    }
+
+    /// Evaluates the items in the current scope of `vm`, so that its
+    /// bindings stay visible afterwards.
+    pub fn eval_in(&self, vm: &Vm) -> Result<(Vm, Value), Error> {
+        self.0
+            .iter()
+            .try_fold((vm.clone(), Value::Empty), |(vm, _value), stmt| {
+                stmt.eval(&vm)
+            })
     }
 }
 
 impl Op {
-    pub fn apply(&self, lhs: Value, rhs: Value) -> Value {
+    pub fn apply(&self, vm: &Vm, lhs: Value, rhs: Value) -> Result<Value, String> {
         match (self, &lhs, &rhs) {
-            (Op::Compose, lhs, rhs) => eval_compose(lhs, rhs),
-            (Op::Apply, lhs, rhs) => eval_apply(lhs, rhs),
-            (Op::Reduce, lhs, rhs) => eval_reduce(lhs, rhs),
-            (Op::Push, lhs, rhs) => eval_push(lhs, rhs),
+            (Op::Or, lhs, rhs) => eval_or(lhs, rhs),
+            // every other operator is applied to each outcome of a choice
+            (op, Value::Choice(values), rhs) => values
+                .iter()
+                .map(|value| op.apply(vm, value.clone(), rhs.clone()))
+                .collect::<Result<_, _>>()
+                .map(Value::Choice),
+            (op, lhs, Value::Choice(values)) => values
+                .iter()
+                .map(|value| op.apply(vm, lhs.clone(), value.clone()))
+                .collect::<Result<_, _>>()
+                .map(Value::Choice),
+            (Op::Compose, lhs, rhs) => eval_compose(vm, lhs, rhs),
+            (Op::Apply, lhs, rhs) => Ok(eval_apply(lhs, rhs)),
+            (Op::Reduce, lhs, rhs) => eval_reduce(vm, lhs, rhs),
+            (Op::ReduceAll, lhs, rhs) => eval_reduce_all(vm, lhs, rhs),
+            (Op::Push, lhs, rhs) => Ok(eval_push(lhs, rhs)),
+            (Op::Add, lhs, rhs) => eval_add(lhs, rhs),
         }
     }
 }
 
-fn eval_compose(lhs: &Value, rhs: &Value) -> Value {
-    match (lhs, rhs) {
+fn eval_compose(vm: &Vm, lhs: &Value, rhs: &Value) -> Result<Value, String> {
+    let value = match (lhs, rhs) {
         (Value::Id, Value::Id) => Value::Id,
         (any, Value::Id) => any.clone(),
         (Value::Id, any) => any.clone(),
 
-        (Value::Effect(lhs), Value::Effect(rhs)) => Value::Effect(lhs.compose(rhs)),
-        (Value::Slice(_), Value::Slice(_)) => unimplemented!(),
-        (Value::ContextEffect(_), Value::ContextEffect(_)) => unimplemented!(),
+        (Value::Effect(lhs), Value::Effect(rhs)) => Value::Effect(lhs.compose(rhs)?),
+        // `rhs` happens after `lhs`
+        (Value::Slice(Slice(lhs)), Value::Slice(Slice(rhs))) => {
+            Value::Slice(Slice(lhs.iter().chain(rhs).cloned().collect()))
+        }
+        (
+            Value::ContextEffect(ContextEffect(label, lhs)),
+            Value::ContextEffect(ContextEffect(rhs_label, rhs)),
+        ) => {
+            if label != rhs_label {
+                return Err(format!(
+                    "cannot compose effects on different contexts `{}` and `{}`",
+                    label.0, rhs_label.0
+                ));
+            }
+
+            Value::ContextEffect(ContextEffect(
+                label.clone(),
+                lhs.compose_on(rhs, vm.state_machine(label).as_ref())?,
+            ))
+        }
 
         _ => Value::Empty,
+    };
+
+    Ok(value)
+}
+
+fn eval_add(lhs: &Value, rhs: &Value) -> Result<Value, String> {
+    let add = |lhs: &i64, rhs: &i64| {
+        lhs.checked_add(*rhs)
+            .ok_or_else(|| format!("{} + {} overflows", lhs, rhs))
+    };
+
+    match (lhs, rhs) {
+        (Value::Int(IntLiteral(lhs)), Value::Int(IntLiteral(rhs))) => {
+            Ok(Value::Int(IntLiteral(add(lhs, rhs)?)))
+        }
+        (
+            Value::Effect(Effect::AddEffect(AddEffect(lhs))),
+            Value::Effect(Effect::AddEffect(AddEffect(rhs))),
+        ) => Ok(Value::Effect(Effect::AddEffect(AddEffect(add(lhs, rhs)?)))),
+        (lhs, rhs) => Err(format!(
+            "`+` expects two Ints or two add effects, found {} and {}",
+            lhs.kind(),
+            rhs.kind()
+        )),
+    }
+}
+
+/// `lhs | rhs` is a choice of effects or of slices. Choices are flat, so
+/// `(a | b) | c` has the three alternatives.
+fn eval_or(lhs: &Value, rhs: &Value) -> Result<Value, String> {
+    let alternatives = |value: &Value| match value {
+        Value::Choice(values) => values.clone(),
+        value => vec![value.clone()],
+    };
+    let values = [alternatives(lhs), alternatives(rhs)].concat();
+
+    let effects = values
+        .iter()
+        .all(|value| matches!(value, Value::Id | Value::Empty | Value::Effect(_)));
+    let slices = values
+        .iter()
+        .all(|value| matches!(value, Value::Id | Value::Empty | Value::Slice(_)));
+
+    match effects || slices {
+        true => Ok(Value::Choice(values)),
+        false => Err(format!(
+            "`|` expects two effects or two slices, found {} and {}",
+            lhs.kind(),
+            rhs.kind()
+        )),
     }
 }
 
@@ -759,7 +1593,7 @@ fn eval_push(lhs: &Value, rhs: &Value) -> Value {
     match (lhs, rhs) {
         (Value::Slice(slice), Value::ContextEffect(context_effect)) => {
             let mut slice = slice.clone();
-            slice.0.push(context_effect.clone());
+            slice.0.push(SliceItem::ContextEffect(context_effect.clone()));
 
             Value::Slice(slice)
         }
@@ -767,52 +1601,111 @@ fn eval_push(lhs: &Value, rhs: &Value) -> Value {
     }
 }
 
-fn eval_reduce(lhs: &Value, rhs: &Value) -> Value {
+fn eval_reduce(vm: &Vm, lhs: &Value, rhs: &Value) -> Result<Value, String> {
     match (lhs, rhs) {
-        (Value::Slice(slice), Value::Snapshot(snapshot)) => Value::Snapshot(slice.apply(snapshot)),
-        _ => Value::Empty,
+        (Value::Slice(slice), Value::Snapshot(snapshot)) => {
+            Ok(Value::Snapshot(slice.apply(snapshot, vm)?))
+        }
+        _ => Ok(Value::Empty),
+    }
+}
+
+fn eval_reduce_all(vm: &Vm, lhs: &Value, rhs: &Value) -> Result<Value, String> {
+    match (lhs, rhs) {
+        (Value::Slice(slice), Value::Snapshot(snapshot)) => {
+            Ok(Value::Routes(slice.apply_all(snapshot, vm)?))
+        }
+        _ => Ok(Value::Empty),
     }
 }
 
 impl Slice {
-    pub fn apply(&self, snapshot: &SnapshotValue) -> SnapshotValue {
-        let context_effects = self
-            .0
-            .iter()
-            .fold(
-                HashMap::<ContextLabel, Vec<Effect>>::new(),
-                |mut acc, ContextEffect(label, effect)| {
-                    let effects = match acc.get_mut(label) {
-                        Some(v) => v,
-                        None => {
-                            acc.insert(label.clone(), Vec::new());
-                            acc.get_mut(label).unwrap()
-                        }
-                    };
-                    effects.push(effect.clone());
+    pub fn apply(&self, snapshot: &SnapshotValue, vm: &Vm) -> Result<SnapshotValue, String> {
+        // effects are applied one by one in causal order, so reducing a
+        // composed slice is the same as reducing its parts in sequence
+        self.0.iter().try_fold(snapshot.clone(), |acc, item| match item {
+            SliceItem::ContextEffect(context_effect) => context_effect.apply(&acc, vm),
+            SliceItem::Branch(_) => {
+                Err("the slice branches, use `reduceAll` to follow every route".to_string())
+            }
+        })
+    }
 
-                    acc
-                },
-            )
-            .iter()
-            .map(|(label, effects)| {
-                ContextEffect(
-                    label.clone(),
-                    effects
+    /// Follows every route of the slice from `snapshot`. A route that hits
+    /// an invalid effect stops there and keeps the error, next to the routes
+    /// that get through. It fails when no route gets through.
+    pub fn apply_all(&self, snapshot: &SnapshotValue, vm: &Vm) -> Result<Vec<Route>, String> {
+        let start = Route {
+            path: Vec::new(),
+            snapshot: snapshot.clone(),
+            error: None,
+        };
+        let routes = self.follow(vec![start], vm);
+
+        match routes.iter().all(|route| route.error.is_some()) {
+            true => Err(routes.into_iter().find_map(|route| route.error).unwrap()),
+            false => Ok(routes),
+        }
+    }
+
+    fn follow(&self, routes: Vec<Route>, vm: &Vm) -> Vec<Route> {
+        self.0.iter().fold(routes, |routes, item| match item {
+            SliceItem::ContextEffect(context_effect) => routes
+                .into_iter()
+                .map(|route| match route.error {
+                    Some(_) => route,
+                    None => match context_effect.apply(&route.snapshot, vm) {
+                        Ok(snapshot) => Route { snapshot, ..route },
+                        Err(err) => Route {
+                            error: Some(err),
+                            ..route
+                        },
+                    },
+                })
+                .collect(),
+            SliceItem::Branch(Branch { routes: branches }) => routes
+                .into_iter()
+                .flat_map(|route| match route.error {
+                    Some(_) => vec![route],
+                    None => branches
                         .iter()
-                        .fold(Effect::Id, |acc, effect| acc.compose(effect)),
-                )
-            })
-            .fold(snapshot.clone(), |acc, ContextEffect(label, effect)| {
-                let value = match snapshot.find_by_label(&label) {
-                    Some(SnapshotValueItem(_, value)) => effect.apply(value),
-                    _ => SnapshotValueItemValue::Empty,
-                };
+                        .flat_map(|(label, slice)| {
+                            let mut path = route.path.clone();
+                            path.push(label.0.clone());
+                            let route = Route {
+                                path,
+                                snapshot: route.snapshot.clone(),
+                                error: None,
+                            };
+
+                            slice.follow(vec![route], vm)
+                        })
+                        .collect::<Vec<_>>(),
+                })
+                .collect(),
+        })
+    }
+}
 
-                acc.insert(&label, &value)
-            });
+impl ContextEffect {
+    pub fn apply(&self, snapshot: &SnapshotValue, vm: &Vm) -> Result<SnapshotValue, String> {
+        let ContextEffect(label, effect) = self;
+        let before = snapshot.find_by_label(label);
+        let value = match before {
+            Some(SnapshotValueItem(_, value)) => {
+                effect.apply(value, vm.state_machine(label).as_ref())?
+            }
+            _ => SnapshotValueItemValue::Empty,
+        };
+        vm.tracer.record(|| TraceEvent::Apply {
+            depth: vm.stack.len(),
+            context: label.0.clone(),
+            effect: effect.to_string(),
+            before: before.map_or("(missing)".to_string(), |item| item.1.to_string()),
+            after: value.to_string(),
+        });
 
-        context_effects
+        Ok(snapshot.insert(label, &value))
     }
 }
 
@@ -835,109 +1728,131 @@ impl Node {
     }
 }
 
-impl From<&Pair<'_, Rule>> for Node {
-    fn from(pair: &Pair<'_, Rule>) -> Self {
-        to_node(pair)
+fn parse_stmt(pair: &Pair<'_, Rule>) -> Result<Stmt, Error> {
+    let pair = pair.clone().into_inner().next().unwrap();
+
+    match pair.as_rule() {
+        Rule::typeStmt => Ok(Stmt::TypeStmt(parse_type_expr(&pair)?)),
+        Rule::letStmt => Ok(Stmt::LetStmt(parse_let_expr(&pair)?)),
+        Rule::calcStmt => Ok(Stmt::Expr(parse_expr(&pair)?)),
+        Rule::scopeStmt => Ok(Stmt::ScopeStmt(parse_scope_stmt(&pair)?)),
+        Rule::importStmt => Ok(Stmt::Import(parse_import_stmt(&pair)?)),
+        Rule::exportStmt => parse_export_stmt(&pair),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_stmt(pair: &Pair<'_, Rule>) -> Stmt {
+fn parse_import_stmt(pair: &Pair<'_, Rule>) -> Result<Import, Error> {
+    let mut inner = pair.clone().into_inner();
+    let namespace = parse_var_symbol(&inner.next().unwrap())?;
+    let path = inner.next().unwrap().as_str().to_string();
+
+    Ok(Import {
+        namespace,
+        path,
+        location: Location::of(pair),
+    })
+}
+
+/// The `type` or `let` statement after `export`, with its symbol exported.
+fn parse_export_stmt(pair: &Pair<'_, Rule>) -> Result<Stmt, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::typeStmt => Stmt::TypeStmt(parse_type_expr(&pair)),
-        Rule::letStmt => Stmt::LetStmt(parse_let_expr(&pair)),
-        Rule::calcStmt => Stmt::Expr(parse_expr(&pair)),
-        Rule::scopeStmt => Stmt::ScopeStmt(parse_scope_stmt(&pair)),
-        _ => panic!(),
+        Rule::typeStmt => {
+            let mut expr = parse_type_expr(&pair)?;
+            if let TypeExpr::Bind(symbol) | TypeExpr::Assign { symbol, .. } = &mut expr {
+                symbol.exported = true;
+            }
+
+            Ok(Stmt::TypeStmt(expr))
+        }
+        Rule::letStmt => {
+            let mut expr = parse_let_expr(&pair)?;
+            let (LetExpr::Bind(symbol) | LetExpr::Assign { symbol, .. }) = &mut expr;
+            symbol.exported = true;
+
+            Ok(Stmt::LetStmt(expr))
+        }
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_let_expr(pair: &Pair<'_, Rule>) -> LetExpr {
+fn parse_let_expr(pair: &Pair<'_, Rule>) -> Result<LetExpr, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::bindExpr => LetExpr::Bind(parse_bind_expr(&pair)),
+        Rule::bindExpr => Ok(LetExpr::Bind(parse_bind_expr(&pair)?)),
         Rule::assignExpr => parse_assign_expr(&pair),
-        _ => panic!(),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_assign_expr(pair: &Pair<'_, Rule>) -> LetExpr {
+fn parse_assign_expr(pair: &Pair<'_, Rule>) -> Result<LetExpr, Error> {
     match inner_len(pair) {
         2 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            LetExpr::Assign {
-                symbol: parse_bind_expr(lhs),
-                expr: parse_expr(rhs),
-            }
+            Ok(LetExpr::Assign {
+                symbol: parse_bind_expr(lhs)?,
+                expr: parse_expr(rhs)?,
+            })
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_bind_expr(pair: &Pair<'_, Rule>) -> ValueSymbol {
+fn parse_bind_expr(pair: &Pair<'_, Rule>) -> Result<ValueSymbol, Error> {
     match inner_len(pair) {
         2 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            ValueSymbol {
-                name: parse_var_symbol(lhs),
-                kind: parse_bind_keywords(rhs),
-            }
+            Ok(ValueSymbol {
+                name: parse_var_symbol(lhs)?,
+                kind: parse_bind_keywords(rhs)?,
+                exported: false,
+                location: Location::of(lhs),
+            })
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_bind_keywords(pair: &Pair<'_, Rule>) -> BindKeywords {
+fn parse_bind_keywords(pair: &Pair<'_, Rule>) -> Result<BindKeywords, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match &pair.as_rule() {
-        Rule::fnTypeExpr => parse_fn_type_expr(&pair),
-        _ => {
-            dbg!(&pair);
-            unimplemented!()
-        }
+        Rule::fnTypeExpr => match pair.as_span().as_str().trim() {
+            "Slice" => Ok(BindKeywords::Slice),
+            "Effect" => Ok(BindKeywords::Effect),
+            "ContextEffect" => Ok(BindKeywords::ContextEffect),
+            "Context" => Ok(BindKeywords::Context),
+            "Snapshot" => Ok(BindKeywords::Snapshot),
+            "Int" => Ok(BindKeywords::Int),
+            _ => parse_fn_type_expr(&pair),
+        },
+        _ => Err(Error::unsupported(&pair)),
     }
-
-    // match pair.as_span().as_str() {
-    //     "Slice" => BindKeywords::Slice,
-    //     "Effect" => BindKeywords::Effect,
-    //     "ContextEffect" => BindKeywords::ContextEffect,
-    //     "Context" => BindKeywords::Context,
-    //     "Snapshot" => BindKeywords::Snapshot,
-    //     _ => unimplemented!(),
-    // }
 }
 
-fn parse_fn_type_expr(pair: &Pair<'_, Rule>) -> BindKeywords {
+fn parse_fn_type_expr(pair: &Pair<'_, Rule>) -> Result<BindKeywords, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match &pair.as_rule() {
         Rule::fnTypeLiteral => parse_fn_type_literal(&pair),
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_fn_type_literal(pair: &Pair<'_, Rule>) -> BindKeywords {
+fn parse_fn_type_literal(pair: &Pair<'_, Rule>) -> Result<BindKeywords, Error> {
     match &pair.as_rule() {
         Rule::fnTypeLiteral => {
             let pairs = pair.clone().into_inner();
@@ -945,122 +1860,156 @@ fn parse_fn_type_literal(pair: &Pair<'_, Rule>) -> BindKeywords {
                 .map(|item| FunctionSignatureItem::Reference(parse_type_symbol(&item)))
                 .collect::<Vec<_>>();
 
-            BindKeywords::Function(FunctionSignature(types))
+            Ok(BindKeywords::Function(FunctionSignature(types)))
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
 fn inner_len(pair: &Pair<'_, Rule>) -> usize {
-    pair.clone()
-        .into_inner()
-        .map(|_item| true)
-        .collect::<Vec<_>>()
-        .len()
+    pair.clone().into_inner().count()
 }
 
-fn parse_type_expr(pair: &Pair<'_, Rule>) -> TypeExpr {
+fn parse_type_expr(pair: &Pair<'_, Rule>) -> Result<TypeExpr, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::bindTypeExpr => TypeExpr::Bind(parse_type_bind_expr(&pair)),
+        Rule::bindTypeExpr => Ok(TypeExpr::Bind(parse_type_bind_expr(&pair)?)),
         Rule::assignTypeExpr => parse_type_assign_expr(&pair),
-        Rule::typeExpr => {
-            unimplemented!()
-        }
-        _ => panic!(),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_type_bind_expr(pair: &Pair<'_, Rule>) -> TypeSymbol {
+fn parse_type_bind_expr(pair: &Pair<'_, Rule>) -> Result<TypeSymbol, Error> {
     match inner_len(pair) {
         2 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            TypeSymbol {
+            Ok(TypeSymbol {
                 name: parse_type_symbol(lhs),
-                kind: parse_type_keyword(rhs),
-            }
+                kind: parse_type_keyword(rhs)?,
+                exported: false,
+                location: Location::of(lhs),
+            })
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_type_assign_expr(pair: &Pair<'_, Rule>) -> TypeExpr {
-    let a = pair.clone().into_inner();
-    let size = pair
-        .clone()
-        .into_inner()
-        .map(|_item| true)
-        .collect::<Vec<_>>()
-        .len();
-    match size {
+fn parse_type_assign_expr(pair: &Pair<'_, Rule>) -> Result<TypeExpr, Error> {
+    match inner_len(pair) {
         2 => {
-            let a = a.map(|item| item).collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            TypeExpr::Assign {
-                symbol: parse_type_bind_expr(lhs),
-                value: parse_type_value(rhs),
-            }
+            Ok(TypeExpr::Assign {
+                symbol: parse_type_bind_expr(lhs)?,
+                value: parse_type_value(rhs)?,
+            })
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_type_value(pair: &Pair<'_, Rule>) -> TypeValue {
-    match pair.as_rule() {
-        Rule::typeExpr => {
-            let pair = pair.clone().into_inner().next().unwrap();
+/// A type expression, where `&` binds tighter than `|`.
+fn parse_type_value(pair: &Pair<'_, Rule>) -> Result<TypeValue, Error> {
+    let mut operands = Vec::new();
+    let mut ops = Vec::new();
+    parse_type_operands(pair, &mut operands, &mut ops)?;
 
-            parse_type_literal(&pair)
+    let location = Location::of(pair);
+    let combine = |mut items: Vec<TypeValue>, combined: fn(Vec<TypeValue>, Location) -> TypeValue| {
+        match items.len() {
+            1 => items.pop().unwrap(),
+            _ => combined(items, location),
+        }
+    };
+
+    let mut operands = operands.into_iter();
+    let mut unions = vec![vec![operands.next().unwrap()]];
+    for (rule, operand) in ops.into_iter().zip(operands) {
+        match rule {
+            Rule::typeOrOp => unions.push(vec![operand]),
+            _ => unions.last_mut().unwrap().push(operand),
         }
-        _ => unimplemented!(),
     }
+    let unions = unions
+        .into_iter()
+        .map(|items| combine(items, TypeValue::Intersection))
+        .collect();
+
+    Ok(combine(unions, TypeValue::Union))
 }
 
-fn parse_type_literal(pair: &Pair<'_, Rule>) -> TypeValue {
+/// The terms of a `typeExpr` and the operators between them, which the
+/// grammar nests to the right.
+fn parse_type_operands(
+    pair: &Pair<'_, Rule>,
+    operands: &mut Vec<TypeValue>,
+    ops: &mut Vec<Rule>,
+) -> Result<(), Error> {
+    let mut inner = pair.clone().into_inner();
+
+    let term = inner.next().unwrap();
+    operands.push(match term.as_rule() {
+        // in parentheses
+        Rule::typeExpr => parse_type_value(&term)?,
+        Rule::typeSymbol | Rule::qualifiedTypeSymbol => TypeValue::Reference(TypeSymbolReference(
+            parse_type_symbol(&term),
+            Location::of(&term),
+        )),
+        _ => parse_type_literal(&term)?,
+    });
+
+    if let Some(op) = inner.next() {
+        ops.push(op.into_inner().next().unwrap().as_rule());
+        parse_type_operands(&inner.next().unwrap(), operands, ops)?;
+    }
+
+    Ok(())
+}
+
+fn parse_type_literal(pair: &Pair<'_, Rule>) -> Result<TypeValue, Error> {
     match pair.as_rule() {
         Rule::typeLiteral => {
             let pair = pair.clone().into_inner().next().unwrap();
 
             match pair.as_rule() {
-                Rule::stateMachineTypeExpr => {
-                    TypeValue::StateMachine(parse_state_machine_type_expr(&pair))
+                Rule::stateMachineTypeExpr => Ok(TypeValue::StateMachine(
+                    parse_state_machine_type_expr(&pair)?,
+                )),
+                Rule::snapshotTypeExpr => {
+                    Ok(TypeValue::Snapshot(parse_snapshot_type_expr(&pair)?))
                 }
-                Rule::snapshotTypeExpr => TypeValue::Snapshot(parse_snapshot_type_expr(&pair)),
-                Rule::contextedTypeExpr => TypeValue::Context(parse_context_type_expr(&pair)),
-                Rule::typeLiteral => TypeValue::Context(parse_context_type_expr(&pair)),
-                _ => unimplemented!(),
+                Rule::contextedTypeExpr => Ok(TypeValue::Context(parse_context_type_expr(&pair)?)),
+                Rule::typeLiteral => Ok(TypeValue::Context(parse_context_type_expr(&pair)?)),
+                _ => Err(Error::unsupported(&pair)),
             }
         }
-        _ => unimplemented!(),
+        Rule::typeExpr => parse_type_value(pair),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_type_expr(pair: &Pair<'_, Rule>) -> SnapshotType {
+fn parse_snapshot_type_expr(pair: &Pair<'_, Rule>) -> Result<SnapshotType, Error> {
     match pair.as_rule() {
         Rule::snapshotTypeExpr => {
             let pair = pair.clone().into_inner().next().unwrap();
 
             parse_snapshot_type_literal(&pair)
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_type_literal(pair: &Pair<'_, Rule>) -> SnapshotType {
+fn parse_snapshot_type_literal(pair: &Pair<'_, Rule>) -> Result<SnapshotType, Error> {
     // TODO: & によるStateMachineの合成がある。あと、途中に式が挟まることがある
     match pair.as_rule() {
         Rule::snapshotTypeLiteral => {
@@ -1068,90 +2017,87 @@ fn parse_snapshot_type_literal(pair: &Pair<'_, Rule>) -> SnapshotType {
                 .clone()
                 .into_inner()
                 .map(|item| parse_snapshot_type_item_literal(&item))
-                .collect::<Vec<_>>();
+                .collect::<Result<Vec<_>, _>>()?;
 
-            SnapshotType(contexts)
+            Ok(SnapshotType(contexts))
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_type_item_literal(pair: &Pair<'_, Rule>) -> ContextType {
+fn parse_snapshot_type_item_literal(pair: &Pair<'_, Rule>) -> Result<ContextType, Error> {
     match inner_len(pair) {
-        1 => unimplemented!(),
         2 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            ContextType(parse_context_label(lhs), parse_effect_type_expr(rhs))
+            Ok(ContextType(
+                parse_context_label(lhs)?,
+                parse_effect_type_expr(rhs)?,
+            ))
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_context_label(pair: &Pair<'_, Rule>) -> ContextLabel {
-    ContextLabel(parse_var_symbol(pair).0)
+fn parse_context_label(pair: &Pair<'_, Rule>) -> Result<ContextLabel, Error> {
+    Ok(ContextLabel(parse_var_symbol(pair)?.0, Location::of(pair)))
 }
 
-fn parse_var_symbol(pair: &Pair<'_, Rule>) -> ValueSymbolName {
+fn parse_var_symbol(pair: &Pair<'_, Rule>) -> Result<ValueSymbolName, Error> {
     match pair.as_rule() {
-        Rule::varSymbol => ValueSymbolName(pair.as_span().as_str().to_string()),
-        _ => panic!(),
+        Rule::varSymbol => Ok(ValueSymbolName(pair.as_span().as_str().to_string())),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_effect_type_expr(pair: &Pair<'_, Rule>) -> EffectType {
+fn parse_effect_type_expr(pair: &Pair<'_, Rule>) -> Result<EffectType, Error> {
     match pair.as_rule() {
         Rule::effectTypeExpr => {
             let pair = pair.clone().into_inner().next().unwrap();
 
             parse_effect_type_literal(&pair)
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_effect_type_literal(pair: &Pair<'_, Rule>) -> EffectType {
+fn parse_effect_type_literal(pair: &Pair<'_, Rule>) -> Result<EffectType, Error> {
     match pair.as_rule() {
         Rule::effectTypeLiteral => {
             let pair = pair.clone().into_inner().next().unwrap();
 
             match pair.as_rule() {
-                Rule::intContextTypeLiteral => parse_int_context_type_literal(&pair),
-                Rule::typeSymbol => EffectType::TypeSymbolReference(parse_type_symbol(&pair)),
-                _ => unimplemented!(),
+                Rule::intContextTypeLiteral => Ok(parse_int_context_type_literal(&pair)),
+                Rule::typeSymbol | Rule::qualifiedTypeSymbol => {
+                    Ok(EffectType::TypeSymbolReference(parse_type_symbol(&pair)))
+                }
+                _ => Err(Error::unsupported(&pair)),
             }
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_int_context_type_literal(pair: &Pair<'_, Rule>) -> EffectType {
-    match pair.as_rule() {
-        Rule::intContextTypeLiteral => EffectType::Int,
-        _ => panic!(),
-    }
+fn parse_int_context_type_literal(_pair: &Pair<'_, Rule>) -> EffectType {
+    EffectType::Int
 }
 
-fn parse_state_machine_type_expr(pair: &Pair<'_, Rule>) -> StateMachine {
+fn parse_state_machine_type_expr(pair: &Pair<'_, Rule>) -> Result<StateMachine, Error> {
     match pair.as_rule() {
         Rule::stateMachineTypeExpr => {
             let pair = pair.clone().into_inner().next().unwrap();
 
             parse_state_machine_type_literal(&pair)
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_state_machine_type_literal(pair: &Pair<'_, Rule>) -> StateMachine {
+fn parse_state_machine_type_literal(pair: &Pair<'_, Rule>) -> Result<StateMachine, Error> {
     // TODO: & によるStateMachineの合成がある。あと、途中に式が挟まることがある
     match pair.as_rule() {
         Rule::stateMachineTypeLiteral => {
@@ -1159,376 +2105,651 @@ fn parse_state_machine_type_literal(pair: &Pair<'_, Rule>) -> StateMachine {
                 .clone()
                 .into_inner()
                 .map(|item| parse_state_expr(&item))
-                .collect::<Vec<_>>();
+                .collect::<Result<Vec<_>, _>>()?;
 
-            StateMachine(states)
+            Ok(StateMachine(states))
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_state_expr(pair: &Pair<'_, Rule>) -> StateLabel {
+fn parse_state_expr(pair: &Pair<'_, Rule>) -> Result<StateLabel, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::stateLiteral => parse_state_literal(&pair),
-        Rule::idLiteral => StateLabel::Id,
-        Rule::emptyLiteral => StateLabel::Empty,
-        _ => unimplemented!(),
+        Rule::stateLiteral => Ok(parse_state_literal(&pair)),
+        Rule::idLiteral => Ok(StateLabel::Id),
+        Rule::emptyLiteral => Ok(StateLabel::Empty),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
 fn parse_state_literal(pair: &Pair<'_, Rule>) -> StateLabel {
     let pair = pair.clone().into_inner().next().unwrap();
 
-    match pair.as_rule() {
-        Rule::likeString => StateLabel::Label(pair.as_span().as_str().to_string()),
-        _ => unimplemented!(),
-    }
+    StateLabel::Label(pair.as_span().as_str().to_string())
 }
 
 fn parse_type_symbol(pair: &Pair<'_, Rule>) -> TypeSymbolName {
     TypeSymbolName(pair.as_span().as_str().to_string())
 }
 
-fn parse_type_keyword(pair: &Pair<'_, Rule>) -> TypeKind {
-    // match pair.as_span().as_str() {
-    //     "Slice" => BindKeywords::Slice,
-    //     "Effect" => BindKeywords::Effect,
-    //     "ContextEffect" => BindKeywords::ContextEffect,
-    //     "Context" => BindKeywords::Context,
-    //     "Snapshot" => BindKeywords::Snapshot,
-    //     _ => unimplemented!(),
-    // }
+fn parse_type_keyword(pair: &Pair<'_, Rule>) -> Result<TypeKind, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     parse_type_kind(&pair)
 }
 
-fn parse_type_kind(pair: &Pair<'_, Rule>) -> TypeKind {
+fn parse_type_kind(pair: &Pair<'_, Rule>) -> Result<TypeKind, Error> {
     match pair.as_rule() {
-        Rule::stateMachineKeyword => TypeKind::StateMachine,
-        Rule::snapshotKeyword => TypeKind::Snapshot,
-        Rule::contextKeyword => TypeKind::Context,
-        _ => panic!(),
-    }
-}
-
-fn parse_op(pair: &Pair<'_, Rule>) -> Op {
+        Rule::stateMachineKeyword => Ok(TypeKind::StateMachine),
+        Rule::snapshotKeyword => Ok(TypeKind::Snapshot),
+        Rule::contextKeyword => Ok(TypeKind::Context),
+        Rule::fnTypeExpr => match pair.as_span().as_str().trim() {
+            "StateMachine" => Ok(TypeKind::StateMachine),
+            "Snapshot" => Ok(TypeKind::Snapshot),
+            "Context" => Ok(TypeKind::Context),
+            name => Err(Error::new(
+                ErrorKind::Unsupported,
+                &format!("`{}` is not a type kind", name),
+                Span::of(pair),
+            )),
+        },
+        _ => Err(Error::unsupported(pair)),
+    }
+}
+
+fn parse_op(pair: &Pair<'_, Rule>) -> Result<Op, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
     match pair.as_rule() {
-        Rule::composeOp => return Op::Compose,
-        Rule::applyOp => return Op::Apply,
-        Rule::reduceOp => return Op::Reduce,
-        Rule::pushOp => return Op::Push,
-        _ => unimplemented!(),
+        Rule::composeOp => Ok(Op::Compose),
+        Rule::applyOp => Ok(Op::Apply),
+        Rule::reduceOp => Ok(Op::Reduce),
+        Rule::reduceAllOp => Ok(Op::ReduceAll),
+        Rule::pushOp => Ok(Op::Push),
+        Rule::addOp => Ok(Op::Add),
+        Rule::orOp => Ok(Op::Or),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_expr(pair: &Pair<'_, Rule>) -> Expr {
+fn parse_expr(pair: &Pair<'_, Rule>) -> Result<Expr, Error> {
+    let parse_operand = |pair: &Pair<'_, Rule>| match pair.as_rule() {
+        Rule::term => parse_term(pair),
+        Rule::expr => parse_expr(pair),
+        _ => Err(Error::unsupported(pair)),
+    };
+
     match inner_len(pair) {
         1 => {
             let v = pair.clone().into_inner().next().unwrap();
+
             match v.as_rule() {
-                Rule::term => parse_term(&v),
-                Rule::expr => parse_expr(&v),
-                _ => panic!(),
+                Rule::outputExpr => parse_output_expr(&v),
+                _ => parse_operand(&v),
             }
         }
         3 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
+            let lhs = parse_operand(a.next().unwrap())?;
             let v = a.next().unwrap();
-            let lhs = match v.as_rule() {
-                Rule::term => parse_term(&v),
-                Rule::expr => parse_expr(&v),
-                _ => panic!(),
-            };
+            let op = parse_op(v)?;
+            let rhs = parse_operand(a.next().unwrap())?;
+
+            Ok(Expr::Op(
+                op,
+                Box::new(lhs),
+                Box::new(rhs),
+                Location::of(v),
+            ))
+        }
+        _ => Err(Error::unsupported(pair)),
+    }
+}
 
-            let v = a.next().unwrap();
-            let op = parse_op(&v);
+fn parse_output_expr(pair: &Pair<'_, Rule>) -> Result<Expr, Error> {
+    let mut inner = pair.clone().into_inner().collect::<Vec<_>>();
+    let expr = parse_expr(&inner.pop().unwrap())?;
+    let label = match inner.first() {
+        Some(label) => Some(parse_var_symbol(&label.clone().into_inner().next().unwrap())?.0),
+        None => None,
+    };
 
-            let v = a.next().unwrap();
-            let rhs = match v.as_rule() {
-                Rule::term => parse_term(&v),
-                Rule::expr => parse_expr(&v),
-                _ => panic!(),
-            };
+    Ok(Expr::Output(label, Box::new(expr), Location::of(pair)))
+}
 
-            Expr::Op(op, Box::new(lhs), Box::new(rhs))
-        }
-        _ => panic!(),
-    }
+fn parse_term(pair: &Pair<'_, Rule>) -> Result<Expr, Error> {
+    let mut inner = pair.clone().into_inner();
+    let callee = parse_term_value(&inner.next().unwrap())?;
+
+    inner.try_fold(callee, |callee, call| {
+        let args = call
+            .clone()
+            .into_inner()
+            .map(|arg| parse_expr(&arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Expr::Call(Box::new(callee), args, Location::of(&call)))
+    })
 }
 
-fn parse_term(pair: &Pair<'_, Rule>) -> Expr {
-    let pair = pair.clone().into_inner().next().unwrap();
-    dbg!(&pair);
+fn parse_term_value(pair: &Pair<'_, Rule>) -> Result<Expr, Error> {
+    let pair = pair.clone();
 
-    match pair.as_rule() {
-        Rule::expr => parse_expr(&pair),
-        Rule::varSymbol => Expr::Reference(ValueSymbolReference(parse_var_symbol(&pair))),
+    let expr = match pair.as_rule() {
+        Rule::expr => parse_expr(&pair)?,
+        Rule::varSymbol => Expr::Reference(ValueSymbolReference(
+            parse_var_symbol(&pair)?,
+            Location::of(&pair),
+        )),
+        Rule::qualifiedVarSymbol => Expr::Reference(ValueSymbolReference(
+            ValueSymbolName(pair.as_str().to_string()),
+            Location::of(&pair),
+        )),
         Rule::addEffectLiteral => Expr::Id(Value::Effect(Effect::AddEffect(
-            parse_add_effect_literal(&pair),
+            parse_add_effect_literal(&pair)?,
         ))),
         Rule::transitionEffectLiteral => Expr::Id(Value::Effect(Effect::TransitionEffect(
-            parse_transition_effect_literal(&pair),
+            parse_transition_effect_literal(&pair)?,
         ))),
         Rule::idLiteral => Expr::Id(Value::Id),
         Rule::emptyLiteral => Expr::Id(Value::Empty),
-        Rule::sliceLiteral => Expr::Id(Value::Slice(parse_slice_expr(&pair))),
-        Rule::snapshotLiteral => Expr::Id(Value::Snapshot(parse_snapshot_value_expr(&pair))),
+        Rule::sliceLiteral => Expr::Id(Value::Slice(parse_slice_expr(&pair)?)),
+        Rule::snapshotLiteral => Expr::Id(Value::Snapshot(parse_snapshot_value_expr(&pair)?)),
         Rule::contextEffectLiteral => {
-            Expr::Id(Value::ContextEffect(parse_context_effect_expr(&pair)))
+            Expr::Id(Value::ContextEffect(parse_context_effect_expr(&pair)?))
         }
         Rule::contextTypeLiteral => {
-            let a = parse_context_type_expr(&pair);
-            Expr::Id(Value::Context(a.0.clone()))
+            let a = parse_context_type_expr(&pair)?;
+            Expr::Id(Value::Context(a.0))
         }
-        Rule::effectLiteral => Expr::Id(Value::Effect(parse_effect_literal(&pair))),
+        Rule::effectLiteral => Expr::Id(Value::Effect(parse_effect_literal(&pair)?)),
         Rule::transitionEffectExpr => Expr::Id(Value::Effect(Effect::TransitionEffect(
-            parse_transition_effect_expr(&pair),
+            parse_transition_effect_expr(&pair)?,
         ))),
-        Rule::scopeExpr => Expr::Id(Value::Scope(parse_scope_expr(&pair))),
+        Rule::scopeExpr => Expr::Id(Value::Scope(parse_scope_expr(&pair)?)),
         Rule::fnExpr => {
-            parse_fn_expr(&pair);
-            unimplemented!()
+            let functions = parse_fn_expr(&pair)?;
+
+            match functions.as_slice() {
+                // `{ ... }` without patterns is a plain scope
+                [Function { args, scope }] if args.is_empty() => {
+                    Expr::Id(Value::Scope(scope.clone()))
+                }
+                [first, rest @ ..] => {
+                    if rest.iter().any(|item| item.args.len() != first.args.len()) {
+                        return Err(Error::new(
+                            ErrorKind::Type,
+                            "the alternatives of a function must take the same number of arguments",
+                            Span::of(&pair),
+                        ));
+                    }
+
+                    Expr::Id(Value::Function(Closure::new(functions)))
+                }
+                [] => return Err(Error::unsupported(&pair)),
+            }
         }
-        _ => panic!(),
-    }
+        Rule::intLiteral => Expr::Id(Value::Int(parse_int_literal(&pair)?)),
+        _ => return Err(Error::unsupported(&pair)),
+    };
+
+    Ok(expr)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Function {
-    args: Pattern,
-    scope: ScopeValue,
+    pub(crate) args: Vec<Pattern>,
+    pub(crate) scope: ScopeValue,
+}
+
+impl Function {
+    fn matches(&self, args: &[Value]) -> bool {
+        self.args
+            .iter()
+            .zip(args)
+            .all(|(pattern, value)| match (pattern, value) {
+                (Pattern::Symbol(_), _) => true,
+                (Pattern::Effect(pattern), Value::Effect(value)) => pattern == value,
+                (Pattern::Int(pattern), Value::Int(value)) => pattern == value,
+                _ => false,
+            })
+    }
 }
 
-fn parse_fn_expr(pair: &Pair<'_, Rule>) -> Function {
-    let pairs = pair.clone().into_inner();
-    let vec = pairs.map(|item| item).collect::<Vec<_>>();
-    let idx = vec.partition_point(|item| item.as_rule() == Rule::scopeExpr);
+/// A function value: alternatives chosen by pattern, the arguments of a
+/// partial application and the scopes it was defined in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Closure {
+    pub(crate) alternatives: Vec<Function>,
+    applied: Vec<Value>,
+    #[serde(skip)]
+    captured: Vec<VmScope>,
+    /// The types of the arguments and of the result, from the `let` the
+    /// function was bound with. Empty when it was not declared.
+    #[serde(skip)]
+    signature: Vec<FunctionSignatureItem>,
+}
+
+impl Closure {
+    fn new(alternatives: Vec<Function>) -> Self {
+        Self {
+            alternatives,
+            applied: Vec::new(),
+            captured: Vec::new(),
+            signature: Vec::new(),
+        }
+    }
+
+    /// The function declared with `signature`, whose calls are checked
+    /// against it.
+    pub(crate) fn with_signature(&self, FunctionSignature(items): &FunctionSignature) -> Self {
+        Self {
+            signature: items.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Whether `args`, from the first argument of the function on, are of
+    /// the types of the signature.
+    fn check_arguments(&self, args: &[Value], span: Span) -> Result<(), Error> {
+        let params = self.signature.len().saturating_sub(1);
+        match args
+            .iter()
+            .zip(&self.signature[..params])
+            .position(|(arg, item)| !item.kind().assignable(arg))
+        {
+            Some(index) => Err(Error::new(
+                ErrorKind::Type,
+                &format!(
+                    "argument {} is {}, but the function takes {}",
+                    index + 1,
+                    args[index].kind(),
+                    self.signature[index].kind()
+                ),
+                span,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// `value` returned after `arity` arguments, checked against the rest of
+    /// the signature. A function returned for the other arguments takes the
+    /// rest of the signature along.
+    fn check_result(&self, value: Value, span: Span) -> Result<Value, Error> {
+        let rest = match self.signature.get(self.arity()..) {
+            Some(rest) if !rest.is_empty() => rest,
+            _ => return Ok(value),
+        };
+
+        match (rest, value) {
+            ([result], value) if result.kind().assignable(&value) => Ok(value),
+            ([_, ..], Value::Function(closure)) if rest.len() > 1 => {
+                Ok(Value::Function(Closure {
+                    signature: rest.to_vec(),
+                    ..closure
+                }))
+            }
+            (_, value) => Err(Error::new(
+                ErrorKind::Type,
+                &format!(
+                    "the function returns {}, but is declared to return {}",
+                    value.kind(),
+                    BindKeywords::Function(FunctionSignature(rest.to_vec()))
+                ),
+                span,
+            )),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.alternatives
+            .first()
+            .map(|item| item.args.len())
+            .unwrap_or(0)
+    }
+
+    /// Calls the last alternative that matches the arguments. Fewer
+    /// arguments than the arity give a curried function, more are passed to
+    /// the function it returns. The returned `Vm` is `vm` with the outputs
+    /// of the call.
+    pub fn call(&self, vm: &Vm, args: Vec<Value>, span: Span) -> Result<(Vm, Value), Error> {
+        let mut applied = self.applied.clone();
+        applied.extend(args);
+        self.check_arguments(&applied, span)?;
+        if applied.len() < self.arity() {
+            return Ok((
+                vm.clone(),
+                Value::Function(Closure {
+                    applied,
+                    ..self.clone()
+                }),
+            ));
+        }
+
+        let rest = applied.split_off(self.arity());
+        let function = self
+            .alternatives
+            .iter()
+            .rev()
+            .find(|item| item.matches(&applied))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Evaluation,
+                    "no alternative of the function matches the arguments",
+                    span,
+                )
+            })?;
+
+        let mut inner = Vm {
+            stack: self.captured.clone(),
+            bindings: Vec::new(),
+            outputs: vm.outputs.clone(),
+            tracer: vm.tracer.clone(),
+        };
+        inner.push_stack();
+        function
+            .args
+            .iter()
+            .zip(&applied)
+            .for_each(|(pattern, value)| {
+                if let Pattern::Symbol(name) = pattern {
+                    inner.current_scope().bind_argument(name, value);
+                    inner.trace_bind(name, value);
+                }
+            });
+        let (mut inner, value) = function.scope.eval(&inner)?;
+        inner.pop_stack();
+        let vm = Vm {
+            outputs: inner.outputs,
+            ..vm.clone()
+        };
+        let value = self.check_result(value, span)?;
+
+        match (value, rest.is_empty()) {
+            (value, true) => Ok((vm, value)),
+            (Value::Function(closure), false) => closure.call(&vm, rest, span),
+            (_, false) => Err(Error::new(
+                ErrorKind::Evaluation,
+                "the function is called with too many arguments",
+                span,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pattern {
+    Symbol(ValueSymbolName),
+    Effect(Effect),
+    Int(IntLiteral),
+}
+
+fn parse_fn_expr(pair: &Pair<'_, Rule>) -> Result<Vec<Function>, Error> {
+    pair.clone()
+        .into_inner()
+        .map(|item| parse_fn_literal(&item))
+        .collect()
+}
+
+fn parse_fn_literal(pair: &Pair<'_, Rule>) -> Result<Function, Error> {
+    let vec = pair.clone().into_inner().collect::<Vec<_>>();
+    let idx = vec.partition_point(|item| item.as_rule() == Rule::patternExpr);
     let args = vec[..idx]
         .iter()
-        .map(|item| parse_pattern_expr(item))
-        .collect::<Vec<_>>();
-    let scope = vec[idx..]
-        .iter()
-        .map(|item| parse_scope_expr(item))
-        .collect::<Vec<_>>();
+        .map(parse_pattern_expr)
+        .collect::<Result<Vec<_>, _>>()?;
+    let scope = parse_scope_expr(&vec[idx])?;
 
-    Function { args, scope }
+    Ok(Function { args, scope })
 }
 
-fn parse_fn_literal(pair: &Pair<'_, Rule>) -> Effect {
-    dbg!(pair);
-    // pairs.map(|item|{
-    //     dbg!(&item);
-    // })
-    unimplemented!()
+fn parse_pattern_expr(pair: &Pair<'_, Rule>) -> Result<Pattern, Error> {
+    let pair = pair.clone().into_inner().next().unwrap();
+
+    match pair.as_rule() {
+        Rule::patternExpr => parse_pattern_expr(&pair),
+        Rule::matchExpr => {
+            let pair = pair.clone().into_inner().next().unwrap();
+
+            match pair.as_rule() {
+                Rule::varSymbol => Ok(Pattern::Symbol(parse_var_symbol(&pair)?)),
+                Rule::effectLiteral => Ok(Pattern::Effect(parse_effect_literal(&pair)?)),
+                Rule::intLiteral => Ok(Pattern::Int(parse_int_literal(&pair)?)),
+                _ => Err(Error::unsupported(&pair)),
+            }
+        }
+        _ => Err(Error::unsupported(&pair)),
+    }
 }
 
-fn parse_effect_literal(pair: &Pair<'_, Rule>) -> Effect {
+fn parse_effect_literal(pair: &Pair<'_, Rule>) -> Result<Effect, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::idLiteral => Effect::Id,
-        Rule::emptyLiteral => Effect::Empty,
-        Rule::addEffectExpr => Effect::AddEffect(parse_add_effect_expr(&pair)),
-        Rule::transitionEffectExpr => Effect::TransitionEffect(parse_transition_effect_expr(&pair)),
-        _ => panic!(),
+        Rule::idLiteral => Ok(Effect::Id),
+        Rule::emptyLiteral => Ok(Effect::Empty),
+        Rule::addEffectExpr => Ok(Effect::AddEffect(parse_add_effect_expr(&pair)?)),
+        Rule::transitionEffectExpr => Ok(Effect::TransitionEffect(
+            parse_transition_effect_expr(&pair)?,
+        )),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_context_type_expr(pair: &Pair<'_, Rule>) -> ContextType {
+fn parse_context_type_expr(pair: &Pair<'_, Rule>) -> Result<ContextType, Error> {
     match inner_len(pair) {
         1 => {
             let pair = pair.clone().into_inner().next().unwrap();
             parse_context_type_expr(&pair)
         }
         2 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            ContextType(parse_context_label(lhs), parse_effect_type_expr(rhs))
+            Ok(ContextType(
+                parse_context_label(lhs)?,
+                parse_effect_type_expr(rhs)?,
+            ))
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_value_expr(pair: &Pair<'_, Rule>) -> SnapshotValue {
+fn parse_snapshot_value_expr(pair: &Pair<'_, Rule>) -> Result<SnapshotValue, Error> {
     match pair.as_rule() {
         Rule::snapshotLiteral => parse_snapshot_value_literal(pair),
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_value_literal(pair: &Pair<'_, Rule>) -> SnapshotValue {
+fn parse_snapshot_value_literal(pair: &Pair<'_, Rule>) -> Result<SnapshotValue, Error> {
     let a = pair
         .clone()
         .into_inner()
         .map(|item| parse_snapshot_value_item_expr(&item))
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, _>>()?;
 
-    SnapshotValue(a)
+    Ok(SnapshotValue(a))
 }
 
-fn parse_snapshot_value_item_expr(pair: &Pair<'_, Rule>) -> SnapshotValueItem {
+fn parse_snapshot_value_item_expr(pair: &Pair<'_, Rule>) -> Result<SnapshotValueItem, Error> {
     match pair.as_rule() {
         Rule::snapshotItemLiteral => parse_snapshot_value_item_literal(pair),
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_value_item_literal(pair: &Pair<'_, Rule>) -> SnapshotValueItem {
+fn parse_snapshot_value_item_literal(pair: &Pair<'_, Rule>) -> Result<SnapshotValueItem, Error> {
     match inner_len(pair) {
         2 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            SnapshotValueItem(
-                parse_context_expr(lhs),
-                parse_snapshot_value_item_value_expr(rhs),
-            )
+            Ok(SnapshotValueItem(
+                parse_context_expr(lhs)?,
+                parse_snapshot_value_item_value_expr(rhs)?,
+            ))
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_value_item_value_expr(pair: &Pair<'_, Rule>) -> SnapshotValueItemValue {
+fn parse_snapshot_value_item_value_expr(
+    pair: &Pair<'_, Rule>,
+) -> Result<SnapshotValueItemValue, Error> {
     match pair.as_rule() {
         Rule::snapshotValueExpr => {
             let pair = pair.clone().into_inner().next().unwrap();
             parse_snapshot_value_item_value_expr(&pair)
         }
         Rule::snapshotValueLiteral => parse_snapshot_value_item_value_literal(pair),
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_snapshot_value_item_value_literal(pair: &Pair<'_, Rule>) -> SnapshotValueItemValue {
+fn parse_snapshot_value_item_value_literal(
+    pair: &Pair<'_, Rule>,
+) -> Result<SnapshotValueItemValue, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::intLiteral => SnapshotValueItemValue::Int(parse_int_literal(&pair)),
-        Rule::stateExpr => SnapshotValueItemValue::StateLabel(parse_state_expr(&pair)),
-        Rule::idLiteral => SnapshotValueItemValue::Id,
-        Rule::emptyLiteral => SnapshotValueItemValue::Empty,
-        _ => unimplemented!(),
+        Rule::intLiteral => Ok(SnapshotValueItemValue::Int(parse_int_literal(&pair)?)),
+        Rule::stateExpr => Ok(SnapshotValueItemValue::StateLabel(parse_state_expr(&pair)?)),
+        Rule::idLiteral => Ok(SnapshotValueItemValue::Id),
+        Rule::emptyLiteral => Ok(SnapshotValueItemValue::Empty),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_slice_expr(pair: &Pair<'_, Rule>) -> Slice {
+fn parse_slice_expr(pair: &Pair<'_, Rule>) -> Result<Slice, Error> {
     parse_slice_literal(pair)
 }
 
-fn parse_slice_literal(pair: &Pair<'_, Rule>) -> Slice {
+fn parse_slice_literal(pair: &Pair<'_, Rule>) -> Result<Slice, Error> {
     let a = pair
         .clone()
         .into_inner()
-        .map(|item| parse_context_effect_expr(&item))
-        .collect::<Vec<_>>();
+        .map(|item| match item.as_rule() {
+            Rule::branchLiteral => Ok(SliceItem::Branch(parse_branch_literal(&item)?)),
+            _ => Ok(SliceItem::ContextEffect(parse_context_effect_expr(&item)?)),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
 
-    Slice(a)
+    Ok(Slice(a))
 }
 
-fn parse_context_effect_expr(pair: &Pair<'_, Rule>) -> ContextEffect {
+fn parse_branch_literal(pair: &Pair<'_, Rule>) -> Result<Branch, Error> {
+    let routes = pair
+        .clone()
+        .into_inner()
+        .map(|route| {
+            let mut inner = route.into_inner();
+            let label = inner.next().unwrap();
+            let slice = inner.next().unwrap();
+
+            Ok((
+                RouteLabel(parse_var_symbol(&label)?.0, Location::of(&label)),
+                parse_slice_literal(&slice)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(Branch { routes })
+}
+
+fn parse_context_effect_expr(pair: &Pair<'_, Rule>) -> Result<ContextEffect, Error> {
     match inner_len(pair) {
         1 => {
             let pair = pair.clone().into_inner().next().unwrap();
             parse_context_effect_literal(&pair)
         }
-        2 => parse_context_effect_literal(&pair),
-        _ => panic!(),
+        2 => parse_context_effect_literal(pair),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_context_effect_literal(pair: &Pair<'_, Rule>) -> ContextEffect {
+fn parse_context_effect_literal(pair: &Pair<'_, Rule>) -> Result<ContextEffect, Error> {
     match inner_len(pair) {
         2 => {
-            let a = pair
-                .clone()
-                .into_inner()
-                .map(|item| item)
-                .collect::<Vec<_>>();
+            let a = pair.clone().into_inner().collect::<Vec<_>>();
             let mut a = a.iter();
 
             let lhs = a.next().unwrap();
             let rhs = a.next().unwrap();
 
-            ContextEffect(parse_context_expr(lhs), parse_effect_expr(rhs))
+            Ok(ContextEffect(
+                parse_context_expr(lhs)?,
+                parse_effect_expr(rhs)?,
+            ))
         }
-        _ => panic!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_context_expr(pair: &Pair<'_, Rule>) -> ContextLabel {
+fn parse_context_expr(pair: &Pair<'_, Rule>) -> Result<ContextLabel, Error> {
     match pair.as_rule() {
         Rule::contextExpr => {
             let pair = pair.clone().into_inner().next().unwrap();
             parse_context_type_literal(&pair)
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_context_type_literal(pair: &Pair<'_, Rule>) -> ContextLabel {
+fn parse_context_type_literal(pair: &Pair<'_, Rule>) -> Result<ContextLabel, Error> {
     match pair.as_rule() {
-        Rule::varSymbol => ContextLabel(parse_var_symbol(&pair).0),
+        Rule::varSymbol => parse_context_label(pair),
         Rule::contextLiteral => {
             let pair = pair.clone().into_inner().next().unwrap();
             parse_context_type_literal(&pair)
         }
         Rule::contextTypeLiteral => {
             let pair = pair.clone().into_inner().next().unwrap();
-            ContextLabel(parse_context_label(&pair).0)
-        }
-        _ => {
-            unimplemented!();
+            parse_context_label(&pair)
         }
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_effect_expr(pair: &Pair<'_, Rule>) -> Effect {
+fn parse_effect_expr(pair: &Pair<'_, Rule>) -> Result<Effect, Error> {
     match pair.as_rule() {
         Rule::addEffectLiteral => {
             let pair = pair.clone().into_inner().next().unwrap();
-            Effect::AddEffect(parse_add_effect_literal(&pair))
+            Ok(Effect::AddEffect(parse_add_effect_literal(&pair)?))
         }
         Rule::transitionEffectLiteral => {
             let pair = pair.clone().into_inner().next().unwrap();
-            Effect::TransitionEffect(parse_transition_effect_literal(&pair))
+            Ok(Effect::TransitionEffect(parse_transition_effect_literal(
+                &pair,
+            )?))
         }
-        Rule::idLiteral => Effect::Id,
-        Rule::emptyLiteral => Effect::Empty,
+        Rule::idLiteral => Ok(Effect::Id),
+        Rule::emptyLiteral => Ok(Effect::Empty),
         Rule::effectExpr => {
             let pair = pair.clone().into_inner().next().unwrap();
-            parse_effect_literal(&pair)
+            match pair.as_rule() {
+                Rule::effectLiteral => parse_effect_literal(&pair),
+                _ => Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "effects in slices must be literals",
+                    Span::of(&pair),
+                )),
+            }
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_add_effect_expr(pair: &Pair<'_, Rule>) -> AddEffect {
+fn parse_add_effect_expr(pair: &Pair<'_, Rule>) -> Result<AddEffect, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
@@ -1536,42 +2757,46 @@ fn parse_add_effect_expr(pair: &Pair<'_, Rule>) -> AddEffect {
             let pair = pair.clone().into_inner().next().unwrap();
             parse_add_effect_literal(&pair)
         }
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_add_effect_literal(pair: &Pair<'_, Rule>) -> AddEffect {
+fn parse_add_effect_literal(pair: &Pair<'_, Rule>) -> Result<AddEffect, Error> {
     match pair.as_rule() {
         Rule::addEffectLiteral => {
             let pair = pair.clone().into_inner().next().unwrap();
             parse_add_effect_literal(&pair)
         }
-        Rule::intLiteral => AddEffect(parse_int_literal(&pair).0),
-        _ => unimplemented!(),
+        Rule::intLiteral => Ok(AddEffect(parse_int_literal(pair)?.0)),
+        _ => Err(Error::unsupported(pair)),
     }
 }
 
-fn parse_int_literal(pair: &Pair<'_, Rule>) -> IntLiteral {
-    IntLiteral(pair.as_span().as_str().parse::<i64>().unwrap())
+fn parse_int_literal(pair: &Pair<'_, Rule>) -> Result<IntLiteral, Error> {
+    pair.as_span()
+        .as_str()
+        .parse::<i64>()
+        .map(IntLiteral)
+        .map_err(|_| Error::new(ErrorKind::Syntax, "integer out of range", Span::of(pair)))
 }
 
-fn parse_transition_effect_expr(pair: &Pair<'_, Rule>) -> TransitionEffect {
+fn parse_transition_effect_expr(pair: &Pair<'_, Rule>) -> Result<TransitionEffect, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::idLiteral => TransitionEffect::Id,
-        Rule::emptyLiteral => TransitionEffect::Empty,
+        Rule::idLiteral => Ok(TransitionEffect::Id),
+        Rule::emptyLiteral => Ok(TransitionEffect::Empty),
         Rule::transitionEffectLiteral => parse_transition_effect_literal(&pair),
-        _ => unimplemented!(),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
-fn parse_transition_effect_literal(pair: &Pair<'_, Rule>) -> TransitionEffect {
+fn parse_transition_effect_literal(pair: &Pair<'_, Rule>) -> Result<TransitionEffect, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     match pair.as_rule() {
-        Rule::stateExpr => TransitionEffect::StateLabel(parse_state_expr(&pair)),
-        _ => unimplemented!(),
+        Rule::stateExpr => Ok(TransitionEffect::StateLabel(parse_state_expr(&pair)?)),
+        _ => Err(Error::unsupported(&pair)),
     }
 }
 
@@ -1579,65 +2804,51 @@ fn parse_comment(pair: &Pair<'_, Rule>) -> Comment {
     Comment(pair.as_span().as_str().to_string())
 }
 
-fn parse_scope_inner(pair: &Pair<'_, Rule>) -> ScopeValue {
-    let items = pair
+fn parse_scope_inner(pair: &Pair<'_, Rule>) -> Result<ScopeValue, Error> {
+    let ret = pair
         .clone()
         .into_inner()
-        .map(|item| item)
-        .collect::<Vec<_>>();
-    let pos = items.iter().position(|item| item.as_rule() == Rule::expr);
-    if pos.is_none() {
-        panic!()
-    }
-
-    let ret = items
-        .iter()
         .map(|item| match item.as_rule() {
-            Rule::comment => ScopeInnerValue::Comment(parse_comment(item)),
-            Rule::stmt => ScopeInnerValue::Stmt(parse_stmt(item)),
-            Rule::expr => ScopeInnerValue::Expr(parse_expr(item)),
-            _ => {
-                unimplemented!()
-            }
+            Rule::comment => Ok(ScopeInnerValue::Comment(parse_comment(&item))),
+            Rule::stmt => Ok(ScopeInnerValue::Stmt(parse_stmt(&item)?)),
+            Rule::expr => Ok(ScopeInnerValue::Expr(parse_expr(&item)?)),
+            _ => Err(Error::unsupported(&item)),
         })
-        .collect::<_>();
+        .collect::<Result<_, _>>()?;
 
-    ScopeValue(ret)
+    Ok(ScopeValue(ret))
 }
 
-fn parse_scope_literal(pair: &Pair<'_, Rule>) -> ScopeValue {
+fn parse_scope_literal(pair: &Pair<'_, Rule>) -> Result<ScopeValue, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     parse_scope_inner(&pair)
 }
 
-fn parse_scope_expr(pair: &Pair<'_, Rule>) -> ScopeValue {
+fn parse_scope_expr(pair: &Pair<'_, Rule>) -> Result<ScopeValue, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     parse_scope_literal(&pair)
 }
 
-fn parse_scope_stmt(pair: &Pair<'_, Rule>) -> ScopeValue {
+fn parse_scope_stmt(pair: &Pair<'_, Rule>) -> Result<ScopeValue, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     parse_scope_expr(&pair)
 }
 
-fn parse_root_scope(pair: &Pair<'_, Rule>) -> ScopeValue {
+fn parse_root_scope(pair: &Pair<'_, Rule>) -> Result<ScopeValue, Error> {
     let pair = pair.clone().into_inner().next().unwrap();
 
     parse_scope_inner(&pair)
 }
 
-pub fn to_node(pair: &Pair<'_, Rule>) -> Node {
+pub fn to_node(pair: &Pair<'_, Rule>) -> Result<Node, Error> {
     match pair.as_rule() {
         Rule::document => {
             let pair = pair.clone().into_inner().next().unwrap();
-            Node::Root(parse_root_scope(&pair))
-        }
-        // Rule::EOI => Node::Comment(Comment("".to_string())),
-        _ => {
-            unimplemented!();
+            Ok(Node::Root(parse_root_scope(&pair)?))
         }
+        _ => Err(Error::unsupported(pair)),
     }
 }
@@ -0,0 +1,81 @@
+use crate::checker::Checker;
+use crate::error::Error;
+use crate::parser::{parse_input, ScopeInnerValue, Vm};
+
+/// An interactive session, where every input is checked and evaluated on top
+/// of the ones before it.
+#[derive(Clone)]
+pub struct Session {
+    checker: Checker,
+    vm: Vm,
+}
+
+/// A `let` binding of a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionBinding {
+    pub name: String,
+    /// The declared kind, as written after `::`.
+    pub kind: String,
+    pub value: String,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        let mut vm = Vm::new();
+        vm.push_stack();
+
+        Self {
+            checker: Checker::default(),
+            vm,
+        }
+    }
+
+    /// Evaluates `input` and returns the value of its last expression, or
+    /// `None` when it ends with a statement. A failing input leaves the
+    /// session as it was.
+    pub fn eval(&mut self, input: &str) -> Result<Option<String>, Error> {
+        let scope = parse_input(input)?;
+        let mut checker = self.checker.clone();
+        checker.input(&scope)?;
+        let (vm, value) = scope.eval_in(&self.vm)?;
+
+        self.checker = checker;
+        self.vm = vm;
+
+        let ends_with_expr = matches!(
+            scope
+                .0
+                .iter()
+                .rfind(|item| !matches!(item, ScopeInnerValue::Comment(_))),
+            Some(ScopeInnerValue::Expr(_))
+        );
+
+        Ok(ends_with_expr.then(|| value.to_string()))
+    }
+
+    /// The static type of `input` without evaluating it.
+    pub fn type_of(&self, input: &str) -> Result<String, Error> {
+        let scope = parse_input(input)?;
+
+        Ok(self.checker.clone().input(&scope)?.describe())
+    }
+
+    /// The bindings made so far, in the order they were made.
+    pub fn bindings(&self) -> Vec<SessionBinding> {
+        self.vm
+            .scope_bindings()
+            .into_iter()
+            .map(|(name, kind, value)| SessionBinding {
+                name,
+                kind: kind.to_string(),
+                value: value.to_string(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,280 @@
+use crate::error::{Error, Span};
+use crate::parser::{DocumentParser, Rule};
+use pest::iterators::Pair;
+use pest::Parser;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolKind {
+    /// `let` bindings and function parameters.
+    Value,
+    Type,
+    Context,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,
+    /// Where the declaration can be referenced from.
+    pub scope: Span,
+    /// The declared type as written.
+    pub detail: Option<String>,
+    /// Index into the evaluated bindings for a `let` outside of functions.
+    pub binding: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reference {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EffectKind {
+    Id,
+    Empty,
+    Add,
+    Transition,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbols {
+    pub declarations: Vec<Declaration>,
+    pub references: Vec<Reference>,
+    pub effects: Vec<(EffectKind, Span)>,
+}
+
+impl Symbols {
+    /// The declaration under `offset`, or the one the reference under it
+    /// resolves to, with the span of the symbol under `offset`.
+    pub fn declaration_at(&self, offset: usize) -> Option<(Span, &Declaration)> {
+        match self
+            .declarations
+            .iter()
+            .find(|item| item.span.contains(offset))
+        {
+            Some(declaration) => Some((declaration.span, declaration)),
+            None => {
+                let reference = self
+                    .references
+                    .iter()
+                    .find(|item| item.span.contains(offset))?;
+
+                Some((reference.span, self.resolve(reference)?))
+            }
+        }
+    }
+
+    /// The innermost declaration visible from `reference`. A value reference
+    /// falls back to a context of the same name.
+    pub fn resolve(&self, reference: &Reference) -> Option<&Declaration> {
+        let find = |kind: SymbolKind| {
+            self.declarations
+                .iter()
+                .filter(|item| {
+                    item.kind == kind
+                        && item.name == reference.name
+                        && item.scope.contains(reference.span.start)
+                })
+                .max_by_key(|item| item.scope.start)
+        };
+
+        match reference.kind {
+            SymbolKind::Value => find(SymbolKind::Value).or_else(|| find(SymbolKind::Context)),
+            kind => find(kind),
+        }
+    }
+
+    pub fn contexts(&self) -> BTreeSet<&str> {
+        self.declarations
+            .iter()
+            .filter(|item| item.kind == SymbolKind::Context)
+            .map(|item| item.name.as_str())
+            .collect()
+    }
+}
+
+/// Collects the declarations, references and effect literals of `document`.
+pub fn symbols(document: &str) -> Result<Symbols, Error> {
+    let pairs = DocumentParser::parse(Rule::document, document)?;
+
+    let mut walker = Walker::default();
+    let document_span = Span::new(0, document.len());
+    pairs.for_each(|pair| walker.walk(pair, document_span, document_span));
+
+    // bindings are recorded when a `let` finishes evaluating, so a `let`
+    // nested in another one's value comes first
+    walker.lets.sort();
+    walker
+        .lets
+        .iter()
+        .enumerate()
+        .for_each(|(binding, (_, idx))| {
+            walker.symbols.declarations[*idx].binding = Some(binding)
+        });
+
+    Ok(walker.symbols)
+}
+
+#[derive(Default)]
+struct Walker {
+    symbols: Symbols,
+    /// `(end of statement, declaration index)` of `let`s outside of functions.
+    lets: Vec<(usize, usize)>,
+    function_depth: usize,
+}
+
+impl Walker {
+    fn walk(&mut self, pair: Pair<'_, Rule>, scope: Span, document: Span) {
+        match pair.as_rule() {
+            // labels of `Output` are not symbols
+            Rule::comment | Rule::outputLabel => {}
+            Rule::scopeLiteral => {
+                let span = Span::of(&pair);
+                pair.into_inner()
+                    .for_each(|item| self.walk(item, span, document));
+            }
+            Rule::importStmt => {
+                let mut inner = pair.into_inner();
+                let namespace = inner.next().unwrap();
+                let path = inner.next().unwrap().as_str().to_string();
+
+                self.declare(&namespace, SymbolKind::Value, scope, Some(path));
+            }
+            Rule::letStmt => {
+                let statement = Span::of(&pair);
+                let inner = pair.into_inner().next().unwrap();
+                let bind = match inner.as_rule() {
+                    Rule::assignExpr => {
+                        let mut inner = inner.into_inner();
+                        let bind = inner.next().unwrap();
+                        inner.for_each(|item| self.walk(item, scope, document));
+
+                        bind
+                    }
+                    _ => inner,
+                };
+                let mut bind = bind.into_inner();
+                let name = bind.next().unwrap();
+                let detail = bind.next().map(|item| {
+                    let detail = item.as_str().trim().to_string();
+                    self.walk(item, scope, document);
+
+                    detail
+                });
+
+                if self.function_depth == 0 {
+                    self.lets
+                        .push((statement.end, self.symbols.declarations.len()));
+                }
+                self.declare(
+                    &name,
+                    SymbolKind::Value,
+                    Span::new(statement.end, scope.end),
+                    detail,
+                );
+            }
+            Rule::bindTypeExpr => {
+                let start = pair.as_span().start();
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap();
+                let detail = inner.next().map(|item| {
+                    let detail = item.as_str().trim().to_string();
+                    self.walk(item, scope, document);
+
+                    detail
+                });
+
+                self.declare(
+                    &name,
+                    SymbolKind::Type,
+                    Span::new(start, scope.end),
+                    detail,
+                );
+            }
+            Rule::contextTypeLiteral | Rule::snapshotTypeItemLiteral => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap();
+                let detail = inner.next().map(|item| {
+                    let detail = item.as_str().trim().to_string();
+                    self.walk(item, scope, document);
+
+                    detail
+                });
+
+                self.declare(&name, SymbolKind::Context, document, detail);
+            }
+            Rule::fnLiteral => {
+                let mut inner = pair.into_inner().collect::<Vec<_>>();
+                let body = inner.pop().unwrap();
+                let body_span = Span::of(&body);
+                let is_function = !inner.is_empty();
+
+                inner.into_iter().for_each(|pattern| {
+                    pattern.into_inner().flatten().for_each(|item| match item.as_rule() {
+                        Rule::varSymbol => {
+                            self.declare(&item, SymbolKind::Value, body_span, None)
+                        }
+                        Rule::effectLiteral => self.effect(&item),
+                        _ => {}
+                    })
+                });
+
+                if is_function {
+                    self.function_depth += 1;
+                }
+                self.walk(body, scope, document);
+                if is_function {
+                    self.function_depth -= 1;
+                }
+            }
+            Rule::effectLiteral => self.effect(&pair),
+            Rule::varSymbol => self.refer(&pair, SymbolKind::Value),
+            Rule::typeSymbol => self.refer(&pair, SymbolKind::Type),
+            _ => pair
+                .into_inner()
+                .for_each(|item| self.walk(item, scope, document)),
+        }
+    }
+
+    fn declare(
+        &mut self,
+        pair: &Pair<'_, Rule>,
+        kind: SymbolKind,
+        scope: Span,
+        detail: Option<String>,
+    ) {
+        self.symbols.declarations.push(Declaration {
+            name: pair.as_str().to_string(),
+            kind,
+            span: Span::of(pair),
+            scope,
+            detail,
+            binding: None,
+        });
+    }
+
+    fn refer(&mut self, pair: &Pair<'_, Rule>, kind: SymbolKind) {
+        self.symbols.references.push(Reference {
+            name: pair.as_str().to_string(),
+            kind,
+            span: Span::of(pair),
+        });
+    }
+
+    fn effect(&mut self, pair: &Pair<'_, Rule>) {
+        let inner = pair.clone().into_inner().next().unwrap();
+        let kind = match inner.as_rule() {
+            Rule::idLiteral => EffectKind::Id,
+            Rule::emptyLiteral => EffectKind::Empty,
+            Rule::addEffectExpr => EffectKind::Add,
+            _ => EffectKind::Transition,
+        };
+
+        self.symbols.effects.push((kind, Span::of(&inner)));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::error::{Error, Span};
+use crate::parser::{DocumentParser, Rule};
+use pest::iterators::Pair;
+use pest::Parser;
+use prose_parser::Node;
+use serde::Serialize;
+
+/// A node of the syntax tree of a document, named after its grammar rule.
+///
+/// Unlike the AST, it keeps every rule as written, so tools that only look
+/// at the shape of a document, such as queries, can walk it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyntaxNode {
+    pub name: String,
+    /// Without the whitespace around the node.
+    pub span: Span,
+    pub children: Vec<SyntaxNode>,
+}
+
+/// A node `query` matched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyntaxMatch {
+    pub name: String,
+    pub span: Span,
+}
+
+/// The syntax tree of `document`, from its `rootScope` down.
+pub fn syntax_tree(document: &str) -> Result<SyntaxNode, Error> {
+    let pair = DocumentParser::parse(Rule::document, document)?
+        .next()
+        .unwrap()
+        .into_inner()
+        .next()
+        .unwrap();
+
+    Ok(to_syntax_node(&pair))
+}
+
+fn to_syntax_node(pair: &Pair<'_, Rule>) -> SyntaxNode {
+    let span = pair.as_span();
+    let text = span.as_str();
+    let is_space = |c: char| c == ' ' || c == '\n' || c == '\r';
+
+    SyntaxNode {
+        name: format!("{:?}", pair.as_rule()),
+        span: Span::new(
+            span.end() - text.trim_start_matches(is_space).len(),
+            span.start() + text.trim_end_matches(is_space).len(),
+        ),
+        children: pair
+            .clone()
+            .into_inner()
+            .map(|item| to_syntax_node(&item))
+            .collect(),
+    }
+}
+
+/// The nodes of `tree`, the syntax tree of `source`, that match `selector`.
+/// Selectors are those of `prose_parser` over the names of the grammar
+/// rules, such as `letStmt > varSymbol`.
+pub fn query(tree: &SyntaxNode, source: &str, selector: &str) -> Result<Vec<SyntaxMatch>, String> {
+    Ok(to_prose_node(tree, source)
+        .query(selector)?
+        .iter()
+        .map(|item| SyntaxMatch {
+            name: item.node.name().unwrap_or_default().to_string(),
+            span: Span::new(item.span.start, item.span.end),
+        })
+        .collect())
+}
+
+/// The tree as a prose tree, so that queries run over it. Rules without
+/// children become values holding their source.
+fn to_prose_node(node: &SyntaxNode, source: &str) -> Node {
+    let span = prose_parser::Span::new(node.span.start, node.span.end);
+
+    match node.children.is_empty() {
+        true => Node::Value(prose_parser::Value {
+            name: node.name.clone(),
+            value: source[span.start..span.end].to_string(),
+            span,
+        }),
+        false => Node::Parent(prose_parser::Parent {
+            name: node.name.clone(),
+            children: node
+                .children
+                .iter()
+                .map(|item| to_prose_node(item, source))
+                .collect(),
+            span,
+            metadata: None,
+        }),
+    }
+}
@@ -0,0 +1,133 @@
+use crate::error::Span;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// What the VM did while evaluating a document, in evaluation order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+/// One step of an evaluation. `depth` is the number of scopes open when it
+/// happened and values are printed as in the source. The span of an
+/// expression is where its operator, call or name is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum TraceEvent {
+    /// An expression and its value. Literals evaluate to themselves and are
+    /// left out.
+    Eval {
+        depth: usize,
+        span: Span,
+        expr: String,
+        value: String,
+    },
+    /// A `let` or an argument of a function call bound to a value.
+    Bind {
+        depth: usize,
+        name: String,
+        value: String,
+    },
+    PushScope {
+        depth: usize,
+    },
+    PopScope {
+        depth: usize,
+    },
+    /// An effect of a slice applied to one context of a snapshot by
+    /// `reduce` or `reduceAll`.
+    Apply {
+        depth: usize,
+        context: String,
+        effect: String,
+        before: String,
+        after: String,
+    },
+}
+
+impl Trace {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| err.to_string())
+    }
+
+    /// One line per event, indented by scope, with the positions of
+    /// expressions in `source`.
+    pub fn render(&self, source: &str) -> String {
+        let mut out = String::new();
+        for event in &self.events {
+            let (depth, line) = match event {
+                TraceEvent::Eval {
+                    depth,
+                    span,
+                    expr,
+                    value,
+                } => {
+                    let (line, column) = span.position(source);
+                    (*depth, format!("{}:{}: {} = {}", line, column, expr, value))
+                }
+                TraceEvent::Bind { depth, name, value } => {
+                    (*depth, format!("let {} = {}", name, value))
+                }
+                // braces are drawn at the depth of the enclosing scope
+                TraceEvent::PushScope { depth } => (depth.saturating_sub(1), "{".to_string()),
+                TraceEvent::PopScope { depth } => (depth.saturating_sub(1), "}".to_string()),
+                TraceEvent::Apply {
+                    depth,
+                    context,
+                    effect,
+                    before,
+                    after,
+                } => (
+                    *depth,
+                    format!("apply ({}, {}): {} -> {}", context, effect, before, after),
+                ),
+            };
+
+            let indent = "  ".repeat(depth);
+            for line in line.lines() {
+                let _ = writeln!(out, "{}{}", indent, line);
+            }
+        }
+
+        out
+    }
+}
+
+/// Where a `Vm` records its trace, when tracing is on. The clones of a `Vm`
+/// share it, so the steps of function calls and reductions, which do not
+/// hand their `Vm` back, end up in the same trace.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Tracer(Option<Rc<RefCell<Vec<TraceEvent>>>>);
+
+impl Tracer {
+    pub(crate) fn on() -> Self {
+        Self(Some(Rc::new(RefCell::new(Vec::new()))))
+    }
+
+    /// Records the event `event` builds, only when tracing is on.
+    pub(crate) fn record(&self, event: impl FnOnce() -> TraceEvent) {
+        if let Some(events) = &self.0 {
+            events.borrow_mut().push(event());
+        }
+    }
+
+    pub(crate) fn trace(&self) -> Trace {
+        Trace {
+            events: self
+                .0
+                .as_ref()
+                .map(|events| events.borrow().clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl PartialEq for Tracer {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Tracer {}
@@ -0,0 +1,289 @@
+use crate::checker::kind_name;
+use crate::error::{Error, ErrorKind, Span};
+use crate::parser::{
+    ContextType, SnapshotType, StateLabel, StateMachine, TypeKind, TypeSymbolReference, TypeValue,
+};
+
+/// The kind and the definition, if it has one, of a type by name.
+pub(crate) type Lookup<'a> = dyn Fn(&str) -> Option<(TypeKind, Option<TypeValue>)> + 'a;
+
+/// A type with its references resolved and its `&` and `|` worked out.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Normal {
+    StateMachine(Machine),
+    Snapshot(Vec<ContextType>),
+    Context(ContextType),
+}
+
+/// The states of a state machine and the transitions between them. Unlike
+/// a `StateMachine` literal, which is a single path, it can branch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Machine {
+    states: Vec<StateLabel>,
+    transitions: Vec<(StateLabel, StateLabel)>,
+}
+
+impl From<&StateMachine> for Machine {
+    fn from(StateMachine(states): &StateMachine) -> Self {
+        let mut machine = Machine::default();
+        states.iter().for_each(|state| machine.add_state(state));
+        states
+            .windows(2)
+            .for_each(|pair| machine.add_transition(&pair[0], &pair[1]));
+
+        machine
+    }
+}
+
+impl Machine {
+    pub fn contains(&self, state: &StateLabel) -> bool {
+        self.states.contains(state)
+    }
+
+    /// Whether `to` can be reached from `from` in one or more transitions.
+    pub fn reachable(&self, from: &StateLabel, to: &StateLabel) -> bool {
+        let mut visited = Vec::new();
+        let mut queue = vec![from];
+        while let Some(state) = queue.pop() {
+            for next in self
+                .transitions
+                .iter()
+                .filter(|(source, _)| source == state)
+                .map(|(_, target)| target)
+            {
+                if next == to {
+                    return true;
+                }
+                if !visited.contains(&next) {
+                    visited.push(next);
+                    queue.push(next);
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn transitions(&self) -> &[(StateLabel, StateLabel)] {
+        &self.transitions
+    }
+
+    fn add_state(&mut self, state: &StateLabel) {
+        if !self.contains(state) {
+            self.states.push(state.clone());
+        }
+    }
+
+    fn add_transition(&mut self, from: &StateLabel, to: &StateLabel) {
+        if !self.has_transition(from, to) {
+            self.transitions.push((from.clone(), to.clone()));
+        }
+    }
+
+    fn has_transition(&self, from: &StateLabel, to: &StateLabel) -> bool {
+        self.transitions
+            .iter()
+            .any(|(source, target)| source == from && target == to)
+    }
+
+    /// The states and transitions of either machine.
+    fn union(&self, other: &Machine) -> Machine {
+        let mut machine = self.clone();
+        other
+            .states
+            .iter()
+            .for_each(|state| machine.add_state(state));
+        other
+            .transitions
+            .iter()
+            .for_each(|(from, to)| machine.add_transition(from, to));
+
+        machine
+    }
+
+    /// The states and transitions both machines have.
+    fn intersection(&self, other: &Machine) -> Machine {
+        Machine {
+            states: self
+                .states
+                .iter()
+                .filter(|state| other.contains(state))
+                .cloned()
+                .collect(),
+            transitions: self
+                .transitions
+                .iter()
+                .filter(|(from, to)| other.has_transition(from, to))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl Normal {
+    fn name(&self) -> &'static str {
+        match self {
+            Normal::StateMachine(_) => "a state machine",
+            Normal::Snapshot(_) => "a snapshot type",
+            Normal::Context(_) => "a context",
+        }
+    }
+
+    /// `self & other` when `intersect`, else `self | other`. A snapshot type
+    /// of `&` has the contexts of both, one of `|` those they share.
+    fn combine(self, other: Normal, intersect: bool) -> Result<Normal, String> {
+        let op = if intersect { "&" } else { "|" };
+
+        match (self, other) {
+            (Normal::StateMachine(lhs), Normal::StateMachine(rhs)) => {
+                Ok(Normal::StateMachine(match intersect {
+                    true => lhs.intersection(&rhs),
+                    false => lhs.union(&rhs),
+                }))
+            }
+            (Normal::Snapshot(lhs), Normal::Snapshot(rhs)) => {
+                let conflict = lhs.iter().find_map(|ContextType(label, effect_type)| {
+                    rhs.iter().find(|ContextType(other, other_type)| {
+                        other == label && other_type != effect_type
+                    })
+                });
+                if let Some(ContextType(label, _)) = conflict {
+                    return Err(format!(
+                        "context `{}` has another type on each side of `{}`",
+                        label.0, op
+                    ));
+                }
+
+                let has = |contexts: &[ContextType], label| {
+                    contexts.iter().any(|ContextType(other, _)| other == label)
+                };
+                let contexts = match intersect {
+                    true => lhs
+                        .iter()
+                        .chain(rhs.iter().filter(|ContextType(label, _)| !has(&lhs, label)))
+                        .cloned()
+                        .collect(),
+                    false => lhs
+                        .iter()
+                        .filter(|ContextType(label, _)| has(&rhs, label))
+                        .cloned()
+                        .collect(),
+                };
+
+                Ok(Normal::Snapshot(contexts))
+            }
+            (lhs, rhs) => Err(format!(
+                "`{}` combines state machines or snapshot types, not {} and {}",
+                op,
+                lhs.name(),
+                rhs.name()
+            )),
+        }
+    }
+}
+
+/// The type `name`, declared as `kind`, defined by `value`. Other
+/// types are found with `types`. Where the definition refers back to
+/// `name`, directly or through other types, the reference adds nothing to
+/// the `&` or `|` it is in, so `type A :: StateMachine = M | A` is `M`.
+/// Errors without a place of their own are at `span`.
+pub(crate) fn normalize(
+    name: &str,
+    kind: &TypeKind,
+    value: &TypeValue,
+    span: Span,
+    types: &Lookup<'_>,
+) -> Result<Normal, Error> {
+    let mut resolving = vec![name.to_string()];
+    let normal = resolve(kind, value, types, &mut resolving)?.ok_or_else(|| {
+        Error::new(
+            ErrorKind::Type,
+            &format!("`{}` is only defined by itself", name),
+            span,
+        )
+    })?;
+
+    match (kind, &normal) {
+        (TypeKind::StateMachine, Normal::StateMachine(_))
+        | (TypeKind::Snapshot, Normal::Snapshot(_))
+        | (TypeKind::Context, Normal::Context(_)) => Ok(normal),
+        _ => Err(Error::new(
+            ErrorKind::Type,
+            &format!(
+                "`{}` is declared as {} but defined as {}",
+                name,
+                kind_name(kind),
+                normal.name()
+            ),
+            span,
+        )),
+    }
+}
+
+/// `None` when all of `value` refers back to a type in `resolving`.
+fn resolve(
+    kind: &TypeKind,
+    value: &TypeValue,
+    types: &Lookup<'_>,
+    resolving: &mut Vec<String>,
+) -> Result<Option<Normal>, Error> {
+    match value {
+        TypeValue::StateMachine(machine) => Ok(Some(Normal::StateMachine(Machine::from(machine)))),
+        TypeValue::Snapshot(SnapshotType(contexts)) => Ok(Some(Normal::Snapshot(contexts.clone()))),
+        TypeValue::Context(context) => Ok(Some(Normal::Context(context.clone()))),
+        TypeValue::Reference(TypeSymbolReference(name, location)) => {
+            if resolving.contains(&name.0) {
+                return Ok(None);
+            }
+
+            match types(&name.0) {
+                Some((declared, Some(value))) if declared == *kind => {
+                    resolving.push(name.0.clone());
+                    let normal = resolve(kind, &value, types, resolving);
+                    resolving.pop();
+
+                    normal
+                }
+                Some((declared, None)) if declared == *kind => Err(Error::new(
+                    ErrorKind::Type,
+                    &format!("`{}` is declared without a definition", name.0),
+                    location.0,
+                )),
+                Some((declared, _)) => Err(Error::new(
+                    ErrorKind::Type,
+                    &format!(
+                        "`{}` is a {}, not a {}",
+                        name.0,
+                        kind_name(&declared),
+                        kind_name(kind)
+                    ),
+                    location.0,
+                )),
+                None => Err(Error::new(
+                    ErrorKind::UnknownSymbol,
+                    &format!("unknown type `{}`", name.0),
+                    location.0,
+                )),
+            }
+        }
+        TypeValue::Intersection(items, location) | TypeValue::Union(items, location) => {
+            let intersect = matches!(value, TypeValue::Intersection(..));
+
+            items.iter().try_fold(None, |acc: Option<Normal>, item| {
+                let normal = match resolve(kind, item, types, resolving)? {
+                    Some(normal) => normal,
+                    None => return Ok(acc),
+                };
+
+                match acc {
+                    Some(acc) => acc
+                        .combine(normal, intersect)
+                        .map(Some)
+                        .map_err(|err| Error::new(ErrorKind::Type, &err, location.0)),
+                    None => Ok(Some(normal)),
+                }
+            })
+        }
+        TypeValue::Id | TypeValue::Int | TypeValue::Empty | TypeValue::TypeSymbol(_) => Ok(None),
+    }
+}
@@ -0,0 +1,308 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{check, parse, run, ErrorKind};
+
+const TYPES: &str = "type Machine :: StateMachine = Id => `a` => `b`;
+type Snap :: Snapshot = { count: Int, state: Machine };
+";
+
+fn check_error(source: &str) -> (ErrorKind, String) {
+    let source = format!("{}{}", TYPES, source);
+    let err = check(&parse(&source).unwrap()).unwrap_err();
+
+    (err.kind, err.render(&source))
+}
+
+#[test]
+fn well_typed() {
+    let source = format!(
+        "{}{}",
+        TYPES,
+        "let s :: Snap = { count: 1, state: `a` };
+let t :: Effect = =>`a` compose =>`b`;
+let slice :: Slice = [(count, +1), (state, =>`b`)] << ((count: Int) apply +2);
+let e :: ContextEffect = (state: Machine) apply t;
+slice reduce s"
+    );
+
+    assert_eq!(check(&parse(&source).unwrap()), Ok(()));
+    assert!(run(&source).is_ok());
+}
+
+#[test]
+fn let_kinds() {
+    assert_eq!(
+        check_error("let a :: Slice = +1;\na"),
+        (
+            ErrorKind::Type,
+            "3:5: `a` is declared as Slice but its value is an add effect".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("let a :: Snapshot = [(count, +1)];\na"),
+        (
+            ErrorKind::Type,
+            "3:5: `a` is declared as Snapshot but its value is a slice".to_string()
+        )
+    );
+}
+
+#[test]
+fn snapshot_types() {
+    assert_eq!(
+        check_error("let s :: Snap = { count: 1 };\ns"),
+        (
+            ErrorKind::Type,
+            "3:5: `s` is missing context `state` of Snap".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("{ count: `a` }"),
+        (
+            ErrorKind::Type,
+            "3:3: the value does not fit context `count`".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("{ state: `c` }"),
+        (
+            ErrorKind::Type,
+            "3:3: the value does not fit context `state`".to_string()
+        )
+    );
+}
+
+#[test]
+fn unknown_contexts() {
+    assert_eq!(
+        check_error("[(count, +1), (amount, +1)]"),
+        (
+            ErrorKind::UnknownSymbol,
+            "3:16: unknown context `amount`".to_string()
+        )
+    );
+}
+
+#[test]
+fn effects_fit_contexts() {
+    assert_eq!(
+        check_error("[(count, =>`a`)]"),
+        (
+            ErrorKind::Type,
+            "3:3: a transition does not fit context `count`, which is Int".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("state apply +1"),
+        (
+            ErrorKind::UnknownSymbol,
+            "3:1: unknown symbol `state`".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("(state: Machine) apply +1"),
+        (
+            ErrorKind::Type,
+            "3:18: an add effect does not fit context `state`, which is a state machine"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check_error("[(state, =>`c`)]"),
+        (
+            ErrorKind::Type,
+            "3:3: `c` is not a state of context `state`".to_string()
+        )
+    );
+}
+
+#[test]
+fn transitions_are_reachable() {
+    assert_eq!(
+        check_error("(state: Machine) apply (=>`b` compose =>`a`)"),
+        (
+            ErrorKind::Type,
+            "3:18: `a` is not reachable from `b` in context `state`".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("(state: Machine) apply (=>`a` compose (=>`b` compose =>`b`))"),
+        (
+            ErrorKind::Type,
+            "3:18: `b` is not reachable from `b` in context `state`".to_string()
+        )
+    );
+}
+
+#[test]
+fn operands() {
+    assert_eq!(
+        check_error("+1 compose =>`a`"),
+        (
+            ErrorKind::Type,
+            "3:4: cannot compose an add effect with a transition".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("[] << { count: 1 }"),
+        (
+            ErrorKind::Type,
+            "3:4: `<<` expects a slice and a context effect, found a slice and a snapshot"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn choices() {
+    assert_eq!(
+        check_error("[(count, +1)] | +1"),
+        (
+            ErrorKind::Type,
+            "3:15: `|` expects two effects or two slices, found a slice and an add effect"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check_error("let e :: Effect = +1 | =>`a`;\n(count: Int) apply e"),
+        (
+            ErrorKind::Type,
+            "4:14: a transition does not fit context `count`, which is Int".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("let s :: Slice = [(count, +1)] | =>`a`;\ns"),
+        (
+            ErrorKind::Type,
+            "3:32: `|` expects two effects or two slices, found a slice and a transition"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn branches() {
+    assert_eq!(
+        check_error("[(a: [(count, +1)] | a: [])]"),
+        (
+            ErrorKind::DuplicateSymbol,
+            "3:22: route `a` is already in this branch".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("[(a: [(b: [(state, +1)])])]"),
+        (
+            ErrorKind::Type,
+            "3:13: an add effect does not fit context `state`, which is a state machine"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check_error("[(a: [])] reduceAll [(a: [])]"),
+        (
+            ErrorKind::Type,
+            "3:11: `reduceAll` expects a slice and a snapshot, found a slice and a slice"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn outputs() {
+    assert_eq!(
+        check_error("Output total << +1 compose =>`a`"),
+        (
+            ErrorKind::Type,
+            "3:20: cannot compose an add effect with a transition".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("let n :: Slice = Output << 1;\nn"),
+        (
+            ErrorKind::Type,
+            "3:5: `n` is declared as Slice but its value is an Int".to_string()
+        )
+    );
+}
+
+#[test]
+fn fixtures() {
+    let source = include_str!("fixtures/complex.crg");
+    let err = check(&parse(source).unwrap()).unwrap_err();
+
+    assert_eq!(
+        err.render(source),
+        "16:11: a transition does not fit context `a`, which is Int"
+    );
+    assert_eq!(
+        check(&parse(include_str!("fixtures/document.crg")).unwrap()),
+        Ok(())
+    );
+}
+
+#[test]
+fn functions() {
+    assert_eq!(
+        check_error("let f :: Int -> Int = x y { x + y };\nf"),
+        (
+            ErrorKind::Type,
+            "3:5: `f` is declared as Int -> Int but its value is a function".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("let f :: Int -> Int = x { 1 + +1 };\nf"),
+        (
+            ErrorKind::Type,
+            "3:29: `+` expects two Ints or two add effects, found an Int and an add effect"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        check_error("let n :: Int = 1;\nn(2)"),
+        (ErrorKind::Type, "4:2: an Int cannot be called".to_string())
+    );
+    assert_eq!(
+        check_error("let f :: Int -> Int = x { y };\nf"),
+        (
+            ErrorKind::UnknownSymbol,
+            "3:27: unknown symbol `y`".to_string()
+        )
+    );
+}
+
+#[test]
+fn function_signatures() {
+    let curried = "let k :: Int -> Int -> Int = x { y { x } };\n";
+
+    assert_eq!(
+        check_error("let f :: Int -> Int = x { x };\nf(+1)"),
+        (
+            ErrorKind::Type,
+            "4:2: argument 1 is an add effect, but the function takes an Int".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("let f :: Int -> Int = x { x };\nlet y :: Slice = f(1);\ny"),
+        (
+            ErrorKind::Type,
+            "4:5: `y` is declared as Slice but its value is an Int".to_string()
+        )
+    );
+    assert_eq!(
+        check_error(&format!("{}k(1, [])", curried)),
+        (
+            ErrorKind::Type,
+            "4:2: argument 2 is a slice, but the function takes an Int".to_string()
+        )
+    );
+    assert_eq!(
+        check_error(&format!(
+            "{}let g :: Int -> Int = k(1);\nlet y :: Slice = g(2);\ny",
+            curried
+        )),
+        (
+            ErrorKind::Type,
+            "5:5: `y` is declared as Slice but its value is an Int".to_string()
+        )
+    );
+}
@@ -0,0 +1,191 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn crg(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crg"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // commands that fail on their arguments exit without reading stdin
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn run_reads_files_and_stdin() {
+    let output = crg(&["run", "tests/fixtures/function.crg"], "");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = crg(&["run"], "1 + 2");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "{\n  \"int\": 3\n}\n");
+
+    let output = crg(&["run", "-"], "+1 + +2");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = crg(&["run", "--outputs"], "Output << 1;\n2");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&stdout(&output)).unwrap(),
+        serde_json::json!({ "outputs": [{ "label": "1", "value": { "int": 1 } }], "value": { "int": 2 } })
+    );
+
+    // a lone file is `run`
+    let output = crg(&["tests/fixtures/function.crg"], "");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn diagnostics_go_to_stderr() {
+    let output = crg(&["run"], "let a :: Slice = +1;\na");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "<stdin>:1:5: `a` is declared as Slice but its value is an add effect\n"
+    );
+
+    let output = crg(&["check", "tests/fixtures/complex.crg"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "tests/fixtures/complex.crg:16:11: a transition does not fit context `a`, which is Int\n"
+    );
+}
+
+#[test]
+fn check() {
+    let output = crg(&["check", "tests/fixtures/document.crg"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn parse_formats() {
+    let output = crg(&["parse", "--format", "yaml"], "+1");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("addEffect: 1"));
+
+    let output = crg(&["parse"], "+1");
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert!(json.is_array());
+
+    let output = crg(&["parse", "--format", "toml"], "+1");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn fmt() {
+    let output = crg(&["fmt"], "let s::Slice=[(a,+1)];\ns");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "let s :: Slice = [(a, +1)];\ns\n");
+
+    let output = crg(&["fmt"], "let s :: Slice = ;");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn trace_formats() {
+    let output = crg(&["trace"], "let n :: Int = 1 + 2;\nn");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "{\n  1:18: 1 + 2 = 3\n  let n = 3\n  2:1: n = 3\n}\n"
+    );
+
+    let output = crg(&["trace", "-f", "json"], "let n :: Int = 1 + 2;\nn");
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json["events"][0]["event"], "pushScope");
+
+    // the steps before a failure are still printed
+    let output = crg(&["trace"], "let f :: Int -> Int = 1 { 1 };\nf(2)");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("let f = <function of 1 argument>"));
+    assert_eq!(
+        stderr(&output),
+        "<stdin>:2:2: no alternative of the function matches the arguments\n"
+    );
+}
+
+#[test]
+fn imports() {
+    let output = crg(&["run", "tests/fixtures/modules/story.crg"], "");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = crg(
+        &[
+            "run",
+            "-I",
+            "tests/fixtures/modules/lib",
+            "tests/fixtures/modules/search.crg",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "{\n  \"int\": 2\n}\n");
+
+    // errors in imported files are shown with their own position
+    let output = crg(&["check", "tests/fixtures/modules/cycle_a.crg"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output)
+        .starts_with("tests/fixtures/modules/cycle_b.crg:1:1: imports go around in a cycle: "));
+
+    let output = crg(
+        &["fmt", "--path", "lib", "tests/fixtures/modules/story.crg"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn graph_formats() {
+    let output = crg(&["graph", "tests/fixtures/branches.crg"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("digraph crg {"));
+
+    let output = crg(
+        &["graph", "-f", "mermaid", "tests/fixtures/branches.crg"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("flowchart LR"));
+}
+
+#[test]
+fn usage_errors() {
+    assert_eq!(crg(&[], "").status.code(), Some(2));
+    assert_eq!(crg(&["run", "--bogus"], "").status.code(), Some(2));
+    assert_eq!(crg(&["run", "a.crg", "b.crg"], "").status.code(), Some(2));
+    assert_eq!(crg(&["check", "--outputs"], "").status.code(), Some(2));
+    assert_eq!(
+        crg(&["run", "-I", "tests/fixtures", "-"], "1")
+            .status
+            .code(),
+        Some(2)
+    );
+    assert_eq!(
+        crg(&["check", "-I", "tests/fixtures"], "1").status.code(),
+        Some(2)
+    );
+    assert_eq!(
+        crg(&["check", "--format", "json"], "").status.code(),
+        Some(2)
+    );
+
+    let output = crg(&["run", "tests/fixtures/missing.crg"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("crg: tests/fixtures/missing.crg: "));
+}
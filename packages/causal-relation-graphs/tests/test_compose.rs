@@ -0,0 +1,104 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::run;
+use proptest::prelude::*;
+
+const TYPES: &str = "type Machine :: StateMachine = Id => `a` => `b` => `c`;
+type Snap :: Snapshot = { count: Int, state: Machine };
+";
+
+fn eval(expr: &str) -> Result<String, ()> {
+    run(&format!("{}{}", TYPES, expr)).map_err(|_| ())
+}
+
+fn add_effect() -> impl Strategy<Value = String> {
+    prop_oneof![-5i64..0, 1i64..5].prop_map(|n| format!("+({})", n))
+}
+
+fn count_effect() -> impl Strategy<Value = String> {
+    prop_oneof![
+        add_effect().prop_map(|effect| format!("(count, {})", effect)),
+        Just("(count, Id)".to_string()),
+    ]
+}
+
+fn state_effect() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::sample::select(vec!["a", "b", "c"])
+            .prop_map(|state| format!("(state, =>`{}`)", state)),
+        Just("(state, Empty)".to_string()),
+    ]
+}
+
+fn context_effect() -> impl Strategy<Value = String> {
+    prop_oneof![count_effect(), state_effect()]
+}
+
+/// Two effects on the same context, which is what they compose on.
+fn same_context() -> impl Strategy<Value = (String, String)> {
+    prop_oneof![
+        (count_effect(), count_effect()),
+        (state_effect(), state_effect())
+    ]
+}
+
+fn slice() -> impl Strategy<Value = String> {
+    prop::collection::vec(context_effect(), 0..4)
+        .prop_map(|items| format!("[{}]", items.join(", ")))
+}
+
+fn snapshot() -> impl Strategy<Value = String> {
+    (1i64..5, prop::sample::select(vec!["a", "b", "c"]))
+        .prop_map(|(count, state)| format!("{{ count: {}, state: `{}` }}", count, state))
+}
+
+proptest! {
+    #[test]
+    fn compose_is_sequential_reduce(a in slice(), b in slice(), s in snapshot()) {
+        prop_assert_eq!(
+            eval(&format!("({} compose {}) reduce {}", a, b, s)),
+            eval(&format!("{} reduce ({} reduce {})", b, a, s))
+        );
+    }
+
+    #[test]
+    fn compose_is_associative(a in slice(), b in slice(), c in slice()) {
+        prop_assert_eq!(
+            eval(&format!("({} compose {}) compose {}", a, b, c)),
+            eval(&format!("{} compose ({} compose {})", a, b, c))
+        );
+    }
+
+    #[test]
+    fn id_is_the_identity(a in slice(), effect in add_effect()) {
+        prop_assert_eq!(eval(&format!("[] compose {}", a)), eval(&a));
+        prop_assert_eq!(eval(&format!("{} compose []", a)), eval(&a));
+        prop_assert_eq!(eval(&format!("Id compose {}", effect)), eval(&effect));
+        prop_assert_eq!(eval(&format!("{} compose Id", effect)), eval(&effect));
+    }
+
+    #[test]
+    fn context_effects_compose_like_slices((lhs, rhs) in same_context(), s in snapshot()) {
+        prop_assert_eq!(
+            eval(&format!("([] << ({} compose {})) reduce {}", lhs, rhs, s)),
+            eval(&format!("[{}, {}] reduce {}", lhs, rhs, s))
+        );
+    }
+}
+
+#[test]
+fn slices_concatenate() {
+    assert_eq!(
+        eval("[(count, +1)] compose [(state, =>`a`), (count, +2)]"),
+        eval("[(count, +1), (state, =>`a`), (count, +2)]")
+    );
+}
+
+#[test]
+fn context_effects_on_one_context() {
+    assert_eq!(eval("(count, +1) compose (count, +2)"), eval("(count, +3)"));
+    assert!(eval("(count, +1) compose (state, =>`a`)").is_err());
+    assert!(eval("((state: Machine) apply =>`b`) compose (state, =>`a`)").is_err());
+    // `b` cannot go back to `a`, even if `Empty` follows
+    assert!(eval("([] << ((state, =>`a`) compose (state, Empty))) reduce { state: `b` }").is_err());
+}
@@ -0,0 +1,111 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{format, parse, ErrorKind};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Every `.crg` file under `dir`, with its path.
+fn fixtures(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut entries = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    entries
+        .into_iter()
+        .flat_map(|path| match path.is_dir() {
+            true => fixtures(&path),
+            false if path.extension().is_some_and(|ext| ext == "crg") => {
+                let source = fs::read_to_string(&path).unwrap();
+
+                vec![(path, source)]
+            }
+            false => Vec::new(),
+        })
+        .collect()
+}
+
+#[test]
+fn fixtures_round_trip() {
+    let fixtures = fixtures(Path::new("tests/fixtures"));
+    // nested directories are read too
+    assert!(fixtures
+        .iter()
+        .any(|(path, _)| path.ends_with("modules/lib/shared.crg")));
+
+    for (path, source) in fixtures {
+        let formatted = format(&source).unwrap();
+
+        assert_eq!(
+            parse(&formatted).unwrap(),
+            parse(&source).unwrap(),
+            "{}",
+            path.display()
+        );
+        assert_eq!(format(&formatted).unwrap(), formatted, "{}", path.display());
+    }
+}
+
+#[test]
+fn canonical_spacing() {
+    assert_eq!(
+        format("let a :: Int = ;\na").unwrap_err().kind,
+        ErrorKind::Syntax
+    );
+    assert_eq!(
+        format("type  M::StateMachine=Id=>`a`  =>`b`;\ntype S :: Snapshot = {a:Int,b:M};\n{a:1}")
+            .unwrap(),
+        "type M :: StateMachine = Id => `a` => `b`;\ntype S :: Snapshot = { a: Int, b: M };\n{ a: 1 }\n"
+    );
+    assert_eq!(
+        format("let  f::Int->Int=x   {x+ +(1)};\nlet s::Slice=[ (a:Int,+(-2)) ]<<(a,=>`b`);\nf( 1 )").unwrap(),
+        "let f :: Int -> Int = x { x + +1 };\nlet s :: Slice = [(a: Int, +(-2))] << (a, =>`b`);\nf(1)\n"
+    );
+    assert_eq!(
+        format("{+1;\n\n\n  {Id}}").unwrap(),
+        "{\n  +1;\n\n  { Id }\n}\n"
+    );
+}
+
+#[test]
+fn long_slices_wrap() {
+    let source =
+        "let s :: Slice = [(count, +1), (state, =>`started`), (count, +2), (state, =>`done`)];\ns";
+
+    assert_eq!(
+        format(source).unwrap(),
+        "let s :: Slice = [
+  (count, +1),
+  (state, =>`started`),
+  (count, +2),
+  (state, =>`done`)
+];
+s
+"
+    );
+
+    let source = "[(a: [(count, +1), (count, +2), (count, +3)] | b: [(count, +4), (count, +5), (count, +6)])]";
+    assert_eq!(
+        format(source).unwrap(),
+        "[
+  (
+    a: [(count, +1), (count, +2), (count, +3)]
+    | b: [(count, +4), (count, +5), (count, +6)]
+  )
+]
+"
+    );
+    assert_eq!(parse(&format(source).unwrap()), parse(source));
+}
+
+#[test]
+fn comments() {
+    let source = "// head\n\n\n+1;   // after\n//\n{\n// inside\n+1 }\n// tail";
+
+    assert_eq!(
+        format(source).unwrap(),
+        "// head\n\n+1; // after\n//\n{\n  // inside\n  +1\n}\n// tail"
+    );
+    assert_eq!(parse(&format(source).unwrap()), parse(source));
+}
@@ -0,0 +1,28 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{graph, parse};
+use insta::assert_snapshot;
+
+const STORY: &str = "type Mood :: StateMachine = Id => `calm` => `angry` => `calm`;
+type Story :: Snapshot = { day: Int, alice: Mood };
+let start :: Story = { day: 1, alice: `calm` };
+let quarrel :: Slice = [(day, +1), (alice, =>`angry`), (alice, =>`calm`)];
+let end :: Story = quarrel reduce start;
+end";
+
+#[test]
+fn dot() {
+    assert_snapshot!(graph(&parse(STORY).unwrap()).unwrap().to_dot());
+}
+
+#[test]
+fn mermaid() {
+    assert_snapshot!(graph(&parse(STORY).unwrap()).unwrap().to_mermaid());
+}
+
+#[test]
+fn branches() {
+    let source = include_str!("fixtures/branches.crg");
+
+    assert_snapshot!(graph(&parse(source).unwrap()).unwrap().to_dot());
+}
@@ -0,0 +1,158 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{
+    check, check_file, parse, run, run_file, run_file_traced, ErrorKind, Span, TraceEvent,
+};
+use std::path::{Path, PathBuf};
+
+const DIR: &str = "tests/fixtures/modules";
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(DIR).join(name)
+}
+
+#[test]
+fn imports_are_namespaced() {
+    let json = run_file(&fixture("story.crg"), &[]).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "snapshot": [
+                ["mood", { "StateLabel": { "Label": "calm" } }],
+                ["trust", { "Int": 2 }]
+            ]
+        })
+    );
+    assert!(check_file(&fixture("story.crg"), &[]).is_ok());
+}
+
+#[test]
+fn modules_are_evaluated_once() {
+    // story.crg imports characters.crg, and so does scenes.crg
+    let (result, trace) = run_file_traced(&fixture("story.crg"), &[]);
+    assert!(result.is_ok());
+
+    let binds = |name: &str| {
+        trace
+            .events
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::Bind { name: bound, .. } if bound == name))
+            .count()
+    };
+    assert_eq!(binds("unused"), 1);
+    assert_eq!(binds("apology"), 1);
+    assert_eq!(binds("day"), 1);
+}
+
+#[test]
+fn search_path() {
+    let err = run_file(&fixture("search.crg"), &[]).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Import);
+    assert_eq!(err.message, "cannot find `shared.crg`");
+
+    let json = run_file(&fixture("search.crg"), &[fixture("lib")]).unwrap();
+    assert_eq!(json, "{\n  \"int\": 2\n}");
+}
+
+#[test]
+fn cycles() {
+    let err = run_file(&fixture("cycle_a.crg"), &[]).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::Import);
+    assert_eq!(err.span, Span::new(0, 29));
+    assert_eq!(err.file, Some(fixture("cycle_b.crg")));
+    assert_eq!(
+        err.message,
+        format!(
+            "imports go around in a cycle: {} -> {} -> {}",
+            fixture("cycle_a.crg").display(),
+            fixture("cycle_b.crg").display(),
+            fixture("cycle_a.crg").display()
+        )
+    );
+}
+
+#[test]
+fn imported_types_are_checked() {
+    let dir = tempdir("imported_types_are_checked");
+    std::fs::copy(fixture("characters.crg"), dir.join("characters.crg")).unwrap();
+
+    let root = dir.join("root.crg");
+    std::fs::write(
+        &root,
+        "import chars from \"characters.crg\";\n[(mood, =>`sad`)] reduce chars.start",
+    )
+    .unwrap();
+    let err = check_file(&root, &[]).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Type);
+    assert_eq!(err.file, Some(root.clone()));
+
+    // only exported names are visible
+    std::fs::write(&root, "import chars from \"characters.crg\";\nchars.unused").unwrap();
+    let err = check_file(&root, &[]).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::UnknownSymbol);
+    assert_eq!(err.message, "unknown symbol `chars.unused`");
+
+    let typed = "import chars from \"characters.crg\";
+type Cast :: Snapshot = { lead: chars.Mood };
+let calm :: Snapshot -> Snapshot = s { [(lead, =>`angry`)] reduce s };
+calm({ lead: `calm` })";
+    std::fs::write(&root, typed).unwrap();
+    assert!(run_file(&root, &[]).is_ok());
+}
+
+#[test]
+fn exports() {
+    let dir = tempdir("exports");
+
+    let leak = dir.join("leak.crg");
+    std::fs::write(
+        &leak,
+        "type Mood :: StateMachine = Id => `a`;\nexport type S :: Snapshot = { mood: Mood };\n{}",
+    )
+    .unwrap();
+    let err = check_file(&leak, &[]).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Type);
+    assert_eq!(
+        err.message,
+        "exported type `S` uses `Mood`, which is not exported"
+    );
+
+    std::fs::write(
+        &leak,
+        "type Mood :: StateMachine = Id => `a`;\nexport type M :: StateMachine = Mood | (`a` => `b`);\n{}",
+    )
+    .unwrap();
+    let err = check_file(&leak, &[]).unwrap_err();
+    assert_eq!(
+        err.message,
+        "exported type `M` uses `Mood`, which is not exported"
+    );
+
+    let nested = "{ export let a :: Int = 1; a }";
+    let err = check(&parse(nested).unwrap()).unwrap_err();
+    assert_eq!(
+        err.message,
+        "values can be exported only at the top of a file"
+    );
+}
+
+#[test]
+fn documents_cannot_import() {
+    let err = run("import chars from \"characters.crg\";\n1").unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::Import);
+    assert_eq!(err.span, Span::new(0, 34));
+    assert_eq!(err.file, None);
+}
+
+/// An empty directory of its own for each test.
+fn tempdir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("crg-test-modules-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
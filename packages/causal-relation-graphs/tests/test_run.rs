@@ -0,0 +1,441 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{execute_ast, parse, run, run_with_outputs, ErrorKind};
+use proptest::prelude::*;
+
+const TOKENS: [&str; 31] = [
+    "let ",
+    "type ",
+    "a",
+    "b",
+    "C",
+    " :: ",
+    " = ",
+    ";",
+    "\n",
+    "Slice",
+    "Effect",
+    "Snapshot",
+    "StateMachine",
+    "Context",
+    "Int",
+    "+1",
+    "+99999999999999999999",
+    "=>",
+    "`a`",
+    "Id",
+    "Empty",
+    " compose ",
+    " apply ",
+    " reduce ",
+    " << ",
+    " | ",
+    "(",
+    ")",
+    "[",
+    "]",
+    "{",
+];
+
+fn source() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::collection::vec(prop::sample::select(&TOKENS[..]), 0..24)
+            .prop_map(|tokens| tokens.concat()),
+        "\\PC{0,40}",
+    ]
+}
+
+proptest! {
+    #[test]
+    fn run_never_panics(source in source()) {
+        let _ = run(&source);
+    }
+
+    #[test]
+    fn fixtures_with_an_edit_never_panic(idx in 0usize..5, at in 0usize..200, token in prop::sample::select(&TOKENS[..])) {
+        let mut source = [
+            include_str!("fixtures/complex.crg"),
+            include_str!("fixtures/document.crg"),
+            include_str!("fixtures/scopes.crg"),
+            include_str!("fixtures/function.crg"),
+            include_str!("fixtures/branches.crg"),
+        ][idx]
+            .to_string();
+        let at = (0..=at.min(source.len()))
+            .rev()
+            .find(|idx| source.is_char_boundary(*idx))
+            .unwrap_or(0);
+        source.insert_str(at, token);
+
+        let _ = run(&source);
+    }
+}
+
+#[test]
+fn unknown_symbol() {
+    let source = "let a :: Effect = +1;\nb";
+    let err = run(source).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::UnknownSymbol);
+    assert_eq!(err.render(source), "2:1: unknown symbol `b`");
+}
+
+#[test]
+fn duplicate_symbol() {
+    let source = "let a :: Effect;\n{ let b :: Slice; let b :: Slice; b }";
+    let err = run(source).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::DuplicateSymbol);
+    assert_eq!(
+        err.render(source),
+        "2:23: `b` is already bound in this scope"
+    );
+
+    // the root scope is a scope too
+    let source = "let x :: Effect = +1;\nlet x :: Effect = +2;\nx";
+    let err = run(source).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::DuplicateSymbol);
+    assert_eq!(
+        err.render(source),
+        "2:5: `x` is already bound in this scope"
+    );
+    // a declaration can be given its value later
+    assert!(run("let d :: Effect;\nlet d :: Effect = +1;\nd").is_ok());
+
+    // and so is a second definition of a type, in the checker and the VM
+    let source = "type A :: StateMachine = Id => `a`;\ntype A :: StateMachine = Id => `b`;\n1";
+    let err = run(source).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::DuplicateSymbol);
+    assert_eq!(
+        err.render(source),
+        "2:6: type `A` is already defined in this scope"
+    );
+    let err = execute_ast(&parse(source).unwrap()).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::DuplicateSymbol);
+    assert_eq!(
+        err.render(source),
+        "2:6: type `A` is already defined in this scope"
+    );
+    assert!(run("type B :: StateMachine;\ntype B :: StateMachine = Id => `b`;\n1").is_ok());
+}
+
+#[test]
+fn syntax_error() {
+    let source = "let a :: = +1;\na";
+    let err = run(source).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::Syntax);
+    assert_eq!(err.position(source), (1, 10));
+}
+
+#[test]
+fn evaluation_error() {
+    // the checker rejects this before it is evaluated
+    let source = "=>`a` compose +1";
+    let err = execute_ast(&parse(source).unwrap()).unwrap_err();
+
+    assert_eq!(err.kind, ErrorKind::Evaluation);
+    assert_eq!(
+        err.render(source),
+        "1:7: a transition cannot be composed with an add effect"
+    );
+    assert_eq!(
+        run("+9223372036854775807 compose +1").unwrap_err().kind,
+        ErrorKind::Evaluation
+    );
+}
+
+const MACHINE: &str = "type Machine :: StateMachine = Id => `draft` => `review` => `published`;
+type Snap :: Snapshot = { count: Int, state: Machine };
+";
+
+fn run_snapshot(source: &str) -> Result<serde_json::Value, String> {
+    let source = format!("{}{}", MACHINE, source);
+
+    run(&source)
+        .map(|json| serde_json::from_str(&json).unwrap())
+        .map_err(|err| err.render(&source))
+}
+
+#[test]
+fn transitions_move_the_state() {
+    assert_eq!(
+        run_snapshot("[(state, =>`review`), (count, +1)] reduce { count: 1, state: `draft` }"),
+        Ok(serde_json::json!({
+            "snapshot": [["count", { "Int": 2 }], ["state", { "StateLabel": { "Label": "review" } }]]
+        }))
+    );
+    assert_eq!(
+        run_snapshot(
+            "[(state, =>`review`), (state, =>`published`)] reduce { count: 1, state: `draft` }"
+        ),
+        Ok(serde_json::json!({
+            "snapshot": [["count", { "Int": 1 }], ["state", { "StateLabel": { "Label": "published" } }]]
+        }))
+    );
+}
+
+#[test]
+fn invalid_transitions() {
+    assert_eq!(
+        run_snapshot("[(state, =>`draft`)] reduce { state: `review` }"),
+        Err("3:22: `review` cannot transition to `draft`".to_string())
+    );
+    assert_eq!(
+        run_snapshot(
+            "[(state, =>`published`), (state, =>`review`)] reduce { count: 1, state: `draft` }"
+        ),
+        Err("3:47: `published` cannot transition to `review`".to_string())
+    );
+}
+
+fn run_value(source: &str) -> Result<serde_json::Value, String> {
+    run(source)
+        .map(|json| serde_json::from_str(&json).unwrap())
+        .map_err(|err| err.render(source))
+}
+
+fn outputs_value(source: &str) -> Result<serde_json::Value, String> {
+    run_with_outputs(source)
+        .map(|json| serde_json::from_str(&json).unwrap())
+        .map_err(|err| err.render(source))
+}
+
+const ADD: &str = "let f :: Int -> Int -> Int = x y { x + y } | 1 y { y };\n";
+
+#[test]
+fn function_fixture() {
+    assert_eq!(
+        run_value(include_str!("fixtures/function.crg")),
+        Ok(serde_json::json!({
+            "function": {
+                "alternatives": [
+                    {
+                        "args": [{ "Symbol": "x" }, { "Symbol": "y" }],
+                        "scope": [{ "Op": ["Add", { "Reference": "x" }, { "Reference": "y" }] }]
+                    },
+                    {
+                        "args": [{ "Int": 1 }, { "Symbol": "y" }],
+                        "scope": [{ "Reference": "y" }]
+                    }
+                ],
+                "applied": []
+            }
+        }))
+    );
+}
+
+#[test]
+fn function_signatures_are_checked_when_evaluated() {
+    let eval = |source: &str| {
+        execute_ast(&parse(source).unwrap()).map_err(|err| (err.kind, err.render(source)))
+    };
+
+    assert_eq!(
+        eval("let f :: Int -> Int = x { x };\nf(+1)"),
+        Err((
+            ErrorKind::Type,
+            "2:2: argument 1 is an add effect, but the function takes Int".to_string()
+        ))
+    );
+    assert_eq!(
+        eval("let f :: Int -> Slice = x { x };\nf(1)"),
+        Err((
+            ErrorKind::Type,
+            "2:2: the function returns an Int, but is declared to return Slice".to_string()
+        ))
+    );
+    assert_eq!(
+        run_value("let k :: Int -> Int -> Int = x { y { x } };\nk(1)(2)"),
+        Ok(serde_json::json!({ "int": 1 }))
+    );
+}
+
+#[test]
+fn functions_choose_the_last_matching_alternative() {
+    assert_eq!(
+        run_value(&format!("{}f(2, 3)", ADD)),
+        Ok(serde_json::json!({ "int": 5 }))
+    );
+    assert_eq!(
+        run_value(&format!("{}f(1, 3)", ADD)),
+        Ok(serde_json::json!({ "int": 3 }))
+    );
+    assert_eq!(
+        run_value("let g :: Effect -> Effect = +1 { +2 } | +3 { +4 };\ng(+2)"),
+        Err("2:2: no alternative of the function matches the arguments".to_string())
+    );
+}
+
+#[test]
+fn functions_are_curried() {
+    assert_eq!(
+        run_value(&format!("{}let g :: Int -> Int = f(2);\ng(3)", ADD)),
+        Ok(serde_json::json!({ "int": 5 }))
+    );
+    assert_eq!(
+        run_value(&format!("{}f(2)(3)", ADD)),
+        Ok(serde_json::json!({ "int": 5 }))
+    );
+    assert_eq!(
+        run_value("let k :: Int -> Int -> Int = x { y { x } };\nk(1, 2)"),
+        Ok(serde_json::json!({ "int": 1 }))
+    );
+}
+
+#[test]
+fn functions_capture_their_scope() {
+    let source = "let add :: Int -> Int = {
+  let n :: Int = 10;
+  x { x + n }
+};
+let n :: Int = 1;
+add(n)";
+
+    assert_eq!(run_value(source), Ok(serde_json::json!({ "int": 11 })));
+}
+
+#[test]
+fn add() {
+    assert_eq!(
+        run_value("+1 + +2"),
+        Ok(serde_json::json!({ "effect": { "addEffect": 3 } }))
+    );
+    assert_eq!(run_value("1 + 2"), Ok(serde_json::json!({ "int": 3 })));
+    assert_eq!(
+        execute_ast(&parse("+1 + 2").unwrap()).map_err(|err| err.render("+1 + 2")),
+        Err(
+            "1:4: `+` expects two Ints or two add effects, found an add effect and an Int"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn choices_branch() {
+    assert_eq!(
+        run_snapshot("([(count, +1)] | [(state, =>`review`)]) reduce { count: 1, state: `draft` }"),
+        Ok(serde_json::json!({
+            "choice": [
+                { "snapshot": [["count", { "Int": 2 }], ["state", { "StateLabel": { "Label": "draft" } }]] },
+                { "snapshot": [["count", { "Int": 1 }], ["state", { "StateLabel": { "Label": "review" } }]] }
+            ]
+        }))
+    );
+    assert_eq!(
+        run_snapshot("[] << ((count: Int) apply ((+1 | +2) | Id))"),
+        Ok(serde_json::json!({
+            "choice": [
+                { "slice": [["count", { "addEffect": 1 }]] },
+                { "slice": [["count", { "addEffect": 2 }]] },
+                { "slice": [["count", "id"]] }
+            ]
+        }))
+    );
+    assert_eq!(
+        execute_ast(&parse("[] | +1").unwrap()).map_err(|err| err.render("[] | +1")),
+        Err(
+            "1:4: `|` expects two effects or two slices, found a slice and an add effect"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn branches_reach_every_route() {
+    assert_eq!(
+        run_value(include_str!("fixtures/branches.crg")),
+        Ok(serde_json::json!({
+            "routes": [
+                {
+                    "path": ["quarrel"],
+                    "snapshot": [["day", { "Int": 3 }], ["alice", { "StateLabel": { "Label": "calm" } }]]
+                },
+                {
+                    "path": ["ignore"],
+                    "snapshot": [["day", { "Int": 4 }], ["alice", { "StateLabel": { "Label": "calm" } }]]
+                }
+            ]
+        }))
+    );
+}
+
+#[test]
+fn unreachable_routes_keep_their_error() {
+    assert_eq!(
+        run_snapshot(
+            "[(a: [(state, =>`published`)] | b: [(state, =>`draft`)] | c: [(d: [] | e: [(count, +1)])])] reduceAll { count: 1, state: `review` }"
+        ),
+        Ok(serde_json::json!({
+            "routes": [
+                {
+                    "path": ["a"],
+                    "snapshot": [["count", { "Int": 1 }], ["state", { "StateLabel": { "Label": "published" } }]]
+                },
+                {
+                    "path": ["b"],
+                    "snapshot": [["count", { "Int": 1 }], ["state", { "StateLabel": { "Label": "review" } }]],
+                    "error": "`review` cannot transition to `draft`"
+                },
+                {
+                    "path": ["c", "d"],
+                    "snapshot": [["count", { "Int": 1 }], ["state", { "StateLabel": { "Label": "review" } }]]
+                },
+                {
+                    "path": ["c", "e"],
+                    "snapshot": [["count", { "Int": 2 }], ["state", { "StateLabel": { "Label": "review" } }]]
+                }
+            ]
+        }))
+    );
+    assert_eq!(
+        run_snapshot("[(a: [(state, =>`draft`)])] reduceAll { state: `review` }"),
+        Err("3:29: `review` cannot transition to `draft`".to_string())
+    );
+    assert_eq!(
+        run_snapshot("[(a: [] | b: [])] reduce { state: `review` }"),
+        Err("3:19: the slice branches, use `reduceAll` to follow every route".to_string())
+    );
+}
+
+#[test]
+fn outputs_are_collected() {
+    let state = |label: &str| serde_json::json!({ "StateLabel": { "Label": label } });
+
+    assert_eq!(
+        outputs_value(include_str!("fixtures/outputs.crg")).map(|json| json["outputs"].clone()),
+        Ok(serde_json::json!([
+            {
+                "label": "monday",
+                "value": { "snapshot": [["edits", { "Int": 3 }], ["state", state("review")]] }
+            },
+            {
+                "label": "friday reduce start",
+                "value": { "snapshot": [["edits", { "Int": 3 }], ["state", state("published")]] }
+            }
+        ]))
+    );
+    // outputs inside functions are kept and the value passes through
+    assert_eq!(
+        outputs_value("let f :: Int -> Int = x { Output arg << x };\nf(2) + (Output << 1 + 2)"),
+        Ok(serde_json::json!({
+            "outputs": [
+                { "label": "arg", "value": { "int": 2 } },
+                { "label": "1 + 2", "value": { "int": 3 } }
+            ],
+            "value": { "int": 5 }
+        }))
+    );
+    // `run` only has the value, and the report is the same with no outputs
+    assert_eq!(
+        run_value("let f :: Int -> Int = x { Output arg << x };\nf(2)"),
+        Ok(serde_json::json!({ "int": 2 }))
+    );
+    assert_eq!(
+        outputs_value("1 + 2"),
+        Ok(serde_json::json!({ "outputs": [], "value": { "int": 3 } }))
+    );
+}
@@ -0,0 +1,113 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{ErrorKind, Session, SessionBinding};
+
+const TYPES: &str = "type Machine :: StateMachine = Id => `draft` => `review`;
+type Snap :: Snapshot = { count: Int, state: Machine };";
+
+#[test]
+fn bindings_stay_across_inputs() {
+    let mut session = Session::new();
+
+    assert_eq!(session.eval(TYPES), Ok(None));
+    assert_eq!(
+        session.eval("let s :: Snap = { count: 1, state: `draft` };"),
+        Ok(None)
+    );
+    assert_eq!(
+        session.eval("let slice :: Slice = [(count, +1), (state, =>`review`)];\nslice"),
+        Ok(Some("[(count, +1), (state, =>`review`)]".to_string()))
+    );
+    assert_eq!(
+        session.eval("slice reduce s"),
+        Ok(Some("{ count: 2, state: `review` }".to_string()))
+    );
+    assert_eq!(
+        session.bindings(),
+        vec![
+            SessionBinding {
+                name: "s".to_string(),
+                kind: "Snap".to_string(),
+                value: "{ count: 1, state: `draft` }".to_string(),
+            },
+            SessionBinding {
+                name: "slice".to_string(),
+                kind: "Slice".to_string(),
+                value: "[(count, +1), (state, =>`review`)]".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn failing_inputs_change_nothing() {
+    let mut session = Session::new();
+    session.eval(TYPES).unwrap();
+
+    let err = session
+        .eval("let a :: Int = 1;\nlet b :: Slice = +1;")
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Type);
+    assert_eq!(
+        session.eval("a").unwrap_err().render("a"),
+        "1:1: unknown symbol `a`"
+    );
+    assert_eq!(
+        session.eval("let a :: Int = 2;\na + 1"),
+        Ok(Some("3".to_string()))
+    );
+}
+
+#[test]
+fn types_without_evaluating() {
+    let mut session = Session::new();
+    session.eval(TYPES).unwrap();
+    session
+        .eval("let e :: ContextEffect = (count, +1);")
+        .unwrap();
+
+    assert_eq!(
+        session.type_of("e"),
+        Ok("a context effect on `count`".to_string())
+    );
+    assert_eq!(
+        session.type_of("[] << e"),
+        Ok("a slice on `count`".to_string())
+    );
+    assert_eq!(
+        session.type_of("+1 | =>`draft`"),
+        Ok("an add effect | a transition".to_string())
+    );
+    assert_eq!(
+        session.type_of("let x :: Int = 1;\nx"),
+        Ok("an Int".to_string())
+    );
+    // statements and `Id` have no type of their own
+    assert_eq!(
+        session.type_of("let y :: Int = 1;"),
+        Ok("a value".to_string())
+    );
+    assert_eq!(
+        session.eval("x").unwrap_err().render("x"),
+        "1:1: unknown symbol `x`"
+    );
+}
+
+#[test]
+fn readable_values() {
+    let mut session = Session::new();
+    session.eval(TYPES).unwrap();
+
+    assert_eq!(
+        session.eval("+1 | +(-2)"),
+        Ok(Some("+1 | +(-2)".to_string()))
+    );
+    assert_eq!(
+        session.eval("[(a: [(count, +1)] | b: [])] reduceAll { count: 1 }"),
+        Ok(Some("a: { count: 2 }\nb: { count: 1 }".to_string()))
+    );
+    assert_eq!(
+        session.eval("let f :: Int -> Int -> Int = x y { x + y };\nf(1)"),
+        Ok(Some("<function of 1 argument>".to_string()))
+    );
+}
@@ -0,0 +1,25 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{query, syntax_tree, Span, SyntaxMatch};
+
+#[test]
+fn query_syntax_tree() {
+    let source = "let a :: Int = 1;\nlet b :: Effect = +2;\na";
+    let tree = syntax_tree(source).unwrap();
+    let matches = query(
+        &tree,
+        source,
+        "bindExpr:has(typeSymbol[value=Effect]) > varSymbol",
+    )
+    .unwrap();
+
+    assert_eq!(
+        matches,
+        vec![SyntaxMatch {
+            name: "varSymbol".to_string(),
+            span: Span::new(22, 23),
+        }]
+    );
+    assert_eq!(&source[22..23], "b");
+    assert!(query(&tree, source, "letStmt >").is_err());
+}
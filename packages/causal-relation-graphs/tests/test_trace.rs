@@ -0,0 +1,114 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{run, run_traced, ErrorKind, Span, TraceEvent};
+
+const SOURCE: &str = "type Machine :: StateMachine = Id => `a` => `b`;
+type Snap :: Snapshot = { count: Int, state: Machine };
+let inc :: Int -> Int = x { x + 1 };
+let slice :: Slice = [(count, +1), (state, =>`b`)];
+let n :: Int = inc(2);
+slice reduce { count: 1, state: `a` }";
+
+#[test]
+fn events() {
+    let (result, trace) = run_traced(SOURCE);
+
+    assert_eq!(result, run(SOURCE));
+    assert_eq!(
+        trace.events.first(),
+        Some(&TraceEvent::PushScope { depth: 1 })
+    );
+    assert_eq!(
+        trace.events.last(),
+        Some(&TraceEvent::PopScope { depth: 1 })
+    );
+    assert!(trace.events.contains(&TraceEvent::Bind {
+        depth: 2,
+        name: "x".to_string(),
+        value: "2".to_string(),
+    }));
+    assert!(trace.events.contains(&TraceEvent::Bind {
+        depth: 1,
+        name: "n".to_string(),
+        value: "3".to_string(),
+    }));
+    assert!(trace.events.contains(&TraceEvent::Apply {
+        depth: 1,
+        context: "state".to_string(),
+        effect: "=>`b`".to_string(),
+        before: "`a`".to_string(),
+        after: "`b`".to_string(),
+    }));
+    assert_eq!(
+        trace.events[trace.events.len() - 2],
+        TraceEvent::Eval {
+            depth: 1,
+            span: Span::new(223, 230),
+            expr: "slice reduce { count: 1, state: `a` }".to_string(),
+            value: "{ count: 2, state: `b` }".to_string(),
+        }
+    );
+}
+
+#[test]
+fn log() {
+    let (_, trace) = run_traced(SOURCE);
+
+    assert_eq!(
+        trace.render(SOURCE),
+        "{
+  let inc = <function of 1 argument>
+  let slice = [(count, +1), (state, =>`b`)]
+  5:16: inc = <function of 1 argument>
+  {
+    let x = 2
+    {
+      3:29: x = 2
+      3:31: x + 1 = 3
+    }
+  }
+  5:19: inc(2) = 3
+  let n = 3
+  6:1: slice = [(count, +1), (state, =>`b`)]
+  apply (count, +1): 1 -> 2
+  apply (state, =>`b`): `a` -> `b`
+  6:7: slice reduce { count: 1, state: `a` } = { count: 2, state: `b` }
+}
+"
+    );
+}
+
+#[test]
+fn routes_are_traced() {
+    let source = "type Snap :: Snapshot = { count: Int };
+[(a: [(count, +1)] | b: [(count, +2)])] reduceAll { count: 1 }";
+    let (result, trace) = run_traced(source);
+    assert!(result.is_ok());
+
+    let applied = trace
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::Apply { after, .. } => Some(after.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(applied, vec!["2", "3"]);
+}
+
+#[test]
+fn failures_keep_the_trace() {
+    let source = "let a :: Int = 1;\nlet f :: Int -> Int = 1 { 1 };\nf(a + 1)";
+    let (result, trace) = run_traced(source);
+
+    assert_eq!(result.unwrap_err().kind, ErrorKind::Evaluation);
+    assert!(trace.events.contains(&TraceEvent::Bind {
+        depth: 1,
+        name: "a".to_string(),
+        value: "1".to_string(),
+    }));
+
+    let (result, trace) = run_traced("let a :: Slice = +1;\na");
+    assert_eq!(result.unwrap_err().kind, ErrorKind::Type);
+    assert!(trace.events.is_empty());
+}
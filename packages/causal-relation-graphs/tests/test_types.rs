@@ -0,0 +1,158 @@
+extern crate causal_relation_graphs;
+
+use causal_relation_graphs::{check, graph, parse, run, ErrorKind};
+
+const MACHINES: &str = "type Walk :: StateMachine = Id => `idle` => `walking` => `tired`;
+type Run :: StateMachine = Id => `idle` => `running` => `tired`;
+";
+
+fn check_error(source: &str) -> (ErrorKind, String) {
+    let source = format!("{}{}", MACHINES, source);
+    let err = check(&parse(&source).unwrap()).unwrap_err();
+
+    (err.kind, err.render(&source))
+}
+
+fn checks(source: &str) -> bool {
+    let source = format!("{}{}", MACHINES, source);
+
+    check(&parse(&source).unwrap()).is_ok() && run(&source).is_ok()
+}
+
+#[test]
+fn unions_of_machines() {
+    let types = "type Move :: StateMachine = Walk | Run;
+type Legs :: Snapshot = { legs: Move };
+";
+
+    assert!(checks(&format!(
+        "{}let s :: Legs = {{ legs: `idle` }};\n[(legs, =>`running`), (legs, =>`tired`)] reduce s",
+        types
+    )));
+    assert!(checks(&format!(
+        "{}let s :: Legs = {{ legs: `idle` }};\n[(legs, =>`walking`)] reduce s",
+        types
+    )));
+    assert_eq!(
+        check_error(&format!(
+            "{}(legs: Move) apply (=>`walking` compose =>`running`)",
+            types
+        )),
+        (
+            ErrorKind::Type,
+            "5:14: `running` is not reachable from `walking` in context `legs`".to_string()
+        )
+    );
+}
+
+#[test]
+fn intersections_of_machines() {
+    let types = "type Both :: StateMachine = Walk & (Id => `idle` => `walking`);
+type Legs :: Snapshot = { legs: Both };
+";
+
+    assert!(checks(&format!(
+        "{}let s :: Legs = {{ legs: `idle` }};\n[(legs, =>`walking`)] reduce s",
+        types
+    )));
+    assert_eq!(
+        check_error(&format!("{}[(legs, =>`tired`)]", types)),
+        (
+            ErrorKind::Type,
+            "5:3: `tired` is not a state of context `legs`".to_string()
+        )
+    );
+}
+
+#[test]
+fn snapshots() {
+    let types = "type A :: Snapshot = { count: Int, legs: Walk };
+type B :: Snapshot = { count: Int, score: Int };
+type All :: Snapshot = A & B;
+type Shared :: Snapshot = A | B;
+";
+
+    assert!(checks(&format!(
+        "{}let s :: All = {{ count: 1, legs: `idle`, score: 1 }};\n\
+         let t :: Shared = {{ count: 1 }};\ns",
+        types
+    )));
+    assert_eq!(
+        check_error(&format!(
+            "{}let s :: All = {{ count: 1, score: 1 }};\ns",
+            types
+        )),
+        (
+            ErrorKind::Type,
+            "7:5: `s` is missing context `legs` of All".to_string()
+        )
+    );
+}
+
+#[test]
+fn recursion() {
+    let types = "type Loop :: StateMachine = (Id => `a` => `b`) | (`b` => `a`) | Loop;
+type S :: Snapshot = { state: Loop };
+";
+
+    assert!(checks(&format!(
+        "{}let s :: S = {{ state: `a` }};\n\
+         [(state, =>`b`), (state, =>`a`), (state, =>`b`)] reduce s",
+        types
+    )));
+
+    // types may refer to each other
+    let types = "type Even :: StateMachine = (Id => `zero`) | Odd;
+type Odd :: StateMachine = (`zero` => `one`) | Even;
+type S :: Snapshot = { state: Even };
+";
+    assert!(checks(&format!(
+        "{}let s :: S = {{ state: `zero` }};\n[(state, =>`one`)] reduce s",
+        types
+    )));
+
+    assert_eq!(
+        check_error("type Self :: StateMachine = Self | Self;\n{}"),
+        (
+            ErrorKind::Type,
+            "3:6: `Self` is only defined by itself".to_string()
+        )
+    );
+}
+
+#[test]
+fn kinds() {
+    assert_eq!(
+        check_error("type S :: Snapshot = { legs: Walk };\ntype M :: StateMachine = Walk & S;\n{}"),
+        (
+            ErrorKind::Type,
+            "4:33: `S` is a Snapshot, not a StateMachine".to_string()
+        )
+    );
+    assert_eq!(
+        check_error("type M :: StateMachine = Walk | Missing;\n{}"),
+        (
+            ErrorKind::UnknownSymbol,
+            "3:33: unknown type `Missing`".to_string()
+        )
+    );
+}
+
+#[test]
+fn graphs() {
+    let source = format!(
+        "{}type Move :: StateMachine = Walk | Run;\ntype S :: Snapshot = {{ legs: Move }};\n{{}}",
+        MACHINES
+    );
+    let graph = graph(&parse(&source).unwrap()).unwrap();
+
+    assert_eq!(
+        graph
+            .nodes
+            .iter()
+            .map(|node| node.label.as_str())
+            .collect::<Vec<_>>(),
+        ["legs", "idle", "walking", "tired", "running"]
+    );
+    assert_eq!(graph.edges.len(), 5);
+}
@@ -0,0 +1,149 @@
+use crate::server::{Document, Server};
+use causal_relation_graphs::{EffectKind, SymbolKind, Symbols};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, Location, MarkupContent, MarkupKind,
+    SemanticToken, SemanticTokenType, SemanticTokens, SemanticTokensParams, SemanticTokensResult,
+    TextDocumentPositionParams,
+};
+
+/// Token types of the semantic tokens legend, indexed by `token_type`.
+pub const TOKEN_TYPES: [SemanticTokenType; 3] = [
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::KEYWORD,
+];
+
+/// The declared type of the symbol under the cursor, and for a `let` the
+/// value it evaluated to.
+pub fn hover(server: &Server, params: HoverParams) -> Option<Hover> {
+    let (document, symbols, offset) = at_position(server, &params.text_document_position_params)?;
+    let (span, declaration) = symbols.declaration_at(offset)?;
+    let detail = declaration
+        .detail
+        .as_ref()
+        .map(|item| format!(" :: {}", item))
+        .unwrap_or_default();
+    let mut value = match declaration.kind {
+        SymbolKind::Value => format!("let {}{}", declaration.name, detail),
+        SymbolKind::Type => format!("type {}{}", declaration.name, detail),
+        SymbolKind::Context => format!(
+            "{}{}",
+            declaration.name,
+            declaration
+                .detail
+                .as_ref()
+                .map(|item| format!(": {}", item))
+                .unwrap_or_default()
+        ),
+    };
+    let binding = match (&document.bindings, declaration.binding) {
+        (Ok(bindings), Some(idx)) => bindings.get(idx),
+        _ => None,
+    };
+    if let Some(binding) = binding {
+        value.push_str(&format!(" = {}", binding.value));
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::PlainText,
+            value,
+        }),
+        range: Some(lsp_common::range(&document.text, span.start, span.end)),
+    })
+}
+
+/// The declaration a `let`, type or context name refers to.
+pub fn definition(server: &Server, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+    let (document, symbols, offset) = at_position(server, &params.text_document_position_params)?;
+    let (_, declaration) = symbols.declaration_at(offset)?;
+
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+        params.text_document_position_params.text_document.uri,
+        lsp_common::range(&document.text, declaration.span.start, declaration.span.end),
+    )))
+}
+
+/// Declared contexts starting with the word before the cursor.
+pub fn completion(server: &Server, params: CompletionParams) -> Option<CompletionResponse> {
+    let position = params.text_document_position;
+    let document = server.documents.get(&position.text_document.uri)?;
+    let offset = lsp_common::offset(&document.text, position.position);
+    let before = &document.text[..offset];
+    let prefix = &before[before
+        .rfind(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .map(|idx| idx + 1)
+        .unwrap_or(0)..];
+
+    Some(CompletionResponse::Array(
+        document
+            .contexts
+            .iter()
+            .filter(|item| item.starts_with(prefix))
+            .map(|item| CompletionItem {
+                label: item.clone(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some("context".to_string()),
+                ..Default::default()
+            })
+            .collect(),
+    ))
+}
+
+/// Effect literals: `+n` as numbers, `=>` transitions as enum members and
+/// `Id` and `Empty` as keywords.
+pub fn semantic_tokens(
+    server: &Server,
+    params: SemanticTokensParams,
+) -> Option<SemanticTokensResult> {
+    let document = server.documents.get(&params.text_document.uri)?;
+    let symbols = document.symbols.as_ref().ok()?;
+    let mut effects = symbols.effects.clone();
+    effects.sort_by_key(|(_, span)| span.start);
+
+    let mut previous = lsp_types::Position::new(0, 0);
+    let data = effects
+        .iter()
+        .map(|(kind, span)| {
+            let range = lsp_common::range(&document.text, span.start, span.end);
+            let delta_line = range.start.line - previous.line;
+            let delta_start = match delta_line {
+                0 => range.start.character - previous.character,
+                _ => range.start.character,
+            };
+            previous = range.start;
+
+            SemanticToken {
+                delta_line,
+                delta_start,
+                // in UTF-16 units of the source, as an effect can span lines
+                length: document.text[span.start..span.end].encode_utf16().count() as u32,
+                token_type: match kind {
+                    EffectKind::Add => 0,
+                    EffectKind::Transition => 1,
+                    EffectKind::Id | EffectKind::Empty => 2,
+                },
+                token_modifiers_bitset: 0,
+            }
+        })
+        .collect();
+
+    Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data,
+    }))
+}
+
+fn at_position<'a>(
+    server: &'a Server,
+    params: &TextDocumentPositionParams,
+) -> Option<(&'a Document, &'a Symbols, usize)> {
+    let document = server.documents.get(&params.text_document.uri)?;
+
+    Some((
+        document,
+        document.symbols.as_ref().ok()?,
+        lsp_common::offset(&document.text, params.position),
+    ))
+}
@@ -0,0 +1,52 @@
+//! Language server for `.crg` causal relation graph files.
+//!
+//! Publishes syntax, type and evaluation errors as diagnostics and serves
+//! the type and value of `let` bindings on hover, definitions of value, type
+//! and context names, completion of declared contexts and semantic tokens
+//! for effect literals.
+
+extern crate causal_relation_graphs;
+extern crate lsp_common;
+extern crate lsp_server;
+extern crate lsp_types;
+
+mod features;
+mod server;
+
+use lsp_server::Connection;
+use lsp_types::{
+    CompletionOptions, HoverProviderCapability, OneOf, SemanticTokensFullOptions,
+    SemanticTokensLegend, SemanticTokensOptions, SemanticTokensServerCapabilities,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+};
+pub use server::{Document, Server};
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["(".to_string(), "{".to_string()]),
+            ..Default::default()
+        }),
+        semantic_tokens_provider: Some(
+            SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: features::TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            }),
+        ),
+        ..Default::default()
+    }
+}
+
+/// Serves `connection` until the client shuts the server down.
+pub fn run(connection: &Connection) -> Result<(), String> {
+    lsp_common::run::<Server>(connection)
+}
@@ -0,0 +1,5 @@
+extern crate crg_lsp;
+
+fn main() {
+    lsp_common::stdio(crg_lsp::run)
+}
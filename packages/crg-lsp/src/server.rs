@@ -0,0 +1,129 @@
+use crate::features;
+use causal_relation_graphs::{Binding, Error, Symbols};
+use lsp_common::{respond, DocumentError};
+use lsp_server::{ErrorCode, Request, Response};
+use lsp_types::request::{
+    Completion, GotoDefinition, HoverRequest, Request as RequestTrait, SemanticTokensFullRequest,
+};
+use lsp_types::{ServerCapabilities, Url};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+/// An open `.crg` file, its symbols and its evaluated bindings.
+pub struct Document {
+    pub text: String,
+    pub symbols: Result<Symbols, Error>,
+    pub bindings: Result<Vec<Binding>, Error>,
+    /// Contexts of the last parse that succeeded, so completion keeps working
+    /// while the file is being edited.
+    pub contexts: BTreeSet<String>,
+}
+
+impl lsp_common::Document for Document {
+    const SOURCE: &'static str = "crg-lsp";
+
+    fn new(text: &str) -> Self {
+        let mut document = Self {
+            text: text.to_string(),
+            symbols: Ok(Symbols::default()),
+            bindings: Ok(Vec::new()),
+            contexts: BTreeSet::new(),
+        };
+        document.update();
+
+        document
+    }
+
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn edit(&mut self, range: Range<usize>, text: &str) {
+        self.text.replace_range(range, text);
+        self.update();
+    }
+
+    fn errors(&self) -> Vec<DocumentError> {
+        match (&self.symbols, &self.bindings) {
+            (Err(err), _) | (_, Err(err)) => vec![DocumentError {
+                start: err.span.start,
+                end: err.span.end,
+                code: err.kind.code().to_string(),
+                message: err.message.clone(),
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Document {
+    fn update(&mut self) {
+        self.symbols = causal_relation_graphs::symbols(&self.text);
+        self.bindings = match &self.symbols {
+            Ok(_) => evaluate(&self.text),
+            Err(_) => Ok(Vec::new()),
+        };
+        if let Ok(symbols) = &self.symbols {
+            self.contexts = symbols
+                .contexts()
+                .into_iter()
+                .map(|item| item.to_string())
+                .collect();
+        }
+    }
+}
+
+fn evaluate(text: &str) -> Result<Vec<Binding>, Error> {
+    let ast = causal_relation_graphs::parse(text)?;
+    causal_relation_graphs::check(&ast)?;
+
+    causal_relation_graphs::bindings(&ast)
+}
+
+#[derive(Default)]
+pub struct Server {
+    pub documents: BTreeMap<Url, Document>,
+}
+
+impl lsp_common::LanguageServer for Server {
+    type Document = Document;
+
+    fn capabilities() -> ServerCapabilities {
+        crate::capabilities()
+    }
+
+    fn documents_mut(&mut self) -> &mut BTreeMap<Url, Document> {
+        &mut self.documents
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => respond(request.params, |params| features::hover(self, params)),
+            GotoDefinition::METHOD => {
+                respond(request.params, |params| features::definition(self, params))
+            }
+            Completion::METHOD => {
+                respond(request.params, |params| features::completion(self, params))
+            }
+            SemanticTokensFullRequest::METHOD => respond(request.params, |params| {
+                features::semantic_tokens(self, params)
+            }),
+            method => {
+                return Response::new_err(
+                    request.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unknown method: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(value) => Response {
+                id: request.id,
+                result: Some(value),
+                error: None,
+            },
+            Err(err) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, err),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+extern crate crg_lsp;
+
+use lsp_common::client::Client;
+use serde_json::{json, Value};
+
+const DOCUMENT: &str = "type State :: StateMachine = Id => `a` => `b`;
+type Snap :: Snapshot = { count: Int, state: State };
+let slice :: Slice = [(count, +10)];
+let t :: Effect = =>`b`;
+slice reduce { count: 1 }";
+
+fn start() -> Client {
+    let client = Client::start(crg_lsp::run, "crg");
+    assert_eq!(client.capabilities["definitionProvider"], json!(true));
+
+    client
+}
+
+#[test]
+fn diagnostics() {
+    let mut client = start();
+    client.open("file:///a.crg", DOCUMENT);
+    client.open("file:///b.crg", "let a :: Effect = +1;\nlet b :: = +1;\nc");
+
+    assert_eq!(client.diagnostics("file:///a.crg"), json!([]));
+    let diagnostics = client.diagnostics("file:///b.crg");
+    assert_eq!(diagnostics[0]["code"], "syntax");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 1, "character": 9 })
+    );
+
+    // the syntax is fixed, but `c` is not bound
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": "file:///b.crg", "version": 2 },
+            "contentChanges": [{ "range": { "start": { "line": 1, "character": 9 }, "end": { "line": 1, "character": 9 } }, "text": "Effect" }],
+        }),
+    );
+    let diagnostics = client.diagnostics("file:///b.crg");
+    assert_eq!(diagnostics[0]["code"], "unknown-symbol");
+    assert_eq!(diagnostics[0]["message"], "unknown symbol `c`");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 2, "character": 0 })
+    );
+}
+
+#[test]
+fn hover_and_definition() {
+    let mut client = start();
+    client.open("file:///a.crg", DOCUMENT);
+
+    let hover = client.at("textDocument/hover", "file:///a.crg", 4, 2);
+    assert_eq!(
+        hover["contents"]["value"],
+        "let slice :: Slice = {\"slice\":[[\"count\",{\"addEffect\":10}]]}"
+    );
+    assert_eq!(
+        hover["range"],
+        json!({ "start": { "line": 4, "character": 0 }, "end": { "line": 4, "character": 5 } })
+    );
+    assert_eq!(
+        client.at("textDocument/hover", "file:///a.crg", 1, 48)["contents"]["value"],
+        "type State :: StateMachine"
+    );
+    assert_eq!(
+        client.at("textDocument/hover", "file:///a.crg", 2, 24)["contents"]["value"],
+        "count: Int"
+    );
+    assert_eq!(
+        client.at("textDocument/hover", "file:///a.crg", 0, 37),
+        Value::Null
+    );
+
+    let definition = client.at("textDocument/definition", "file:///a.crg", 4, 2);
+    assert_eq!(
+        definition["range"],
+        json!({ "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 9 } })
+    );
+    let definition = client.at("textDocument/definition", "file:///a.crg", 2, 24);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 1, "character": 26 })
+    );
+}
+
+#[test]
+fn completion() {
+    let mut client = start();
+    client.open("file:///a.crg", DOCUMENT);
+
+    // the half typed slice does not parse, the contexts of the last parse are kept
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": "file:///a.crg", "version": 2 },
+            "contentChanges": [{ "range": { "start": { "line": 4, "character": 25 }, "end": { "line": 4, "character": 25 } }, "text": ";\n[(s" }],
+        }),
+    );
+    let items = client.at("textDocument/completion", "file:///a.crg", 5, 3);
+    assert_eq!(
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["state"]
+    );
+    assert_eq!(
+        client
+            .at("textDocument/completion", "file:///a.crg", 5, 2)
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn semantic_tokens() {
+    let mut client = start();
+    client.open("file:///a.crg", DOCUMENT);
+
+    let tokens = client.request(
+        "textDocument/semanticTokens/full",
+        json!({ "textDocument": { "uri": "file:///a.crg" } }),
+    );
+    // `+10` on line 2 and `=>`b`` on line 3
+    assert_eq!(tokens["data"], json!([2, 30, 3, 0, 0, 1, 18, 5, 1, 0]));
+
+    client.open("file:///b.crg", "let e :: Effect = +(\n    1);\ne");
+    let tokens = client.request(
+        "textDocument/semanticTokens/full",
+        json!({ "textDocument": { "uri": "file:///b.crg" } }),
+    );
+    // `+(\n    1)` starts on line 0 and its length counts the line break
+    assert_eq!(tokens["data"], json!([0, 18, 9, 0, 0]));
+}
@@ -0,0 +1,124 @@
+//! A client that drives a server on a thread of its own, for tests.
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use serde_json::{json, Value};
+use std::thread::{self, JoinHandle};
+
+pub struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<Result<(), String>>>,
+    next_id: i32,
+    language_id: String,
+    /// The capabilities the server answered `initialize` with.
+    pub capabilities: Value,
+    /// Notifications from the server, oldest first.
+    pub notifications: Vec<Notification>,
+}
+
+impl Client {
+    /// Starts `run` and initializes it. Documents are opened as
+    /// `language_id`.
+    pub fn start(run: fn(&Connection) -> Result<(), String>, language_id: &str) -> Self {
+        let (server, connection) = Connection::memory();
+        let server = thread::spawn(move || run(&server));
+        let mut client = Client {
+            connection,
+            server: Some(server),
+            next_id: 0,
+            language_id: language_id.to_string(),
+            capabilities: Value::Null,
+            notifications: Vec::new(),
+        };
+        let result = client.request("initialize", json!({ "capabilities": {} }));
+        client.capabilities = result["capabilities"].clone();
+        client.notify("initialized", json!({}));
+
+        client
+    }
+
+    /// The result of a request, which must succeed.
+    pub fn request(&mut self, method: &str, params: Value) -> Value {
+        let response = self.send(method, params);
+        assert!(response.error.is_none(), "{:?}", response.error);
+
+        response.result.unwrap_or(Value::Null)
+    }
+
+    fn send(&mut self, method: &str, params: Value) -> Response {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        self.connection
+            .sender
+            .send(Message::Request(Request::new(
+                id.clone(),
+                method.to_string(),
+                params,
+            )))
+            .unwrap();
+
+        loop {
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) if response.id == id => return response,
+                Message::Notification(notification) => self.notifications.push(notification),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn notify(&self, method: &str, params: Value) {
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                method.to_string(),
+                params,
+            )))
+            .unwrap();
+    }
+
+    pub fn open(&mut self, uri: &str, text: &str) {
+        let language_id = self.language_id.clone();
+        self.notify(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": uri, "languageId": language_id, "version": 1, "text": text } }),
+        );
+    }
+
+    /// Diagnostics most recently published for `uri`.
+    pub fn diagnostics(&mut self, uri: &str) -> Value {
+        // a request round trip flushes the notifications sent before it,
+        // whatever the server answers
+        self.send("$/flush", Value::Null);
+
+        self.notifications
+            .iter()
+            .rev()
+            .find(|item| {
+                item.method == "textDocument/publishDiagnostics" && item.params["uri"] == uri
+            })
+            .map(|item| item.params["diagnostics"].clone())
+            .unwrap()
+    }
+
+    /// A request about the position `line`:`character` of `uri`.
+    pub fn at(&mut self, method: &str, uri: &str, line: u32, character: u32) -> Value {
+        let mut params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        });
+        if method == "textDocument/references" {
+            params["context"] = json!({ "includeDeclaration": true });
+        }
+
+        self.request(method, params)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        if let Some(server) = self.server.take() {
+            assert_eq!(server.join().unwrap(), Ok(()));
+        }
+    }
+}
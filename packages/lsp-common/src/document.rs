@@ -0,0 +1,113 @@
+use lsp_server::Notification;
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    PublishDiagnosticsParams, Url,
+};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// An open file of a language server, reparsed as the client edits it.
+pub trait Document {
+    /// The name diagnostics are published under.
+    const SOURCE: &'static str;
+
+    fn new(text: &str) -> Self;
+
+    fn text(&self) -> &str;
+
+    /// Replaces the bytes `range` of the text with `text`.
+    fn edit(&mut self, range: Range<usize>, text: &str);
+
+    /// The errors of the latest parse.
+    fn errors(&self) -> Vec<DocumentError>;
+
+    fn diagnostics(&self) -> Vec<lsp_types::Diagnostic> {
+        self.errors()
+            .into_iter()
+            .map(|err| lsp_types::Diagnostic {
+                range: crate::range(self.text(), err.start, err.end),
+                severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+                code: Some(lsp_types::NumberOrString::String(err.code)),
+                source: Some(Self::SOURCE.to_string()),
+                message: err.message,
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// An error at the bytes `start..end` of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentError {
+    pub start: usize,
+    pub end: usize,
+    pub code: String,
+    pub message: String,
+}
+
+/// Opens, edits and closes `documents` as `notification` says, and returns
+/// the diagnostics to publish for the document it is about.
+pub fn sync<D: Document>(
+    documents: &mut BTreeMap<Url, D>,
+    notification: Notification,
+) -> Vec<Notification> {
+    let uri = match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            match serde_json::from_value::<DidOpenTextDocumentParams>(notification.params) {
+                Ok(params) => {
+                    let uri = params.text_document.uri;
+                    documents.insert(uri.clone(), D::new(&params.text_document.text));
+
+                    uri
+                }
+                Err(_) => return Vec::new(),
+            }
+        }
+        DidChangeTextDocument::METHOD => {
+            match serde_json::from_value::<DidChangeTextDocumentParams>(notification.params) {
+                Ok(params) => {
+                    let uri = params.text_document.uri;
+                    if let Some(document) = documents.get_mut(&uri) {
+                        params.content_changes.iter().for_each(|change| {
+                            let range = match change.range {
+                                Some(range) => {
+                                    let (start, end) = crate::byte_range(document.text(), range);
+                                    start..end
+                                }
+                                None => 0..document.text().len(),
+                            };
+                            document.edit(range, &change.text);
+                        });
+                    }
+
+                    uri
+                }
+                Err(_) => return Vec::new(),
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            match serde_json::from_value::<DidCloseTextDocumentParams>(notification.params) {
+                Ok(params) => {
+                    documents.remove(&params.text_document.uri);
+
+                    params.text_document.uri
+                }
+                Err(_) => return Vec::new(),
+            }
+        }
+        _ => return Vec::new(),
+    };
+    let diagnostics = documents
+        .get(&uri)
+        .map(Document::diagnostics)
+        .unwrap_or_default();
+
+    vec![Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        PublishDiagnosticsParams::new(uri, diagnostics, None),
+    )]
+}
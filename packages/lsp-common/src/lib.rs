@@ -0,0 +1,15 @@
+//! What the prose and CRG language servers share: positions in UTF-16
+//! columns, the message loop, keeping open documents in sync and a client
+//! for their tests.
+
+extern crate lsp_server;
+extern crate lsp_types;
+
+pub mod client;
+mod document;
+mod position;
+mod server;
+
+pub use document::{sync, Document, DocumentError};
+pub use position::{byte_range, offset, position, range};
+pub use server::{respond, run, stdio, LanguageServer};
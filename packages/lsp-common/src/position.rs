@@ -0,0 +1,43 @@
+use lsp_types::{Position, Range};
+
+/// LSP position of a byte offset. Columns are UTF-16 code units.
+pub fn position(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+
+    Position::new(
+        text[..line_start].matches('\n').count() as u32,
+        text[line_start..offset].encode_utf16().count() as u32,
+    )
+}
+
+/// LSP range of the bytes `start..end`.
+pub fn range(text: &str, start: usize, end: usize) -> Range {
+    Range::new(position(text, start), position(text, end))
+}
+
+/// Byte offset of an LSP position, clamped to the line and the text.
+pub fn offset(text: &str, position: Position) -> usize {
+    let line_start = match position.line {
+        0 => 0,
+        line => match text.match_indices('\n').nth(line as usize - 1) {
+            Some((idx, _)) => idx + 1,
+            None => return text.len(),
+        },
+    };
+    let mut column = 0;
+
+    text[line_start..]
+        .char_indices()
+        .find(|(_, ch)| {
+            column += ch.len_utf16();
+            *ch == '\n' || column > position.character as usize
+        })
+        .map(|(idx, _)| line_start + idx)
+        .unwrap_or(text.len())
+}
+
+/// Byte offsets of the start and end of an LSP range.
+pub fn byte_range(text: &str, range: Range) -> (usize, usize) {
+    (offset(text, range.start), offset(text, range.end))
+}
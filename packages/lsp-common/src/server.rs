@@ -0,0 +1,87 @@
+use crate::Document;
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::{ServerCapabilities, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The state of a language server and how it answers the client.
+pub trait LanguageServer: Default {
+    type Document: Document;
+
+    fn capabilities() -> ServerCapabilities;
+
+    /// The open documents, by URI.
+    fn documents_mut(&mut self) -> &mut BTreeMap<Url, Self::Document>;
+
+    fn handle_request(&self, request: Request) -> Response;
+
+    /// Handles a notification and returns the notifications to send back.
+    /// Keeps the open documents in sync unless overridden.
+    fn handle_notification(&mut self, notification: Notification) -> Vec<Notification> {
+        crate::sync(self.documents_mut(), notification)
+    }
+}
+
+/// Serves `connection` with a new `S` until the client shuts the server down.
+pub fn run<S: LanguageServer>(connection: &Connection) -> Result<(), String> {
+    let capabilities = serde_json::to_value(S::capabilities()).map_err(|err| err.to_string())?;
+    connection
+        .initialize(capabilities)
+        .map_err(|err| err.to_string())?;
+
+    let mut server = S::default();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection
+                    .handle_shutdown(&request)
+                    .map_err(|err| err.to_string())?
+                {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(Message::Response(server.handle_request(request)))
+                    .map_err(|err| err.to_string())?;
+            }
+            Message::Notification(notification) => server
+                .handle_notification(notification)
+                .into_iter()
+                .try_for_each(|item| connection.sender.send(Message::Notification(item)))
+                .map_err(|err| err.to_string())?,
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a server over stdin and stdout, for the `main` of a server binary.
+/// Exits with 1 when it fails.
+pub fn stdio(run: fn(&Connection) -> Result<(), String>) {
+    let (connection, io_threads) = Connection::stdio();
+    let result = run(&connection);
+    drop(connection);
+
+    match result.and_then(|_| io_threads.join().map_err(|err| err.to_string())) {
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads the params of a request as `P` and answers with what `f` makes of
+/// them.
+pub fn respond<P, R, F>(params: serde_json::Value, f: F) -> Result<serde_json::Value, String>
+where
+    P: DeserializeOwned,
+    R: Serialize,
+    F: FnOnce(P) -> R,
+{
+    let params = serde_json::from_value(params).map_err(|err| err.to_string())?;
+
+    serde_json::to_value(f(params)).map_err(|err| err.to_string())
+}
@@ -0,0 +1,288 @@
+use crate::server::{Document, Server};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, DocumentSymbol,
+    DocumentSymbolParams, DocumentSymbolResponse, FoldingRange, FoldingRangeKind,
+    FoldingRangeParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, Location, MarkupContent, MarkupKind, ReferenceParams, SymbolKind,
+    TextDocumentPositionParams, Url,
+};
+use prose_parser::{Node, Selector, Span, TagIndex, TagOccurrence};
+
+const TAGS: [&str; 2] = ["tag", "speaker"];
+
+/// Headings, nested by level.
+pub fn document_symbols(
+    server: &Server,
+    params: DocumentSymbolParams,
+) -> Option<DocumentSymbolResponse> {
+    let (document, tree) = parsed(server, &params.text_document.uri)?;
+    let mut roots = Vec::new();
+    let mut stack: Vec<(usize, DocumentSymbol)> = Vec::new();
+    let attach =
+        |roots: &mut Vec<DocumentSymbol>, stack: &mut Vec<(usize, DocumentSymbol)>, symbol| {
+            match stack.last_mut() {
+                Some((_, parent)) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+                None => roots.push(symbol),
+            }
+        };
+
+    tree.select(&Selector::name("heading"))
+        .iter()
+        .for_each(|heading| {
+            let level = heading
+                .children()
+                .first()
+                .and_then(|item| item.span())
+                .map(|span| span.end - span.start - 1)
+                .unwrap_or(1);
+            while stack.last().map(|(top, _)| *top >= level).unwrap_or(false) {
+                let (_, symbol) = stack.pop().unwrap();
+                attach(&mut roots, &mut stack, symbol);
+            }
+            let span = heading.span().unwrap_or_default();
+            let range = lsp_common::range(&document.text, span.start, span.end);
+
+            #[allow(deprecated)]
+            stack.push((
+                level,
+                DocumentSymbol {
+                    name: heading
+                        .remove(&Selector::names(&TAGS))
+                        .text()
+                        .trim()
+                        .to_string(),
+                    detail: None,
+                    kind: SymbolKind::STRING,
+                    tags: None,
+                    deprecated: None,
+                    range,
+                    selection_range: range,
+                    children: None,
+                },
+            ));
+        });
+    while let Some((_, symbol)) = stack.pop() {
+        attach(&mut roots, &mut stack, symbol);
+    }
+
+    Some(DocumentSymbolResponse::Nested(roots))
+}
+
+/// Runs of block comments on consecutive lines, and author notes.
+pub fn folding_ranges(server: &Server, params: FoldingRangeParams) -> Option<Vec<FoldingRange>> {
+    let (document, tree) = parsed(server, &params.text_document.uri)?;
+    let line = |offset: usize| lsp_common::position(&document.text, offset).line;
+    let folding_range = |start: usize, end: usize, kind: FoldingRangeKind| FoldingRange {
+        start_line: line(start),
+        start_character: None,
+        end_line: line(end),
+        end_character: None,
+        kind: Some(kind),
+        collapsed_text: None,
+    };
+
+    let mut comments: Vec<(usize, usize)> = Vec::new();
+    let mut ret = Vec::new();
+    tree.children().iter().for_each(|item| {
+        let span = item.span().unwrap_or_default();
+        match item.name() {
+            Some("block_comment") => match comments.last_mut() {
+                Some(run) if line(run.1) + 1 == line(span.start) => run.1 = span.start,
+                _ => comments.push((span.start, span.start)),
+            },
+            Some("preface" | "afterword") => ret.push(folding_range(
+                span.start,
+                span.end.saturating_sub(1),
+                FoldingRangeKind::Region,
+            )),
+            _ => {}
+        }
+    });
+    ret.extend(
+        comments
+            .into_iter()
+            .filter(|(start, end)| line(*start) < line(*end))
+            .map(|(start, end)| folding_range(start, end, FoldingRangeKind::Comment)),
+    );
+    ret.sort_by_key(|item| item.start_line);
+
+    Some(ret)
+}
+
+/// Tags of every open file, after `#`.
+pub fn completion(server: &Server, params: CompletionParams) -> Option<CompletionResponse> {
+    let position = params.text_document_position;
+    let document = server.documents.get(&position.text_document.uri)?;
+    let offset = lsp_common::offset(&document.text, position.position);
+    let before = &document.text[..offset];
+    let prefix_start = before
+        .rfind(|ch: char| !is_slug_char(ch))
+        .map(|idx| {
+            idx + before[idx..]
+                .chars()
+                .next()
+                .map(char::len_utf8)
+                .unwrap_or(1)
+        })
+        .unwrap_or(0);
+    if !before[..prefix_start].ends_with('#') {
+        return Some(CompletionResponse::Array(Vec::new()));
+    }
+    let prefix = &before[prefix_start..];
+
+    Some(CompletionResponse::Array(
+        tag_index(server)
+            .tags
+            .iter()
+            .filter(|(tag, _)| tag.starts_with(prefix))
+            .map(|(tag, occurrences)| CompletionItem {
+                label: tag.clone(),
+                kind: Some(CompletionItemKind::REFERENCE),
+                detail: Some(format!("{} occurrence(s)", occurrences.len())),
+                ..Default::default()
+            })
+            .collect(),
+    ))
+}
+
+/// Character count of the paragraph under the cursor, without tags and comments.
+pub fn hover(server: &Server, params: HoverParams) -> Option<Hover> {
+    let (document, tree, offset) = at_position(server, &params.text_document_position_params)?;
+    let paragraph = tree.children().iter().find(|item| {
+        item.name() == Some("paragraph")
+            && item
+                .span()
+                .map(|span| span.contains(offset))
+                .unwrap_or(false)
+    })?;
+    let characters = paragraph
+        .remove(&Selector::names(&[
+            "tag",
+            "speaker",
+            "inline_comment",
+            "footnote_ref",
+        ]))
+        .text()
+        .chars()
+        .count();
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::PlainText,
+            value: format!("{} characters", characters),
+        }),
+        range: paragraph
+            .span()
+            .map(|span| lsp_common::range(&document.text, span.start, span.end)),
+    })
+}
+
+/// The first occurrence of the tag under the cursor in a heading, or else the
+/// first occurrence in any open file.
+pub fn definition(server: &Server, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+    let tag = tag_at(server, &params.text_document_position_params)?;
+    let index = tag_index(server);
+    let occurrence = declaration(server, index.occurrences(&tag))?;
+
+    location(server, occurrence).map(GotoDefinitionResponse::Scalar)
+}
+
+pub fn references(server: &Server, params: ReferenceParams) -> Option<Vec<Location>> {
+    let tag = tag_at(server, &params.text_document_position)?;
+    let index = tag_index(server);
+    let occurrences = index.occurrences(&tag);
+    let declaration = declaration(server, occurrences);
+
+    Some(
+        occurrences
+            .iter()
+            .filter(|item| params.context.include_declaration || Some(*item) != declaration)
+            .filter_map(|item| location(server, item))
+            .collect(),
+    )
+}
+
+fn parsed<'a>(server: &'a Server, uri: &Url) -> Option<(&'a Document, &'a Node)> {
+    let document = server.documents.get(uri)?;
+
+    Some((document, document.tree.as_ref().ok()?))
+}
+
+fn at_position<'a>(
+    server: &'a Server,
+    params: &TextDocumentPositionParams,
+) -> Option<(&'a Document, &'a Node, usize)> {
+    let (document, tree) = parsed(server, &params.text_document.uri)?;
+
+    Some((
+        document,
+        tree,
+        lsp_common::offset(&document.text, params.position),
+    ))
+}
+
+fn tag_at(server: &Server, params: &TextDocumentPositionParams) -> Option<String> {
+    let (_, tree, offset) = at_position(server, params)?;
+
+    tree.select(&Selector::names(&TAGS))
+        .iter()
+        .find(|item| {
+            item.span()
+                .map(|span| span.start <= offset && offset <= span.end)
+                .unwrap_or(false)
+        })
+        .map(|item| item.text())
+}
+
+fn tag_index(server: &Server) -> TagIndex {
+    server
+        .documents
+        .iter()
+        .fold(TagIndex::new(), |mut acc, (uri, document)| {
+            if let Ok(tree) = &document.tree {
+                acc.add_document(uri.as_str(), &document.text, tree);
+            }
+
+            acc
+        })
+}
+
+fn declaration<'a>(server: &Server, occurrences: &'a [TagOccurrence]) -> Option<&'a TagOccurrence> {
+    occurrences
+        .iter()
+        .find(|item| in_heading(server, &item.file, item.span))
+        .or_else(|| occurrences.first())
+}
+
+fn in_heading(server: &Server, file: &str, span: Span) -> bool {
+    let tree = Url::parse(file)
+        .ok()
+        .and_then(|uri| server.documents.get(&uri))
+        .and_then(|document| document.tree.as_ref().ok());
+
+    tree.map(|tree| {
+        tree.select(&Selector::name("heading"))
+            .iter()
+            .any(|heading| {
+                heading
+                    .span()
+                    .map(|item| item.contains(span.start))
+                    .unwrap_or(false)
+            })
+    })
+    .unwrap_or(false)
+}
+
+fn location(server: &Server, occurrence: &TagOccurrence) -> Option<Location> {
+    let uri = Url::parse(&occurrence.file).ok()?;
+    let document = server.documents.get(&uri)?;
+
+    Some(Location::new(
+        uri,
+        lsp_common::range(&document.text, occurrence.span.start, occurrence.span.end),
+    ))
+}
+
+fn is_slug_char(ch: char) -> bool {
+    ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-' || ch == '_'
+}
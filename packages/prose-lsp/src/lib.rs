@@ -0,0 +1,43 @@
+//! Language server for `.pd` prose files.
+//!
+//! Publishes parse errors as diagnostics and serves document symbols for
+//! headings, folding of block comments, `#tag` completion across open files,
+//! paragraph character counts on hover and tag definition and references.
+
+extern crate lsp_common;
+extern crate lsp_server;
+extern crate lsp_types;
+extern crate prose_parser;
+
+mod features;
+mod server;
+
+use lsp_server::Connection;
+use lsp_types::{
+    CompletionOptions, FoldingRangeProviderCapability, HoverProviderCapability, OneOf,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+};
+pub use server::{Document, Server};
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["#".to_string()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+/// Serves `connection` until the client shuts the server down.
+pub fn run(connection: &Connection) -> Result<(), String> {
+    lsp_common::run::<Server>(connection)
+}
@@ -0,0 +1,5 @@
+extern crate prose_lsp;
+
+fn main() {
+    lsp_common::stdio(prose_lsp::run)
+}
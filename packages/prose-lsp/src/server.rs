@@ -0,0 +1,115 @@
+use crate::features;
+use lsp_common::{respond, DocumentError};
+use lsp_server::{ErrorCode, Request, Response};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, FoldingRangeRequest, GotoDefinition, HoverRequest,
+    References, Request as RequestTrait,
+};
+use lsp_types::{ServerCapabilities, Url};
+use prose_parser::{Diagnostic, Node, Span, TextEdit};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// An open `.pd` file and its latest parse.
+pub struct Document {
+    pub text: String,
+    pub tree: Result<Node, Diagnostic>,
+}
+
+impl lsp_common::Document for Document {
+    const SOURCE: &'static str = "prose-lsp";
+
+    fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            tree: prose_parser::check(text),
+        }
+    }
+
+    fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Reparses only the touched blocks when the previous parse succeeded.
+    fn edit(&mut self, range: Range<usize>, text: &str) {
+        let edit = TextEdit::new(Span::new(range.start, range.end), text);
+        let mut text = self.text.clone();
+        text.replace_range(range, &edit.text);
+        let tree = match &self.tree {
+            Ok(tree) => prose_parser::reparse(tree, &text, &edit).ok(),
+            Err(_) => None,
+        };
+
+        self.tree = match tree {
+            Some(tree) => Ok(tree),
+            None => prose_parser::check(&text),
+        };
+        self.text = text;
+    }
+
+    fn errors(&self) -> Vec<DocumentError> {
+        match &self.tree {
+            Ok(_) => Vec::new(),
+            Err(diagnostic) => vec![DocumentError {
+                start: diagnostic.span.start,
+                end: diagnostic.span.end,
+                code: diagnostic.code.clone(),
+                message: diagnostic.message.clone(),
+            }],
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Server {
+    pub documents: BTreeMap<Url, Document>,
+}
+
+impl lsp_common::LanguageServer for Server {
+    type Document = Document;
+
+    fn capabilities() -> ServerCapabilities {
+        crate::capabilities()
+    }
+
+    fn documents_mut(&mut self) -> &mut BTreeMap<Url, Document> {
+        &mut self.documents
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let result = match request.method.as_str() {
+            DocumentSymbolRequest::METHOD => respond(request.params, |params| {
+                features::document_symbols(self, params)
+            }),
+            FoldingRangeRequest::METHOD => respond(request.params, |params| {
+                features::folding_ranges(self, params)
+            }),
+            Completion::METHOD => {
+                respond(request.params, |params| features::completion(self, params))
+            }
+            HoverRequest::METHOD => respond(request.params, |params| features::hover(self, params)),
+            GotoDefinition::METHOD => {
+                respond(request.params, |params| features::definition(self, params))
+            }
+            References::METHOD => {
+                respond(request.params, |params| features::references(self, params))
+            }
+            method => {
+                return Response::new_err(
+                    request.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unknown method: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(value) => Response {
+                id: request.id,
+                result: Some(value),
+                error: None,
+            },
+            Err(err) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, err),
+        }
+    }
+}
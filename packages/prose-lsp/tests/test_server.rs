@@ -0,0 +1,144 @@
+extern crate prose_lsp;
+
+use lsp_common::client::Client;
+use serde_json::{json, Value};
+
+const CHAPTER1: &str =
+    "# 第一話 #alice\n%% 回想\n%% ここまで\n　#alice は%仮%言った。\n## 場面\n#bob「やあ」";
+const CHAPTER2: &str = "#alice と#bob\n# \nここ";
+
+fn start() -> Client {
+    let client = Client::start(prose_lsp::run, "prose");
+    assert_eq!(client.capabilities["definitionProvider"], json!(true));
+
+    client
+}
+
+fn ranges(locations: &Value) -> Vec<(String, u64, u64, u64)> {
+    locations
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            (
+                item["uri"].as_str().unwrap().to_string(),
+                item["range"]["start"]["line"].as_u64().unwrap(),
+                item["range"]["start"]["character"].as_u64().unwrap(),
+                item["range"]["end"]["character"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn diagnostics() {
+    let mut client = start();
+    client.open("file:///a.pd", CHAPTER1);
+    client.open("file:///b.pd", CHAPTER2);
+
+    assert_eq!(client.diagnostics("file:///a.pd"), json!([]));
+    let diagnostics = client.diagnostics("file:///b.pd");
+    assert_eq!(diagnostics[0]["code"], "syntax");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 2, "character": 0 })
+    );
+
+    // fix the empty heading
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": "file:///b.pd", "version": 2 },
+            "contentChanges": [{ "range": { "start": { "line": 1, "character": 2 }, "end": { "line": 1, "character": 2 } }, "text": "見出し" }],
+        }),
+    );
+    assert_eq!(client.diagnostics("file:///b.pd"), json!([]));
+
+    // and break the first chapter with an incremental edit
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": "file:///a.pd", "version": 2 },
+            "contentChanges": [{ "range": { "start": { "line": 4, "character": 3 }, "end": { "line": 4, "character": 5 } }, "text": "" }],
+        }),
+    );
+    assert_eq!(client.diagnostics("file:///a.pd")[0]["code"], "syntax");
+}
+
+#[test]
+fn symbols_and_folding() {
+    let mut client = start();
+    client.open("file:///a.pd", CHAPTER1);
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": "file:///a.pd" } }),
+    );
+    assert_eq!(symbols[0]["name"], "第一話");
+    assert_eq!(symbols[0]["children"][0]["name"], "場面");
+    assert_eq!(symbols[0]["children"][0]["range"]["start"]["line"], 4);
+
+    let folding = client.request(
+        "textDocument/foldingRange",
+        json!({ "textDocument": { "uri": "file:///a.pd" } }),
+    );
+    assert_eq!(
+        folding,
+        json!([{ "startLine": 1, "endLine": 2, "kind": "comment" }])
+    );
+}
+
+#[test]
+fn completion_and_hover() {
+    let mut client = start();
+    client.open("file:///a.pd", CHAPTER1);
+    client.open("file:///b.pd", "#a");
+
+    let items = client.at("textDocument/completion", "file:///b.pd", 0, 2);
+    assert_eq!(
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["a", "alice"]
+    );
+    assert_eq!(
+        client.at("textDocument/completion", "file:///a.pd", 3, 9),
+        json!([])
+    );
+
+    let hover = client.at("textDocument/hover", "file:///a.pd", 3, 10);
+    assert_eq!(hover["contents"]["value"], "6 characters");
+    assert_eq!(
+        hover["range"]["start"],
+        json!({ "line": 3, "character": 0 })
+    );
+    assert_eq!(
+        client.at("textDocument/hover", "file:///a.pd", 0, 2),
+        Value::Null
+    );
+}
+
+#[test]
+fn definition_and_references() {
+    let mut client = start();
+    client.open("file:///a.pd", CHAPTER1);
+    client.open("file:///b.pd", "#alice と#bob");
+
+    let definition = client.at("textDocument/definition", "file:///b.pd", 0, 2);
+    assert_eq!(
+        ranges(&json!([definition])),
+        vec![("file:///a.pd".to_string(), 0, 6, 12)]
+    );
+
+    let references = client.at("textDocument/references", "file:///a.pd", 5, 1);
+    assert_eq!(
+        ranges(&references),
+        vec![
+            ("file:///a.pd".to_string(), 5, 0, 4),
+            ("file:///b.pd".to_string(), 0, 8, 12),
+        ]
+    );
+}
@@ -0,0 +1,32 @@
+use crate::parser::Span;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the rule that produced the diagnostic.
+    pub code: String,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &str, message: &str, span: Span) -> Self {
+        Self {
+            severity,
+            code: code.to_string(),
+            message: message.to_string(),
+            span,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::parser::{Node, Span};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A top-level `「」` or `『』` quotation. Nested quotes are part of `text`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dialogue {
+    pub speaker: Option<String>,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DialogueStats {
+    /// Characters of prose, excluding tags and comments.
+    pub characters: usize,
+    pub dialogue_characters: usize,
+    /// `dialogue_characters / characters`, or 0 for an empty document.
+    pub ratio: f64,
+    /// Dialogue characters per speaker tag.
+    pub speakers: BTreeMap<String, usize>,
+}
+
+pub fn dialogues(document: &Node) -> Vec<Dialogue> {
+    let mut ret = Vec::new();
+    collect_dialogues(document, &mut ret);
+
+    ret
+}
+
+pub fn dialogue_stats(document: &Node) -> DialogueStats {
+    let dialogues = dialogues(document);
+    let characters = prose_text(document).chars().count();
+    let dialogue_characters = dialogues
+        .iter()
+        .map(|item| item.text.chars().count())
+        .sum::<usize>();
+    let speakers = dialogues
+        .iter()
+        .filter_map(|item| Some((item.speaker.clone()?, item.text.chars().count())))
+        .fold(BTreeMap::new(), |mut acc, (speaker, count)| {
+            *acc.entry(speaker).or_insert(0) += count;
+
+            acc
+        });
+
+    DialogueStats {
+        characters,
+        dialogue_characters,
+        ratio: match characters {
+            0 => 0.0,
+            _ => dialogue_characters as f64 / characters as f64,
+        },
+        speakers,
+    }
+}
+
+fn collect_dialogues(node: &Node, acc: &mut Vec<Dialogue>) {
+    match node {
+        Node::Parent(parent) if parent.name == "dialogue" => {
+            let speaker = parent
+                .children
+                .iter()
+                .find(|item| item.name() == Some("speaker"))
+                .map(|item| item.text());
+            let quote = parent
+                .children
+                .iter()
+                .find(|item| matches!(item.name(), Some("quote" | "double_quote")));
+
+            if let Some(quote) = quote {
+                acc.push(Dialogue {
+                    speaker,
+                    text: quote_text(quote),
+                    span: parent.span,
+                });
+            }
+        }
+        Node::Parent(parent) if parent.name == "quote" || parent.name == "double_quote" => {
+            acc.push(Dialogue {
+                speaker: None,
+                text: quote_text(node),
+                span: parent.span,
+            });
+        }
+        Node::Parent(parent) => parent
+            .children
+            .iter()
+            .for_each(|item| collect_dialogues(item, acc)),
+        _ => {}
+    }
+}
+
+/// Text inside the outermost brackets, keeping the brackets of nested quotes.
+fn quote_text(node: &Node) -> String {
+    node.children().iter().map(prose_text).collect()
+}
+
+fn prose_text(node: &Node) -> String {
+    match node {
+        Node::Parent(parent) => match parent.name.as_str() {
+            "tag" | "speaker" | "inline_comment" | "block_comment" | "heading_begin"
+            | "footnote_ref" => String::new(),
+            "quote" => format!("「{}」", quote_text(node)),
+            "double_quote" => format!("『{}』", quote_text(node)),
+            _ => parent.children.iter().map(prose_text).collect(),
+        },
+        Node::Value(value) if value.name == "text" => value.value.clone(),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,182 @@
+use crate::parser::{self, Node, Parent, Span, Token, Value};
+
+/// Marks of constructs that may run over several lines: author notes and
+/// `%` comments, which are closed by the next `%` wherever it is.
+const MARKERS: [&str; 2] = [":::", "%"];
+
+/// Replacement of `range` (byte offsets in the previous source) with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Span, text: &str) -> Self {
+        Self {
+            range,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Reparses the blocks of `previous` touched by `edit` and reuses the rest.
+/// `source` is the text after the edit. Falls back to a full parse when the
+/// edit may change how surrounding lines are read, such as front matter and
+/// comments and `:::` author notes.
+pub fn reparse(previous: &Node, source: &str, edit: &TextEdit) -> Result<Node, String> {
+    match reparse_blocks(previous, source, edit) {
+        Some(node) => Ok(node),
+        None => crate::parse(source),
+    }
+}
+
+fn reparse_blocks(previous: &Node, source: &str, edit: &TextEdit) -> Option<Node> {
+    let document = match previous {
+        Node::Parent(parent) if parent.name == "document" => parent,
+        _ => return None,
+    };
+    let children = &document.children;
+    let old_len = document.span.end;
+    if edit.range.start > edit.range.end || edit.range.end > old_len || children.is_empty() {
+        return None;
+    }
+    let new_len = old_len - (edit.range.end - edit.range.start) + edit.text.len();
+    if new_len != source.len() {
+        return None;
+    }
+
+    let is_break = |idx: usize| children[idx].name() == Some("line_break");
+    let span = |idx: usize| children[idx].span().unwrap_or_default();
+
+    // the window starts at a block right after a line break, so that text
+    // inserted at its head cannot merge into the previous line
+    let mut lo = children
+        .iter()
+        .position(|item| item.span().unwrap_or_default().end >= edit.range.start)
+        .unwrap_or(children.len() - 1);
+    while lo > 0 && (is_break(lo) || !is_break(lo - 1) || span(lo).start >= edit.range.start) {
+        lo -= 1;
+    }
+    // and ends with a line break that is followed by a block
+    let mut hi = lo;
+    while hi < children.len()
+        && !(is_break(hi)
+            && span(hi).end > edit.range.end
+            && (hi + 1 == children.len() || !is_break(hi + 1)))
+    {
+        hi += 1;
+    }
+    let to_end = hi == children.len();
+    let hi = hi.min(children.len() - 1) + 1;
+
+    if children[lo..hi].iter().any(spans_lines) {
+        return None;
+    }
+
+    let start = match lo {
+        0 => 0,
+        _ => span(lo - 1).end,
+    };
+    let old_end = match to_end {
+        true => old_len,
+        false => span(hi - 1).end,
+    };
+    let new_end = old_end + new_len - old_len;
+    let text = source.get(start..new_end)?;
+    if MARKERS.iter().any(|marker| text.contains(marker)) {
+        return None;
+    }
+
+    let window = parser::parse(text).ok()?;
+    let window = window.children();
+    if window
+        .iter()
+        .any(|item| item.name() == Some("front_matter"))
+    {
+        return None;
+    }
+    if start > 0 && window.first().and_then(|item| item.name()) == Some("line_break") {
+        return None;
+    }
+    if !to_end {
+        match window.last() {
+            Some(Node::Parent(parent))
+                if parent.name == "line_break" && parent.span.end == text.len() => {}
+            Some(Node::Token(token))
+                if token.name == "line_break" && token.span.end == text.len() => {}
+            _ => return None,
+        }
+    }
+
+    let delta = new_len as isize - old_len as isize;
+    let children: Vec<Node> = children[..lo]
+        .iter()
+        .cloned()
+        .chain(window.iter().map(|item| shift(item, start as isize)))
+        .chain(children[hi..].iter().map(|item| shift(item, delta)))
+        .collect();
+
+    let span = Span::new(document.span.start, new_len);
+    match children.is_empty() {
+        true => Some(Node::Token(Token {
+            name: document.name.clone(),
+            span,
+        })),
+        false => Some(Node::Parent(Parent {
+            name: document.name.clone(),
+            children,
+            span,
+            // the window never has front matter, so it is kept as it was
+            metadata: document.metadata.clone(),
+        })),
+    }
+}
+
+fn shift(node: &Node, offset: isize) -> Node {
+    let shift_span = |span: Span| {
+        Span::new(
+            span.start.wrapping_add_signed(offset),
+            span.end.wrapping_add_signed(offset),
+        )
+    };
+
+    match node {
+        Node::Parent(parent) => Node::Parent(Parent {
+            name: parent.name.clone(),
+            children: parent
+                .children
+                .iter()
+                .map(|item| shift(item, offset))
+                .collect(),
+            span: shift_span(parent.span),
+            metadata: parent.metadata.clone(),
+        }),
+        Node::Value(value) => Node::Value(Value {
+            span: shift_span(value.span),
+            ..value.clone()
+        }),
+        Node::Token(token) => Node::Token(Token {
+            span: shift_span(token.span),
+            ..token.clone()
+        }),
+        Node::Empty => Node::Empty,
+    }
+}
+
+/// Whether `node` is, or may have been read as, a construct over several lines.
+fn spans_lines(node: &Node) -> bool {
+    match node {
+        Node::Parent(parent)
+            if matches!(
+                parent.name.as_str(),
+                "front_matter" | "preface" | "afterword" | "inline_comment" | "block_comment"
+            ) =>
+        {
+            true
+        }
+        Node::Parent(parent) => parent.children.iter().any(spans_lines),
+        Node::Value(value) => MARKERS.iter().any(|marker| value.value.contains(marker)),
+        _ => false,
+    }
+}
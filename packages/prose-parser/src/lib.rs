@@ -1,10 +1,60 @@
 extern crate pest;
 #[macro_use]
 extern crate pest_derive;
+extern crate syntax_error;
 
+use syntax_error::SyntaxError;
+
+mod diagnostic;
+mod dialogue;
+mod incremental;
+pub mod lint;
+mod metadata;
+mod notes;
 mod parser;
-pub use parser::Node;
+mod query;
+mod sentence;
+mod tag_index;
+mod visitor;
+pub use diagnostic::{Diagnostic, Severity};
+pub use dialogue::{dialogue_stats, dialogues, Dialogue, DialogueStats};
+pub use incremental::{reparse, TextEdit};
+pub use lint::{LintConfig, Linter};
+pub use metadata::{Metadata, Status};
+pub use notes::{extract_notes, AuthorNoteMode, Footnote, FootnoteMode, Notes, NotesConfig};
+pub use parser::{Node, Parent, Span, Token, Value};
+pub use query::{Query, QueryMatch};
+pub use tag_index::{TagCoOccurrence, TagIndex, TagOccurrence};
+pub use visitor::{Fold, Selector, Visitor, VisitorMut};
 
 pub fn parse(document: &str) -> Result<parser::Node, String> {
-    parser::parse(document)
+    metadata::read_front_matter(parser::parse(document)?)
+        .map_err(|(err, _)| format!("front matter: {}", err))
+}
+
+/// Like `parse`, but reports a syntax or front matter error as a `Diagnostic`
+/// with its span.
+pub fn check(document: &str) -> Result<Node, Diagnostic> {
+    let node = parser::parse_document(document).map_err(|err| {
+        let err = SyntaxError::from(&*err);
+
+        Diagnostic::new(
+            Severity::Error,
+            "syntax",
+            &err.message,
+            Span::new(err.start, err.end),
+        )
+    })?;
+    metadata::read_front_matter(node)
+        .map_err(|(err, span)| Diagnostic::new(Severity::Error, "front-matter", &err, span))
+}
+
+/// Builds a tag index over `(file, source)` pairs of a project.
+pub fn index_tags(documents: &[(&str, &str)]) -> Result<TagIndex, String> {
+    TagIndex::build(documents)
+}
+
+/// Parses `document` and runs every built-in lint rule over it.
+pub fn lint(document: &str, config: &LintConfig) -> Result<Vec<Diagnostic>, String> {
+    Ok(Linter::new(config.clone()).lint(document, &parse(document)?))
 }
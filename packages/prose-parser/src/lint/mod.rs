@@ -0,0 +1,252 @@
+mod rules;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::parser::{Node, Span};
+use crate::sentence::{self, SENTENCE_TERMINATORS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub use rules::{
+    BracketPairs, DashEllipsisPairs, LongSentence, MixedPunctuation, ParagraphIndent,
+    RepeatedEndings, UnknownTag, UnusedFootnote,
+};
+
+/// A lint rule. Rules only report spans and messages; the severity comes from
+/// `LintConfig` or falls back to `default_severity`.
+pub trait Rule {
+    fn name(&self) -> &'static str;
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleLevel {
+    Off,
+    Hint,
+    Information,
+    Warning,
+    Error,
+}
+
+/// Per-project lint settings, usually read from a YAML file.
+///
+/// ```yaml
+/// rules:
+///   paragraph-indent: off
+///   long-sentence: error
+/// maxSentenceLength: 80
+/// knownTags: [alice, bob]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LintConfig {
+    pub rules: BTreeMap<String, RuleLevel>,
+    pub max_sentence_length: usize,
+    pub repeated_endings: usize,
+    /// Tags allowed by `unknown-tag`. The rule is skipped when unset.
+    pub known_tags: Option<Vec<String>>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            rules: BTreeMap::new(),
+            max_sentence_length: 100,
+            repeated_endings: 3,
+            known_tags: None,
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn from_yaml(text: &str) -> Result<LintConfig, String> {
+        serde_yaml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    fn severity(&self, rule: &dyn Rule) -> Option<Severity> {
+        match self.rules.get(rule.name()) {
+            None => Some(rule.default_severity()),
+            Some(RuleLevel::Off) => None,
+            Some(RuleLevel::Hint) => Some(Severity::Hint),
+            Some(RuleLevel::Information) => Some(Severity::Information),
+            Some(RuleLevel::Warning) => Some(Severity::Warning),
+            Some(RuleLevel::Error) => Some(Severity::Error),
+        }
+    }
+}
+
+pub struct LintContext<'a> {
+    pub source: &'a str,
+    pub document: &'a Node,
+    pub config: &'a LintConfig,
+}
+
+impl LintContext<'_> {
+    /// Prose of every paragraph, heading and footnote definition, including
+    /// those inside a preface or an afterword, without markup.
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut ret = Vec::new();
+        collect_blocks(self.source, self.document.children(), &mut ret);
+
+        ret
+    }
+}
+
+fn collect_blocks(source: &str, nodes: &[Node], acc: &mut Vec<Block>) {
+    nodes.iter().for_each(|node| match node {
+        Node::Parent(parent) if parent.name == "preface" || parent.name == "afterword" => {
+            collect_blocks(source, &parent.children, acc)
+        }
+        Node::Parent(parent)
+            if parent.name == "paragraph"
+                || parent.name == "heading"
+                || parent.name == "footnote_def" =>
+        {
+            let mut chars = Vec::new();
+            collect_prose(source, node, &mut chars);
+
+            acc.push(Block {
+                name: parent.name.clone(),
+                span: parent.span,
+                chars,
+            });
+        }
+        _ => {}
+    })
+}
+
+/// Characters of a block with their byte offsets in the source.
+pub struct Block {
+    pub name: String,
+    pub span: Span,
+    pub chars: Vec<(usize, char)>,
+}
+
+impl Block {
+    pub fn sentences(&self) -> Vec<Sentence> {
+        sentence::split(&self.chars)
+            .into_iter()
+            .map(|chars| Sentence { chars })
+            .collect()
+    }
+}
+
+pub struct Sentence {
+    pub chars: Vec<(usize, char)>,
+}
+
+impl Sentence {
+    pub fn span(&self) -> Span {
+        match (self.chars.first(), self.chars.last()) {
+            (Some((start, _)), Some((end, ch))) => Span::new(*start, end + ch.len_utf8()),
+            _ => Span::default(),
+        }
+    }
+
+    /// The last character before the terminator, ignoring closing brackets.
+    pub fn ending(&self) -> Option<char> {
+        self.chars.iter().rev().map(|(_, ch)| *ch).find(|ch| {
+            !SENTENCE_TERMINATORS.contains(ch) && !"」』）)".contains(*ch) && !ch.is_whitespace()
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+}
+
+fn collect_prose(source: &str, node: &Node, acc: &mut Vec<(usize, char)>) {
+    match node {
+        Node::Parent(parent) => match parent.name.as_str() {
+            "tag" | "speaker" | "inline_comment" | "block_comment" | "heading_begin"
+            | "footnote_ref" => {}
+            "quote" | "double_quote" => {
+                let text = &source[parent.span.start..parent.span.end];
+                let (open, close) = (text.chars().next(), text.chars().last());
+
+                if let Some(open) = open {
+                    acc.push((parent.span.start, open));
+                }
+                parent
+                    .children
+                    .iter()
+                    .for_each(|item| collect_prose(source, item, acc));
+                if let Some(close) = close {
+                    acc.push((parent.span.end - close.len_utf8(), close));
+                }
+            }
+            _ => parent
+                .children
+                .iter()
+                .for_each(|item| collect_prose(source, item, acc)),
+        },
+        Node::Value(value) if value.name == "text" => {
+            source[value.span.start..value.span.end]
+                .char_indices()
+                .for_each(|(idx, ch)| acc.push((value.span.start + idx, ch)));
+        }
+        _ => {}
+    }
+}
+
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    config: LintConfig,
+}
+
+impl Linter {
+    /// A linter with every built-in rule.
+    pub fn new(config: LintConfig) -> Self {
+        Self {
+            rules: vec![
+                Box::new(MixedPunctuation),
+                Box::new(DashEllipsisPairs),
+                Box::new(ParagraphIndent),
+                Box::new(BracketPairs),
+                Box::new(LongSentence),
+                Box::new(RepeatedEndings),
+                Box::new(UnknownTag),
+                Box::new(UnusedFootnote),
+            ],
+            config,
+        }
+    }
+
+    pub fn rule<R: Rule + 'static>(mut self, rule: R) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn lint(&self, source: &str, document: &Node) -> Vec<Diagnostic> {
+        let context = LintContext {
+            source,
+            document,
+            config: &self.config,
+        };
+
+        let mut ret = self
+            .rules
+            .iter()
+            .filter_map(|rule| Some((rule, self.config.severity(rule.as_ref())?)))
+            .flat_map(|(rule, severity)| {
+                rule.check(&context)
+                    .into_iter()
+                    .map(move |(span, message)| {
+                        Diagnostic::new(severity, rule.name(), &message, span)
+                    })
+            })
+            .collect::<Vec<_>>();
+        ret.sort_by_key(|item| (item.span.start, item.span.end));
+
+        ret
+    }
+}
@@ -0,0 +1,324 @@
+use super::{LintContext, Rule, Sentence};
+use crate::notes::{collect_references, footnote_label};
+use crate::parser::Span;
+use crate::tag_index::collect_tags;
+
+const PUNCTUATION_PAIRS: [(char, char); 8] = [
+    ('！', '!'),
+    ('？', '?'),
+    ('：', ':'),
+    ('；', ';'),
+    ('（', '('),
+    ('）', ')'),
+    ('，', ','),
+    ('．', '.'),
+];
+const BRACKET_PAIRS: [(char, char); 4] = [('「', '」'), ('『', '』'), ('（', '）'), ('【', '】')];
+const PAIRED_MARKS: [char; 2] = ['…', '―'];
+const INDENT_EXEMPT: &str = "　「『（【〈《“#%";
+
+fn char_span(offset: usize, ch: char) -> Span {
+    Span::new(offset, offset + ch.len_utf8())
+}
+
+/// Full-width and half-width forms of the same punctuation in one document.
+/// The less frequent form is reported.
+pub struct MixedPunctuation;
+
+impl Rule for MixedPunctuation {
+    fn name(&self) -> &'static str {
+        "mixed-punctuation"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        let chars = context
+            .blocks()
+            .into_iter()
+            .flat_map(|block| {
+                let chars = block.chars;
+                // decimal points and digit separators are not punctuation
+                (0..chars.len())
+                    .filter(|idx| {
+                        let is_digit = |idx: Option<&(usize, char)>| {
+                            idx.map(|(_, ch)| ch.is_ascii_digit()).unwrap_or(false)
+                        };
+                        !(matches!(chars[*idx].1, '.' | ',')
+                            && is_digit(idx.checked_sub(1).and_then(|idx| chars.get(idx)))
+                            && is_digit(chars.get(idx + 1)))
+                    })
+                    .map(|idx| chars[idx])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        PUNCTUATION_PAIRS
+            .iter()
+            .flat_map(|(full, half)| {
+                let count = |target: char| chars.iter().filter(|(_, ch)| *ch == target).count();
+                let (reported, other) = match (count(*full), count(*half)) {
+                    (0, _) | (_, 0) => return Vec::new(),
+                    (full_count, half_count) if full_count < half_count => (*full, *half),
+                    _ => (*half, *full),
+                };
+
+                chars
+                    .iter()
+                    .filter(|(_, ch)| *ch == reported)
+                    .map(|(offset, ch)| {
+                        (
+                            char_span(*offset, *ch),
+                            format!("`{}` is mixed with `{}`", reported, other),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// `…` and `―` are written in pairs, as in `……` and `――`.
+pub struct DashEllipsisPairs;
+
+impl Rule for DashEllipsisPairs {
+    fn name(&self) -> &'static str {
+        "dash-ellipsis-pairs"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        context
+            .blocks()
+            .iter()
+            .flat_map(|block| {
+                let mut runs: Vec<(usize, char, usize)> = Vec::new();
+                let mut prev_end = None;
+                block.chars.iter().for_each(|(offset, ch)| {
+                    if !PAIRED_MARKS.contains(ch) {
+                        prev_end = None;
+                        return;
+                    }
+                    match runs.last_mut() {
+                        Some(run) if run.1 == *ch && prev_end == Some(*offset) => run.2 += 1,
+                        _ => runs.push((*offset, *ch, 1)),
+                    }
+                    prev_end = Some(offset + ch.len_utf8());
+                });
+
+                runs.into_iter()
+                    .filter(|(_, _, count)| count % 2 == 1)
+                    .map(|(offset, ch, count)| {
+                        (
+                            Span::new(offset, offset + ch.len_utf8() * count),
+                            format!("`{}` is used {} time(s); use it in pairs", ch, count),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Paragraphs start with a full-width space unless they open with a bracket.
+pub struct ParagraphIndent;
+
+impl Rule for ParagraphIndent {
+    fn name(&self) -> &'static str {
+        "paragraph-indent"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        context
+            .blocks()
+            .iter()
+            .filter(|block| block.name == "paragraph")
+            .filter_map(|block| {
+                let ch = context.source[block.span.start..].chars().next()?;
+
+                match INDENT_EXEMPT.contains(ch) {
+                    true => None,
+                    false => Some((
+                        char_span(block.span.start, ch),
+                        "paragraph does not start with a full-width space".to_string(),
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Unclosed, unopened or crossed `「」`, `『』`, `（）` and `【】`.
+pub struct BracketPairs;
+
+impl Rule for BracketPairs {
+    fn name(&self) -> &'static str {
+        "bracket-pairs"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        context
+            .blocks()
+            .iter()
+            .flat_map(|block| {
+                let mut ret = Vec::new();
+                let mut stack: Vec<(usize, char)> = Vec::new();
+
+                block.chars.iter().for_each(|(offset, ch)| {
+                    if BRACKET_PAIRS.iter().any(|(open, _)| open == ch) {
+                        stack.push((*offset, *ch));
+                        return;
+                    }
+                    let open = match BRACKET_PAIRS.iter().find(|(_, close)| close == ch) {
+                        Some((open, _)) => *open,
+                        None => return,
+                    };
+                    match stack.last() {
+                        Some((_, top)) if *top == open => {
+                            stack.pop();
+                        }
+                        // the opener stays on the stack for its own closer
+                        Some((_, top)) => ret.push((
+                            char_span(*offset, *ch),
+                            format!("`{}` closes `{}`", ch, top),
+                        )),
+                        None => ret.push((
+                            char_span(*offset, *ch),
+                            format!("`{}` has no matching `{}`", ch, open),
+                        )),
+                    }
+                });
+                stack.into_iter().for_each(|(offset, ch)| {
+                    ret.push((char_span(offset, ch), format!("`{}` is not closed", ch)))
+                });
+
+                ret
+            })
+            .collect()
+    }
+}
+
+/// Sentences longer than `LintConfig::max_sentence_length` characters.
+pub struct LongSentence;
+
+impl Rule for LongSentence {
+    fn name(&self) -> &'static str {
+        "long-sentence"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        let max = context.config.max_sentence_length;
+
+        context
+            .blocks()
+            .iter()
+            .flat_map(|block| block.sentences())
+            .filter(|sentence| sentence.len() > max)
+            .map(|sentence| {
+                (
+                    sentence.span(),
+                    format!(
+                        "sentence is {} characters long (max {})",
+                        sentence.len(),
+                        max
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+/// `LintConfig::repeated_endings` consecutive sentences ending the same way,
+/// such as `〜た。〜た。〜た。`.
+pub struct RepeatedEndings;
+
+impl Rule for RepeatedEndings {
+    fn name(&self) -> &'static str {
+        "repeated-endings"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        let limit = context.config.repeated_endings.max(2);
+        let sentences = context
+            .blocks()
+            .iter()
+            .filter(|block| block.name == "paragraph")
+            .flat_map(|block| block.sentences())
+            .collect::<Vec<Sentence>>();
+
+        let mut ret = Vec::new();
+        let mut run_start = 0;
+        (0..sentences.len()).for_each(|idx| {
+            let ending = sentences[idx].ending();
+            if idx > 0 && (ending.is_none() || ending != sentences[idx - 1].ending()) {
+                run_start = idx;
+            }
+            if ending.is_some() && idx + 1 - run_start == limit {
+                ret.push((
+                    Span::new(sentences[run_start].span().start, sentences[idx].span().end),
+                    format!(
+                        "{} sentences in a row end with `{}`",
+                        limit,
+                        ending.unwrap_or_default()
+                    ),
+                ));
+            }
+        });
+
+        ret
+    }
+}
+
+/// Tags that are not listed in `LintConfig::known_tags`.
+pub struct UnknownTag;
+
+impl Rule for UnknownTag {
+    fn name(&self) -> &'static str {
+        "unknown-tag"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        let known_tags = match &context.config.known_tags {
+            Some(tags) => tags,
+            None => return Vec::new(),
+        };
+        let mut tags = Vec::new();
+        collect_tags(context.document, &mut tags);
+
+        tags.into_iter()
+            .filter(|(name, _)| !known_tags.contains(name))
+            .map(|(name, span)| (span, format!("unknown tag `#{}`", name)))
+            .collect()
+    }
+}
+
+/// Footnote definitions nothing refers to, which exports leave out.
+pub struct UnusedFootnote;
+
+impl Rule for UnusedFootnote {
+    fn name(&self) -> &'static str {
+        "unused-footnote"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        let (definitions, body): (Vec<_>, Vec<_>) = context
+            .document
+            .children()
+            .iter()
+            .partition(|item| item.name() == Some("footnote_def"));
+        let mut references = Vec::new();
+        body.iter()
+            .for_each(|item| collect_references(item, &mut references));
+
+        definitions
+            .into_iter()
+            .filter_map(|item| {
+                let label = footnote_label(item)?;
+                match references.contains(&label) {
+                    true => None,
+                    false => Some((
+                        item.span().unwrap_or_default(),
+                        format!("footnote `[^{}]` is never referenced", label),
+                    )),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,78 @@
+use crate::parser::{Node, Span};
+use serde::{Deserialize, Serialize};
+
+/// Front matter of a document.
+///
+/// ```text
+/// ---
+/// title: 出会い
+/// episode: 1
+/// status: draft
+/// pov: alice
+/// publishDate: 2022-01-01
+/// ---
+/// ```
+///
+/// TOML front matter is fenced with `+++` instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub episode: Option<u32>,
+    pub status: Option<Status>,
+    pub pov: Option<String>,
+    pub publish_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Draft,
+    Published,
+}
+
+impl Metadata {
+    pub fn from_yaml(text: &str) -> Result<Metadata, String> {
+        match text.trim().is_empty() {
+            true => Ok(Metadata::default()),
+            false => serde_yaml::from_str(text).map_err(|err| err.to_string()),
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Metadata, String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+}
+
+impl Node {
+    /// The front matter of a `document` node, if it has one.
+    pub fn metadata(&self) -> Option<&Metadata> {
+        match self {
+            Node::Parent(parent) => parent.metadata.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the front matter of `document` and keeps it on the node. Errors
+/// come with the span of the front matter.
+pub(crate) fn read_front_matter(document: Node) -> Result<Node, (String, Span)> {
+    let mut document = match document {
+        Node::Parent(parent) => parent,
+        node => return Ok(node),
+    };
+    let front_matter = match document.children.first() {
+        Some(node) if node.name() == Some("front_matter") => node,
+        _ => return Ok(Node::Parent(document)),
+    };
+
+    let metadata = match front_matter.children().first() {
+        Some(Node::Value(value)) if value.name == "toml" => Metadata::from_toml(&value.value),
+        Some(Node::Value(value)) => Metadata::from_yaml(&value.value),
+        _ => Ok(Metadata::default()),
+    }
+    .map_err(|err| (err, front_matter.span().unwrap_or_default()))?;
+    document.metadata = Some(metadata);
+
+    Ok(Node::Parent(document))
+}
@@ -0,0 +1,198 @@
+use crate::parser::{Node, Parent, Span};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FootnoteMode {
+    /// Collect footnote definitions into an `endnotes` node at the end.
+    /// Definitions nothing refers to are left out, which the
+    /// `unused-footnote` lint reports.
+    Endnotes,
+    /// Drop footnote references and definitions.
+    Omit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthorNoteMode {
+    /// Leave `preface` and `afterword` in the body.
+    Inline,
+    /// Move them out of the body into `Notes`, for sites with 前書き/後書き fields.
+    Fields,
+    Omit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotesConfig {
+    pub footnotes: FootnoteMode,
+    pub author_notes: AuthorNoteMode,
+}
+
+impl Default for NotesConfig {
+    fn default() -> Self {
+        Self {
+            footnotes: FootnoteMode::Endnotes,
+            author_notes: AuthorNoteMode::Fields,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notes {
+    pub preface: Option<Node>,
+    pub afterword: Option<Node>,
+    /// Definitions in the order they are first referenced.
+    pub footnotes: Vec<Footnote>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Footnote {
+    pub label: String,
+    /// 1-based position in `Notes::footnotes`.
+    pub number: usize,
+    /// The `footnote_def` node without its label.
+    pub definition: Node,
+}
+
+/// Splits footnotes and author notes out of a `document` node according to
+/// `config`. Returns the rewritten document and the extracted notes.
+pub fn extract_notes(document: &Node, config: &NotesConfig) -> (Node, Notes) {
+    let mut notes = Notes::default();
+    let mut definitions = Vec::new();
+    let mut children: Vec<Node> = Vec::new();
+    let mut removed = false;
+
+    document.children().iter().for_each(|item| {
+        // the line break after a removed block goes with it
+        if removed && item.name() == Some("line_break") {
+            removed = false;
+            return;
+        }
+        removed = match (item.name(), config.author_notes) {
+            (Some("footnote_def"), _) => {
+                definitions.push(item.clone());
+                true
+            }
+            (Some("preface"), AuthorNoteMode::Fields) => {
+                notes.preface = Some(item.clone());
+                true
+            }
+            (Some("afterword"), AuthorNoteMode::Fields) => {
+                notes.afterword = Some(item.clone());
+                true
+            }
+            (Some("preface" | "afterword"), AuthorNoteMode::Omit) => true,
+            _ => false,
+        };
+        if !removed {
+            children.push(item.clone());
+        }
+    });
+
+    let mut references = Vec::new();
+    children
+        .iter()
+        .for_each(|item| collect_references(item, &mut references));
+    notes.footnotes = references
+        .iter()
+        .filter_map(|label| {
+            definitions
+                .iter()
+                .find(|item| footnote_label(item).as_deref() == Some(label.as_str()))
+                .map(|definition| (label, definition))
+        })
+        .enumerate()
+        .map(|(idx, (label, definition))| Footnote {
+            label: label.clone(),
+            number: idx + 1,
+            definition: without_label(definition),
+        })
+        .collect();
+
+    let span = document.span().unwrap_or_default();
+    let children = match config.footnotes {
+        FootnoteMode::Endnotes if !notes.footnotes.is_empty() => {
+            let mut children = children;
+            children.push(Node::Parent(Parent {
+                name: "endnotes".to_string(),
+                children: notes
+                    .footnotes
+                    .iter()
+                    .map(|item| item.definition.clone())
+                    .collect(),
+                span: Span::new(span.end, span.end),
+                metadata: None,
+            }));
+
+            children
+        }
+        FootnoteMode::Endnotes => children,
+        FootnoteMode::Omit => children.iter().filter_map(remove_references).collect(),
+    };
+
+    let document = match document {
+        Node::Parent(parent) => Node::Parent(Parent {
+            children,
+            ..parent.clone()
+        }),
+        _ => document.clone(),
+    };
+
+    (document, notes)
+}
+
+pub(crate) fn footnote_label(node: &Node) -> Option<String> {
+    node.children()
+        .iter()
+        .find(|item| item.name() == Some("footnote_label"))
+        .map(|item| item.text())
+}
+
+fn without_label(node: &Node) -> Node {
+    match node {
+        Node::Parent(parent) => Node::Parent(Parent {
+            children: parent
+                .children
+                .iter()
+                .filter(|item| item.name() != Some("footnote_label"))
+                .cloned()
+                .collect(),
+            ..parent.clone()
+        }),
+        _ => node.clone(),
+    }
+}
+
+pub(crate) fn collect_references(node: &Node, acc: &mut Vec<String>) {
+    match node.name() {
+        Some("footnote_ref") => {
+            if let Some(label) = footnote_label(node) {
+                if !acc.contains(&label) {
+                    acc.push(label);
+                }
+            }
+        }
+        _ => node
+            .children()
+            .iter()
+            .for_each(|item| collect_references(item, acc)),
+    }
+}
+
+fn remove_references(node: &Node) -> Option<Node> {
+    match node {
+        _ if node.name() == Some("footnote_ref") => None,
+        Node::Parent(parent) => Some(Node::Parent(Parent {
+            children: parent
+                .children
+                .iter()
+                .filter_map(remove_references)
+                .collect(),
+            ..parent.clone()
+        })),
+        _ => Some(node.clone()),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::metadata::Metadata;
 use pest::iterators::Pair;
 use pest::Parser;
 use serde::{Deserialize, Serialize};
@@ -7,13 +8,17 @@ use serde::{Deserialize, Serialize};
 pub struct DocumentParser;
 
 pub fn parse(document: &str) -> Result<Node, String> {
+    parse_document(document).map_err(|err| err.to_string())
+}
+
+pub fn parse_document(document: &str) -> Result<Node, Box<pest::error::Error<Rule>>> {
     DocumentParser::parse(Rule::document, document)
         .map(|pairs| {
             let mut nodes = pairs.map(|item| Node::from(&item)).collect::<Vec<Node>>();
 
             nodes.pop().unwrap().clone()
         })
-        .map_err(|err| err.to_string())
+        .map_err(Box::new)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -34,6 +39,63 @@ impl Node {
     pub fn to_yaml(&self) -> Result<String, String> {
         serde_yaml::to_string(self).map_err(|err| err.to_string())
     }
+
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Node::Parent(parent) => Some(parent.name.as_str()),
+            Node::Value(value) => Some(value.name.as_str()),
+            Node::Token(token) => Some(token.name.as_str()),
+            Node::Empty => None,
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Node::Parent(parent) => Some(parent.span),
+            Node::Value(value) => Some(value.span),
+            Node::Token(token) => Some(token.span),
+            Node::Empty => None,
+        }
+    }
+
+    pub fn children(&self) -> &[Node] {
+        match self {
+            Node::Parent(parent) => parent.children.as_slice(),
+            _ => &[],
+        }
+    }
+
+    /// Concatenated `text` values of the node and its descendants.
+    pub fn text(&self) -> String {
+        match self {
+            Node::Parent(parent) => parent.children.iter().map(|item| item.text()).collect(),
+            Node::Value(value) => value.value.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Byte range of a node in the parsed source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        Span::new(span.start(), span.end())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -41,6 +103,11 @@ impl Node {
 pub struct Parent {
     pub name: String,
     pub children: Vec<Node>,
+    #[serde(skip)]
+    pub span: Span,
+    /// The front matter of a `document`, read once when it is parsed.
+    #[serde(skip)]
+    pub metadata: Option<Metadata>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -48,12 +115,16 @@ pub struct Parent {
 pub struct Value {
     pub name: String,
     pub value: String,
+    #[serde(skip)]
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Token {
     pub name: String,
+    #[serde(skip)]
+    pub span: Span,
 }
 
 impl From<&Pair<'_, Rule>> for Node {
@@ -67,14 +138,27 @@ pub fn to_node(pair: &Pair<'_, Rule>) -> Node {
         Rule::text => Node::Value(Value {
             name: "text".to_string(),
             value: pair.as_str().to_string(),
+            span: pair.as_span().into(),
         }),
         Rule::slug => Node::Value(Value {
             name: "text".to_string(),
             value: pair.as_str().to_string(),
+            span: pair.as_span().into(),
         }),
         Rule::ch => Node::Value(Value {
             name: "text".to_string(),
             value: pair.as_str().to_string(),
+            span: pair.as_span().into(),
+        }),
+        Rule::yaml => Node::Value(Value {
+            name: "yaml".to_string(),
+            value: pair.as_str().to_string(),
+            span: pair.as_span().into(),
+        }),
+        Rule::toml => Node::Value(Value {
+            name: "toml".to_string(),
+            value: pair.as_str().to_string(),
+            span: pair.as_span().into(),
         }),
         Rule::EOI => Node::Empty,
         _ => {
@@ -90,6 +174,7 @@ pub fn to_node(pair: &Pair<'_, Rule>) -> Node {
                             acc.push(Node::Value(Value {
                                 name: "text".to_string(),
                                 value: left.value.clone() + right.value.as_str(),
+                                span: Span::new(left.span.start, right.span.end),
                             }));
                         }
                         (Some(left), right) => {
@@ -107,10 +192,13 @@ pub fn to_node(pair: &Pair<'_, Rule>) -> Node {
             match child_nodes.is_empty() {
                 true => Node::Token(Token {
                     name: format!("{:?}", rule),
+                    span: pair.as_span().into(),
                 }),
                 false => Node::Parent(Parent {
                     name: format!("{:?}", rule),
                     children: child_nodes,
+                    span: pair.as_span().into(),
+                    metadata: None,
                 }),
             }
         }
@@ -0,0 +1,246 @@
+use crate::parser::{Node, Span};
+use crate::visitor::Selector;
+use pest::iterators::Pair;
+use pest::Parser;
+use serde::Serialize;
+use std::str::FromStr;
+
+#[derive(Parser)]
+#[grammar = "query.pest"]
+struct QueryParser;
+
+/// A CSS-like selector over a prose tree.
+///
+/// - `heading`, `*`: nodes by name
+/// - `tag[value=alice]`, `text[value="a b"]`: nodes whose `text()` equals the value
+/// - `paragraph:has(inline_comment)`, `paragraph:has(> tag)`, `tag:not(heading tag)`
+/// - `heading tag`, `heading > tag`, `block_comment + paragraph`, `block_comment ~ paragraph`
+/// - `heading, paragraph`
+///
+/// `+` and `~` skip `line_break` nodes, as CSS skips whitespace between elements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    selectors: Vec<Complex>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryMatch<'a> {
+    pub node: &'a Node,
+    pub span: Span,
+}
+
+/// Compounds from left to right. The combinator of each relates it to the
+/// previous compound, or for the first one, to the `:has()` subject.
+type Complex = Vec<(Combinator, Compound)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+    Adjacent,
+    Sibling,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Compound {
+    selector: Selector,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Has(Vec<Complex>),
+    Not(Vec<Complex>),
+}
+
+impl Query {
+    pub fn parse(source: &str) -> Result<Query, String> {
+        let pair = QueryParser::parse(Rule::query, source)
+            .map_err(|err| err.to_string())?
+            .next()
+            .and_then(|pair| pair.into_inner().next())
+            .ok_or_else(|| "empty query".to_string())?;
+
+        Ok(Query {
+            selectors: selector_list(pair),
+        })
+    }
+
+    /// Matching nodes of `node` and its descendants in document order.
+    pub fn select<'a>(&self, node: &'a Node) -> Vec<QueryMatch<'a>> {
+        let tree = Tree::new(node);
+
+        (0..tree.entries.len())
+            .filter(|idx| {
+                self.selectors
+                    .iter()
+                    .any(|complex| tree.matches(*idx, complex, None))
+            })
+            .map(|idx| {
+                let node = tree.entries[idx].node;
+
+                QueryMatch {
+                    node,
+                    span: node.span().unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Query::parse(source)
+    }
+}
+
+impl Node {
+    /// Nodes matching the `selector` query. See `Query`.
+    pub fn query(&self, selector: &str) -> Result<Vec<QueryMatch<'_>>, String> {
+        Ok(Query::parse(selector)?.select(self))
+    }
+}
+
+fn selector_list(pair: Pair<'_, Rule>) -> Vec<Complex> {
+    pair.into_inner().map(complex).collect()
+}
+
+fn complex(pair: Pair<'_, Rule>) -> Complex {
+    let mut combinator = Combinator::Descendant;
+
+    pair.into_inner().fold(Vec::new(), |mut acc, item| {
+        match item.as_rule() {
+            Rule::compound => acc.push((combinator, compound(item))),
+            Rule::complex => acc.extend(complex(item)),
+            Rule::child => combinator = Combinator::Child,
+            Rule::adjacent => combinator = Combinator::Adjacent,
+            Rule::sibling => combinator = Combinator::Sibling,
+            _ => combinator = Combinator::Descendant,
+        }
+
+        acc
+    })
+}
+
+fn compound(pair: Pair<'_, Rule>) -> Compound {
+    pair.into_inner()
+        .fold(Compound::default(), |mut acc, item| {
+            match item.as_rule() {
+                Rule::ident => acc.selector = Selector::name(item.as_str()),
+                Rule::value => {
+                    let value = item
+                        .into_inner()
+                        .next()
+                        .map(|item| item.as_str())
+                        .unwrap_or_default();
+                    acc.selector = std::mem::take(&mut acc.selector).value(value);
+                }
+                Rule::has => acc
+                    .filters
+                    .push(Filter::Has(item.into_inner().map(complex).collect())),
+                Rule::not => acc.filters.push(Filter::Not(
+                    item.into_inner().flat_map(selector_list).collect(),
+                )),
+                _ => {}
+            }
+
+            acc
+        })
+}
+
+/// Nodes in document order with their parent and previous sibling.
+struct Tree<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+struct Entry<'a> {
+    node: &'a Node,
+    parent: Option<usize>,
+    /// Previous sibling, not counting line breaks.
+    prev: Option<usize>,
+}
+
+impl<'a> Tree<'a> {
+    fn new(node: &'a Node) -> Self {
+        let mut tree = Tree {
+            entries: Vec::new(),
+        };
+        tree.add(node, None);
+
+        tree
+    }
+
+    fn add(&mut self, node: &'a Node, parent: Option<usize>) {
+        let idx = self.entries.len();
+        self.entries.push(Entry {
+            node,
+            parent,
+            prev: None,
+        });
+
+        let mut prev = None;
+        node.children()
+            .iter()
+            .filter(|item| **item != Node::Empty)
+            .for_each(|item| {
+                let child = self.entries.len();
+                self.add(item, Some(idx));
+                self.entries[child].prev = prev;
+                if item.name() != Some("line_break") {
+                    prev = Some(child);
+                }
+            });
+    }
+
+    /// Entries the combinator may relate `idx` to, nearest first.
+    fn related(&self, idx: usize, combinator: Combinator) -> Vec<usize> {
+        let step = |idx: &usize| match combinator {
+            Combinator::Descendant | Combinator::Child => self.entries[*idx].parent,
+            Combinator::Adjacent | Combinator::Sibling => self.entries[*idx].prev,
+        };
+        let ret = std::iter::successors(step(&idx), step);
+
+        match combinator {
+            Combinator::Child | Combinator::Adjacent => ret.take(1).collect(),
+            Combinator::Descendant | Combinator::Sibling => ret.collect(),
+        }
+    }
+
+    fn matches(
+        &self,
+        idx: usize,
+        complex: &[(Combinator, Compound)],
+        anchor: Option<usize>,
+    ) -> bool {
+        let ((combinator, compound), rest) = match complex.split_last() {
+            Some(item) => item,
+            None => return false,
+        };
+        if !self.matches_compound(idx, compound) {
+            return false;
+        }
+        let related = self.related(idx, *combinator);
+
+        match rest.is_empty() {
+            true => anchor
+                .map(|anchor| related.contains(&anchor))
+                .unwrap_or(true),
+            false => related.iter().any(|item| self.matches(*item, rest, anchor)),
+        }
+    }
+
+    fn matches_compound(&self, idx: usize, compound: &Compound) -> bool {
+        compound.selector.matches(self.entries[idx].node)
+            && compound.filters.iter().all(|filter| match filter {
+                // descendants and following siblings come after `idx`
+                Filter::Has(list) => (idx + 1..self.entries.len()).any(|item| {
+                    list.iter()
+                        .any(|complex| self.matches(item, complex, Some(idx)))
+                }),
+                Filter::Not(list) => !list.iter().any(|complex| self.matches(idx, complex, None)),
+            })
+    }
+}
@@ -0,0 +1,47 @@
+use crate::parser::Span;
+
+pub(crate) const SENTENCE_TERMINATORS: [char; 5] = ['。', '！', '？', '!', '?'];
+
+/// Splits characters with their offsets into sentences, each one ending with
+/// its terminator. Whitespace before a sentence is dropped.
+pub(crate) fn split(chars: &[(usize, char)]) -> Vec<Vec<(usize, char)>> {
+    let mut ret = Vec::new();
+    let mut current: Vec<(usize, char)> = Vec::new();
+
+    chars.iter().for_each(|item| {
+        if current.is_empty() && item.1.is_whitespace() {
+            return;
+        }
+        current.push(*item);
+        if SENTENCE_TERMINATORS.contains(&item.1) {
+            ret.push(std::mem::take(&mut current));
+        }
+    });
+    if !current.is_empty() {
+        ret.push(current);
+    }
+
+    ret
+}
+
+/// The sentence of `source` around `span`. Line breaks end sentences too.
+pub(crate) fn sentence_at(source: &str, span: &Span) -> String {
+    let is_boundary = |ch: char| ch == '\n' || ch == '\r' || SENTENCE_TERMINATORS.contains(&ch);
+
+    let start = source[..span.start]
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| is_boundary(*ch))
+        .map(|(idx, ch)| idx + ch.len_utf8())
+        .unwrap_or(0);
+    let end = source[span.end..]
+        .char_indices()
+        .find(|(_, ch)| is_boundary(*ch))
+        .map(|(idx, ch)| match SENTENCE_TERMINATORS.contains(&ch) {
+            true => span.end + idx + ch.len_utf8(),
+            false => span.end + idx,
+        })
+        .unwrap_or(source.len());
+
+    source[start..end].trim().to_string()
+}
@@ -0,0 +1,135 @@
+use crate::parser::{Node, Span};
+use crate::sentence::sentence_at;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagIndex {
+    pub tags: BTreeMap<String, Vec<TagOccurrence>>,
+    pub co_occurrences: Vec<TagCoOccurrence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagOccurrence {
+    pub file: String,
+    pub span: Span,
+    pub sentence: String,
+}
+
+/// Number of blocks in which both tags appear. `tags` is sorted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCoOccurrence {
+    pub tags: (String, String),
+    pub count: usize,
+}
+
+impl TagIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses every `(file, source)` pair and indexes the tags found in them.
+    pub fn build(documents: &[(&str, &str)]) -> Result<TagIndex, String> {
+        documents
+            .iter()
+            .try_fold(TagIndex::new(), |mut acc, (file, source)| {
+                let document = crate::parse(source).map_err(|err| format!("{}: {}", file, err))?;
+                acc.add_document(file, source, &document);
+
+                Ok(acc)
+            })
+    }
+
+    pub fn add_document(&mut self, file: &str, source: &str, document: &Node) {
+        document.children().iter().for_each(|block| {
+            let mut tags = Vec::new();
+            collect_tags(block, &mut tags);
+
+            tags.iter().for_each(|(name, span)| {
+                self.tags
+                    .entry(name.clone())
+                    .or_default()
+                    .push(TagOccurrence {
+                        file: file.to_string(),
+                        span: *span,
+                        sentence: sentence_at(source, span),
+                    });
+            });
+
+            let mut names = tags.into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            names.iter().enumerate().for_each(|(idx, lhs)| {
+                names[idx + 1..]
+                    .iter()
+                    .for_each(|rhs| self.count_co_occurrence(lhs, rhs));
+            });
+        });
+    }
+
+    pub fn occurrences(&self, tag: &str) -> &[TagOccurrence] {
+        self.tags
+            .get(tag)
+            .map(|item| item.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn co_occurrence(&self, lhs: &str, rhs: &str) -> usize {
+        let key = ordered_pair(lhs, rhs);
+
+        self.co_occurrences
+            .iter()
+            .find(|item| item.tags == key)
+            .map(|item| item.count)
+            .unwrap_or(0)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|err| err.to_string())
+    }
+
+    fn count_co_occurrence(&mut self, lhs: &str, rhs: &str) {
+        let key = ordered_pair(lhs, rhs);
+
+        match self
+            .co_occurrences
+            .binary_search_by(|item| item.tags.cmp(&key))
+        {
+            Ok(idx) => self.co_occurrences[idx].count += 1,
+            Err(idx) => self.co_occurrences.insert(
+                idx,
+                TagCoOccurrence {
+                    tags: key,
+                    count: 1,
+                },
+            ),
+        }
+    }
+}
+
+fn ordered_pair(lhs: &str, rhs: &str) -> (String, String) {
+    match lhs <= rhs {
+        true => (lhs.to_string(), rhs.to_string()),
+        false => (rhs.to_string(), lhs.to_string()),
+    }
+}
+
+pub(crate) fn collect_tags(node: &Node, acc: &mut Vec<(String, Span)>) {
+    match node {
+        Node::Parent(parent) if parent.name == "tag" || parent.name == "speaker" => {
+            let name = node.text();
+            // the tag rule also consumes the space that terminates it
+            let span = Span::new(parent.span.start, parent.span.start + 1 + name.len());
+
+            acc.push((name, span));
+        }
+        Node::Parent(parent) => parent
+            .children
+            .iter()
+            .for_each(|item| collect_tags(item, acc)),
+        _ => {}
+    }
+}
@@ -0,0 +1,222 @@
+use crate::parser::{Node, Parent, Span, Value};
+
+/// Read-only traversal. `enter` is called before the children of a node and
+/// `leave` after them; returning `false` from `enter` skips the children.
+///
+/// Closures taking `&Node` are visitors that only `enter`.
+pub trait Visitor {
+    fn enter(&mut self, _node: &Node) -> bool {
+        true
+    }
+
+    fn leave(&mut self, _node: &Node) {}
+}
+
+impl<F: FnMut(&Node)> Visitor for F {
+    fn enter(&mut self, node: &Node) -> bool {
+        self(node);
+
+        true
+    }
+}
+
+/// In-place traversal. The children are visited after `enter` has run, so
+/// nodes it adds are visited too.
+pub trait VisitorMut {
+    fn enter(&mut self, _node: &mut Node) -> bool {
+        true
+    }
+
+    fn leave(&mut self, _node: &mut Node) {}
+}
+
+/// Tree rewriter. `pre` is called before the children are folded and `post`
+/// after; returning `None` from either removes the node.
+///
+/// Closures taking and returning a node are folds with only a `post` hook.
+pub trait Fold {
+    fn pre(&mut self, node: Node) -> Option<Node> {
+        Some(node)
+    }
+
+    fn post(&mut self, node: Node) -> Option<Node> {
+        Some(node)
+    }
+}
+
+impl<F: FnMut(Node) -> Option<Node>> Fold for F {
+    fn post(&mut self, node: Node) -> Option<Node> {
+        self(node)
+    }
+}
+
+/// Matches nodes by name and, for `Value` nodes, by value.
+///
+/// ```
+/// use prose_parser::Selector;
+///
+/// let comments = Selector::names(&["inline_comment", "block_comment"]);
+/// let tag = Selector::name("tag").value("alice");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selector {
+    names: Vec<String>,
+    value: Option<String>,
+}
+
+impl Selector {
+    /// Matches every node but `Node::Empty`.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    pub fn name(name: &str) -> Self {
+        Self::names(&[name])
+    }
+
+    pub fn names(names: &[&str]) -> Self {
+        Self {
+            names: names.iter().map(|item| item.to_string()).collect(),
+            value: None,
+        }
+    }
+
+    /// Narrows to nodes whose `text()` equals `value`, so a `tag` parent
+    /// matches by its name.
+    pub fn value(self, value: &str) -> Self {
+        Self {
+            value: Some(value.to_string()),
+            ..self
+        }
+    }
+
+    pub fn matches(&self, node: &Node) -> bool {
+        let name = match node.name() {
+            Some(name) => name,
+            None => return false,
+        };
+
+        (self.names.is_empty() || self.names.iter().any(|item| item == name))
+            && self
+                .value
+                .as_ref()
+                .map(|value| &node.text() == value)
+                .unwrap_or(true)
+    }
+}
+
+impl Node {
+    pub fn walk<V: Visitor>(&self, visitor: &mut V) {
+        if visitor.enter(self) {
+            self.children().iter().for_each(|item| item.walk(visitor));
+        }
+        visitor.leave(self);
+    }
+
+    pub fn walk_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        if visitor.enter(self) {
+            if let Node::Parent(parent) = self {
+                parent
+                    .children
+                    .iter_mut()
+                    .for_each(|item| item.walk_mut(visitor));
+            }
+        }
+        visitor.leave(self);
+    }
+
+    /// Rewrites the tree with `folder`. A removed root becomes `Node::Empty`.
+    pub fn fold<F: Fold>(self, folder: &mut F) -> Node {
+        fold_node(self, folder).unwrap_or(Node::Empty)
+    }
+
+    /// Nodes matching `selector` in document order.
+    pub fn select(&self, selector: &Selector) -> Vec<&Node> {
+        let mut ret = Vec::new();
+        collect(self, selector, &mut ret);
+
+        ret
+    }
+
+    /// Replaces the value of every `text` node.
+    pub fn map_text<F: FnMut(&str) -> String>(&self, mut f: F) -> Node {
+        self.clone().fold(&mut |node| match node {
+            Node::Value(value) if value.name == "text" => Some(Node::Value(Value {
+                value: f(&value.value),
+                ..value
+            })),
+            _ => Some(node),
+        })
+    }
+
+    /// Removes the nodes matching `selector` and joins the text left around them.
+    pub fn remove(&self, selector: &Selector) -> Node {
+        self.clone()
+            .fold(&mut |node: Node| match selector.matches(&node) {
+                true => None,
+                false => Some(merge_text(node)),
+            })
+    }
+
+    pub fn remove_comments(&self) -> Node {
+        self.remove(&Selector::names(&["inline_comment", "block_comment"]))
+    }
+}
+
+fn fold_node<F: Fold>(node: Node, folder: &mut F) -> Option<Node> {
+    let node = match folder.pre(node)? {
+        Node::Parent(parent) => Node::Parent(Parent {
+            children: parent
+                .children
+                .into_iter()
+                .filter_map(|item| fold_node(item, folder))
+                .collect(),
+            ..parent
+        }),
+        node => node,
+    };
+
+    folder.post(node)
+}
+
+fn collect<'a>(node: &'a Node, selector: &Selector, acc: &mut Vec<&'a Node>) {
+    if selector.matches(node) {
+        acc.push(node);
+    }
+    node.children()
+        .iter()
+        .for_each(|item| collect(item, selector, acc));
+}
+
+/// Joins adjacent `text` values as the parser does.
+fn merge_text(node: Node) -> Node {
+    let parent = match node {
+        Node::Parent(parent) => parent,
+        node => return node,
+    };
+    let children = parent
+        .children
+        .into_iter()
+        .fold(Vec::new(), |mut acc, item| {
+            match (acc.pop(), item) {
+                (Some(Node::Value(left)), Node::Value(right))
+                    if left.name == "text" && right.name == "text" =>
+                {
+                    acc.push(Node::Value(Value {
+                        name: left.name,
+                        value: left.value + right.value.as_str(),
+                        span: Span::new(left.span.start, right.span.end),
+                    }));
+                }
+                (Some(left), right) => {
+                    acc.push(left);
+                    acc.push(right);
+                }
+                (None, right) => acc.push(right),
+            }
+
+            acc
+        });
+
+    Node::Parent(Parent { children, ..parent })
+}
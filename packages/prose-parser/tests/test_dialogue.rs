@@ -0,0 +1,37 @@
+extern crate prose_parser;
+
+use prose_parser::{Dialogue, Span};
+
+#[test]
+fn extract_dialogues() {
+    let text = "#alice「おはよう『約束』」\n「ええ」と #bob が言った。";
+    let document = prose_parser::parse(text).unwrap();
+
+    assert_eq!(
+        prose_parser::dialogues(&document),
+        vec![
+            Dialogue {
+                speaker: Some("alice".to_string()),
+                text: "おはよう『約束』".to_string(),
+                span: Span::new(0, 36),
+            },
+            Dialogue {
+                speaker: None,
+                text: "ええ".to_string(),
+                span: Span::new(37, 49),
+            },
+        ]
+    );
+}
+
+#[test]
+fn dialogue_stats() {
+    let document = prose_parser::parse("#alice「はい」\n#bob「いいえ」と言った。").unwrap();
+    let stats = prose_parser::dialogue_stats(&document);
+
+    assert_eq!(stats.characters, 14);
+    assert_eq!(stats.dialogue_characters, 5);
+    assert_eq!(stats.speakers.get("alice"), Some(&2));
+    assert_eq!(stats.speakers.get("bob"), Some(&3));
+    assert!((stats.ratio - 5.0 / 14.0).abs() < f64::EPSILON);
+}
@@ -0,0 +1,142 @@
+extern crate prose_parser;
+
+use prose_parser::lint::{LintContext, Rule, RuleLevel};
+use prose_parser::{Diagnostic, LintConfig, Linter, Severity, Span};
+
+fn lint(text: &str, config: &LintConfig) -> Vec<(String, String)> {
+    prose_parser::lint(text, config)
+        .unwrap()
+        .iter()
+        .map(|Diagnostic { code, span, .. }| (code.clone(), text[span.start..span.end].to_string()))
+        .collect()
+}
+
+#[test]
+fn lint_clean_document() {
+    let text = "# 第一話\n\n　彼は歩いた。空は青い。\n「こんにちは」\n";
+
+    assert_eq!(lint(text, &LintConfig::default()), vec![]);
+}
+
+#[test]
+fn lint_rules() {
+    let config = LintConfig::default();
+
+    assert_eq!(
+        lint("　本当？　嘘?　本当？", &config),
+        vec![("mixed-punctuation".to_string(), "?".to_string())]
+    );
+    assert_eq!(lint("　値は3.5です。", &config), vec![]);
+    assert_eq!(
+        lint("　待って…。――そう…………", &config),
+        vec![("dash-ellipsis-pairs".to_string(), "…".to_string())]
+    );
+    assert_eq!(
+        lint("彼は歩いた。", &config),
+        vec![("paragraph-indent".to_string(), "彼".to_string())]
+    );
+    assert_eq!(
+        lint("「ええ』と言った」", &config),
+        vec![("bracket-pairs".to_string(), "』".to_string())]
+    );
+    assert_eq!(
+        lint("「ええ", &config),
+        vec![("bracket-pairs".to_string(), "「".to_string())]
+    );
+    assert_eq!(
+        lint("　歩いた。走った。止まった。", &config),
+        vec![(
+            "repeated-endings".to_string(),
+            "歩いた。走った。止まった。".to_string()
+        )]
+    );
+    assert_eq!(
+        lint("　本文[^a]。\n[^a]: 注A\n[^b]: 注B\n", &config),
+        vec![("unused-footnote".to_string(), "[^b]: 注B".to_string())]
+    );
+}
+
+#[test]
+fn lint_notes_and_footnotes() {
+    let config = LintConfig::default();
+
+    assert_eq!(
+        lint("::: preface\n　本当？　嘘?\n:::\n", &config),
+        vec![("mixed-punctuation".to_string(), "?".to_string())]
+    );
+    assert_eq!(
+        lint("::: afterword\n　「ええ\n:::\n", &config),
+        vec![("bracket-pairs".to_string(), "「".to_string())]
+    );
+    assert_eq!(
+        lint("　本文[^a]。\n[^a]: 本当？　嘘?\n", &config),
+        vec![("mixed-punctuation".to_string(), "?".to_string())]
+    );
+}
+
+#[test]
+fn lint_config() {
+    let config = LintConfig::from_yaml(
+        "rules:\n  paragraph-indent: off\n  long-sentence: error\nmaxSentenceLength: 3\nknownTags: [alice]\n",
+    )
+    .unwrap();
+    assert_eq!(config.rules.get("paragraph-indent"), Some(&RuleLevel::Off));
+
+    let diagnostics = prose_parser::lint("#alice #bob 長い文章。", &config).unwrap();
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::new(
+                Severity::Warning,
+                "unknown-tag",
+                "unknown tag `#bob`",
+                Span::new(7, 11)
+            ),
+            Diagnostic::new(
+                Severity::Error,
+                "long-sentence",
+                "sentence is 5 characters long (max 3)",
+                Span::new(12, 27)
+            ),
+        ]
+    );
+}
+
+struct NoQuestions;
+
+impl Rule for NoQuestions {
+    fn name(&self) -> &'static str {
+        "no-questions"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Hint
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<(Span, String)> {
+        context
+            .source
+            .match_indices('？')
+            .map(|(idx, ch)| (Span::new(idx, idx + ch.len()), "question".to_string()))
+            .collect()
+    }
+}
+
+#[test]
+fn lint_custom_rule() {
+    let text = "　本当？";
+    let document = prose_parser::parse(text).unwrap();
+    let diagnostics = Linter::new(LintConfig::default())
+        .rule(NoQuestions)
+        .lint(text, &document);
+
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::new(
+            Severity::Hint,
+            "no-questions",
+            "question",
+            Span::new(9, 12)
+        )]
+    );
+}
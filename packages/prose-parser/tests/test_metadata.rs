@@ -0,0 +1,74 @@
+extern crate prose_parser;
+
+use insta::assert_yaml_snapshot;
+use prose_parser::{Metadata, Status};
+
+#[test]
+fn parse_yaml_front_matter() {
+    let document = prose_parser::parse(
+        "---\ntitle: 出会い\nepisode: 3\nstatus: published\npov: alice\npublishDate: 2022-01-01\n---\n# 出会い\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        document.metadata(),
+        Some(&Metadata {
+            title: Some("出会い".to_string()),
+            episode: Some(3),
+            status: Some(Status::Published),
+            pov: Some("alice".to_string()),
+            publish_date: Some("2022-01-01".to_string()),
+        })
+    );
+    assert_yaml_snapshot!(document, @r###"
+    ---
+    name: document
+    children:
+      - name: front_matter
+        children:
+          - name: yaml
+            value: "title: 出会い\nepisode: 3\nstatus: published\npov: alice\npublishDate: 2022-01-01\n"
+      - name: heading
+        children:
+          - name: heading_begin
+          - name: text
+            value: 出会い
+      - name: line_break
+    "###);
+}
+
+#[test]
+fn parse_toml_front_matter() {
+    let document =
+        prose_parser::parse("+++\ntitle = \"出会い\"\nstatus = \"draft\"\n+++\n本文").unwrap();
+
+    assert_eq!(
+        document.metadata(),
+        Some(&Metadata {
+            title: Some("出会い".to_string()),
+            status: Some(Status::Draft),
+            ..Metadata::default()
+        })
+    );
+}
+
+#[test]
+fn parse_without_front_matter() {
+    assert_eq!(
+        prose_parser::parse("本文\n---\n").unwrap().metadata(),
+        None
+    );
+    assert_eq!(
+        prose_parser::parse("---\n---\n").unwrap().metadata(),
+        Some(&Metadata::default())
+    );
+    assert!(prose_parser::parse("---\nepisode: first\n---\n").is_err());
+}
+
+#[test]
+fn check_reports_front_matter_span() {
+    let diagnostic = prose_parser::check("---\nepisode: first\n---\n本文").unwrap_err();
+
+    assert_eq!(diagnostic.code, "front-matter");
+    assert_eq!(diagnostic.span, prose_parser::Span::new(0, 23));
+}
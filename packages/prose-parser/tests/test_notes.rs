@@ -0,0 +1,69 @@
+extern crate prose_parser;
+
+use prose_parser::{AuthorNoteMode, FootnoteMode, NotesConfig};
+
+const TEXT: &str =
+    "::: 前書き\n更新しました。\n:::\n本文[^b]と[^a]。\n[^a]: 注A\n[^b]: 注B\n::: afterword\nまた次回\n:::\n";
+
+fn names(node: &prose_parser::Node) -> Vec<&str> {
+    node.children()
+        .iter()
+        .filter_map(|item| item.name())
+        .collect()
+}
+
+#[test]
+fn extract_notes_as_fields_and_endnotes() {
+    let document = prose_parser::parse(TEXT).unwrap();
+    let (body, notes) = prose_parser::extract_notes(&document, &NotesConfig::default());
+
+    assert_eq!(names(&body), vec!["paragraph", "line_break", "endnotes"]);
+    assert_eq!(notes.preface.unwrap().text(), "更新しました。");
+    assert_eq!(notes.afterword.unwrap().text(), "また次回");
+    assert_eq!(
+        notes
+            .footnotes
+            .iter()
+            .map(|item| (item.label.as_str(), item.number, item.definition.text()))
+            .collect::<Vec<_>>(),
+        vec![("b", 1, "注B".to_string()), ("a", 2, "注A".to_string())]
+    );
+}
+
+#[test]
+fn extract_notes_omit() {
+    let document = prose_parser::parse(TEXT).unwrap();
+    let config = NotesConfig {
+        footnotes: FootnoteMode::Omit,
+        author_notes: AuthorNoteMode::Omit,
+    };
+    let (body, notes) = prose_parser::extract_notes(&document, &config);
+
+    assert_eq!(names(&body), vec!["paragraph", "line_break"]);
+    assert_eq!(body.text(), "本文と。");
+    assert_eq!(notes.preface, None);
+    assert_eq!(notes.footnotes.len(), 2);
+}
+
+#[test]
+fn extract_notes_inline() {
+    let document = prose_parser::parse(TEXT).unwrap();
+    let config = NotesConfig {
+        footnotes: FootnoteMode::Endnotes,
+        author_notes: AuthorNoteMode::Inline,
+    };
+    let (body, _) = prose_parser::extract_notes(&document, &config);
+
+    assert_eq!(
+        names(&body),
+        vec![
+            "preface",
+            "line_break",
+            "paragraph",
+            "line_break",
+            "afterword",
+            "line_break",
+            "endnotes"
+        ]
+    );
+}
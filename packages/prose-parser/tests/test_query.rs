@@ -0,0 +1,92 @@
+extern crate prose_parser;
+
+use prose_parser::{Query, Span};
+
+const TEXT: &str = "# 第一話 #alice\n%% 回想\n　#bob は%仮%言った。\n\n#alice「#bob と」\n# 第二話";
+
+fn select(selector: &str) -> Vec<(String, String)> {
+    let document = prose_parser::parse(TEXT).unwrap();
+
+    document
+        .query(selector)
+        .unwrap()
+        .iter()
+        .map(|item| {
+            (
+                item.node.name().unwrap_or_default().to_string(),
+                TEXT[item.span.start..item.span.end].to_string(),
+            )
+        })
+        .collect()
+}
+
+fn texts(selector: &str) -> Vec<String> {
+    select(selector).into_iter().map(|(_, text)| text).collect()
+}
+
+#[test]
+fn query_names_and_combinators() {
+    assert_eq!(texts("heading > tag"), vec!["#alice"]);
+    assert_eq!(texts("heading"), vec!["# 第一話 #alice", "# 第二話"]);
+    assert_eq!(texts("quote tag"), vec!["#bob "]);
+    assert_eq!(texts("paragraph > tag"), vec!["#bob "]);
+    assert_eq!(
+        texts("block_comment + paragraph"),
+        vec!["　#bob は%仮%言った。"]
+    );
+    assert_eq!(
+        texts("block_comment ~ paragraph"),
+        vec!["　#bob は%仮%言った。", "#alice「#bob と」"]
+    );
+    assert_eq!(
+        select("heading, speaker")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>(),
+        vec!["heading", "speaker", "heading"]
+    );
+}
+
+#[test]
+fn query_filters() {
+    assert_eq!(
+        texts("paragraph:has(inline_comment)"),
+        vec!["　#bob は%仮%言った。"]
+    );
+    assert_eq!(
+        texts("paragraph:has(> dialogue)"),
+        vec!["#alice「#bob と」"]
+    );
+    assert_eq!(
+        texts("*:has(+ paragraph)"),
+        vec!["%% 回想", "　#bob は%仮%言った。"]
+    );
+    assert_eq!(texts("tag[value=bob]"), vec!["#bob ", "#bob "]);
+    assert_eq!(texts("tag[value=bob]:not(quote tag)"), vec!["#bob "]);
+    assert_eq!(
+        texts("*[value=\"alice\"]:not(text)"),
+        vec!["#alice", "#alice"]
+    );
+}
+
+#[test]
+fn query_spans() {
+    let document = prose_parser::parse("ab #tag").unwrap();
+    let query = "tag".parse::<Query>().unwrap();
+
+    assert_eq!(
+        query
+            .select(&document)
+            .iter()
+            .map(|item| item.span)
+            .collect::<Vec<_>>(),
+        vec![Span::new(3, 7)]
+    );
+}
+
+#[test]
+fn query_errors() {
+    assert!(Query::parse("heading >").is_err());
+    assert!(Query::parse("").is_err());
+    assert!(Query::parse("tag[name=a]").is_err());
+}
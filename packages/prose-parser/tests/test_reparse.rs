@@ -0,0 +1,104 @@
+extern crate prose_parser;
+
+use prose_parser::{Span, TextEdit};
+use std::fs;
+
+const INSERTIONS: [&str; 12] = [
+    "あ",
+    "\n",
+    "\n\n",
+    "「",
+    "」",
+    "#alice ",
+    "# ",
+    "%",
+    "[^a]",
+    "[^a]: 注\n",
+    "::: preface\n",
+    "---\n",
+];
+
+fn apply(source: &str, edit: &TextEdit) -> String {
+    let mut ret = source.to_string();
+    ret.replace_range(edit.range.start..edit.range.end, &edit.text);
+
+    ret
+}
+
+fn assert_reparse(source: &str, edit: &TextEdit) -> String {
+    let previous = prose_parser::parse(source).unwrap();
+    let next = apply(source, edit);
+
+    assert_eq!(
+        prose_parser::reparse(&previous, &next, edit),
+        prose_parser::parse(&next),
+        "{:?} -> {:?}",
+        source,
+        next
+    );
+
+    next
+}
+
+#[test]
+fn reparse_single_block() {
+    let source = "　一行目。\n\n　二行目。\n　三行目。";
+    let edit = TextEdit::new(Span::new(23, 23), "追加");
+    let next = assert_reparse(source, &edit);
+
+    assert_eq!(next, "　一行目。\n\n　二追加行目。\n　三行目。");
+}
+
+#[test]
+fn reparse_joins_and_splits_lines() {
+    let source = "一行目\n二行目\n\n三行目";
+
+    assert_reparse(source, &TextEdit::new(Span::new(9, 10), ""));
+    assert_reparse(source, &TextEdit::new(Span::new(3, 3), "\n"));
+    assert_reparse(source, &TextEdit::new(Span::new(0, source.len()), ""));
+    assert_reparse(
+        source,
+        &TextEdit::new(Span::new(source.len(), source.len()), "\n#alice「やあ」"),
+    );
+}
+
+#[test]
+fn reparse_matches_full_parse() {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as usize
+    };
+
+    ["document.pd", "dialogue.pd", "notes.pd"]
+        .iter()
+        .for_each(|name| {
+            let mut source = fs::read_to_string(format!("./tests/fixtures/{}", name)).unwrap();
+            (0..200).for_each(|_| {
+                let boundaries = source
+                    .char_indices()
+                    .map(|(idx, _)| idx)
+                    .chain(std::iter::once(source.len()))
+                    .collect::<Vec<_>>();
+                let start = boundaries[next() % boundaries.len()];
+                let end = boundaries
+                    .iter()
+                    .copied()
+                    .filter(|idx| *idx >= start)
+                    .nth(next() % 3)
+                    .unwrap_or(start);
+                let text = match next() % 3 {
+                    0 => "",
+                    _ => INSERTIONS[next() % INSERTIONS.len()],
+                };
+
+                let next = assert_reparse(&source, &TextEdit::new(Span::new(start, end), text));
+                // keep editing a document that parses
+                if prose_parser::parse(&next).is_ok() {
+                    source = next;
+                }
+            });
+        });
+}
@@ -0,0 +1,38 @@
+extern crate prose_parser;
+
+use prose_parser::Span;
+
+#[test]
+fn index_tags() {
+    let chapter1 = "# 出会い #alice\n\n#alice は歩いた。#bob と会った。\n";
+    let chapter2 = "#alice #bob 二人は笑った。";
+    let index = prose_parser::index_tags(&[("1.pd", chapter1), ("2.pd", chapter2)]).unwrap();
+
+    assert_eq!(index.tags.keys().collect::<Vec<_>>(), vec!["alice", "bob"]);
+
+    let alice = index.occurrences("alice");
+    assert_eq!(alice.len(), 3);
+    assert_eq!(alice[0].file, "1.pd");
+    assert_eq!(&chapter1[alice[0].span.start..alice[0].span.end], "#alice");
+    assert_eq!(alice[0].sentence, "# 出会い #alice");
+    assert_eq!(alice[1].sentence, "#alice は歩いた。");
+    assert_eq!(alice[2].file, "2.pd");
+    assert_eq!(alice[2].span, Span::new(0, 6));
+
+    let bob = index.occurrences("bob");
+    assert_eq!(bob[0].sentence, "#bob と会った。");
+
+    assert_eq!(index.co_occurrence("alice", "bob"), 2);
+    assert_eq!(index.co_occurrence("bob", "alice"), 2);
+    assert_eq!(index.co_occurrence("alice", "carol"), 0);
+}
+
+#[test]
+fn index_tags_to_json() {
+    let index = prose_parser::index_tags(&[("1.pd", "#a #b")]).unwrap();
+
+    assert_eq!(
+        index.to_json().unwrap(),
+        r##"{"tags":{"a":[{"file":"1.pd","span":{"start":0,"end":2},"sentence":"#a #b"}],"b":[{"file":"1.pd","span":{"start":3,"end":5},"sentence":"#a #b"}]},"coOccurrences":[{"tags":["a","b"],"count":1}]}"##
+    );
+}
@@ -0,0 +1,111 @@
+extern crate prose_parser;
+
+use prose_parser::{Fold, Node, Selector, Visitor, VisitorMut};
+
+const TEXT: &str = "#alice 「やあ」%独白%と言った。\n%% メモ\n# 見出し";
+
+#[test]
+fn walk() {
+    struct Depth {
+        depth: usize,
+        max: usize,
+    }
+    impl Visitor for Depth {
+        fn enter(&mut self, _node: &Node) -> bool {
+            self.depth += 1;
+            self.max = self.max.max(self.depth);
+
+            true
+        }
+
+        fn leave(&mut self, _node: &Node) {
+            self.depth -= 1;
+        }
+    }
+
+    let document = prose_parser::parse(TEXT).unwrap();
+    let mut depth = Depth { depth: 0, max: 0 };
+    document.walk(&mut depth);
+    assert_eq!((depth.depth, depth.max), (0, 4));
+
+    let mut names = Vec::new();
+    document.walk(&mut |node: &Node| names.extend(node.name().map(|name| name.to_string())));
+    assert_eq!(names[..4], ["document", "paragraph", "tag", "text"]);
+}
+
+#[test]
+fn walk_mut() {
+    struct Upper;
+    impl VisitorMut for Upper {
+        fn enter(&mut self, node: &mut Node) -> bool {
+            node.name() != Some("tag")
+        }
+
+        fn leave(&mut self, node: &mut Node) {
+            if let Node::Value(value) = node {
+                value.value = value.value.to_uppercase();
+            }
+        }
+    }
+
+    let mut document = prose_parser::parse("#tag abc").unwrap();
+    document.walk_mut(&mut Upper);
+    assert_eq!(document.text(), "tagABC");
+}
+
+#[test]
+fn fold_with_pre_and_post() {
+    /// Drops headings before their children are visited, counts the rest.
+    struct NoHeadings(usize);
+    impl Fold for NoHeadings {
+        fn pre(&mut self, node: Node) -> Option<Node> {
+            match node.name() {
+                Some("heading") => None,
+                _ => Some(node),
+            }
+        }
+
+        fn post(&mut self, node: Node) -> Option<Node> {
+            self.0 += 1;
+
+            Some(node)
+        }
+    }
+
+    let mut folder = NoHeadings(0);
+    let document = prose_parser::parse(TEXT).unwrap().fold(&mut folder);
+    assert!(document.select(&Selector::name("heading")).is_empty());
+    assert_eq!(folder.0, 13);
+}
+
+#[test]
+fn select() {
+    let document = prose_parser::parse(TEXT).unwrap();
+
+    assert_eq!(document.select(&Selector::name("quote")).len(), 1);
+    assert_eq!(
+        document
+            .select(&Selector::names(&["speaker", "tag"]).value("alice"))
+            .len(),
+        1
+    );
+    assert!(document
+        .select(&Selector::name("tag").value("bob"))
+        .is_empty());
+}
+
+#[test]
+fn map_text_and_remove_comments() {
+    let document = prose_parser::parse(TEXT).unwrap();
+
+    assert_eq!(
+        document.map_text(|text| text.replace("言", "い")).text(),
+        "aliceやあ独白といった。メモ見出し"
+    );
+
+    let document = document.remove_comments();
+    assert!(document
+        .select(&Selector::names(&["inline_comment", "block_comment"]))
+        .is_empty());
+    assert_eq!(document.text(), "aliceやあと言った。見出し");
+}
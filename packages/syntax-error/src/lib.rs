@@ -0,0 +1,40 @@
+//! How the prose and CRG parsers report a pest error: the byte range it
+//! covers and a message naming the rules that were expected there.
+
+extern crate pest;
+
+use pest::error::{Error, ErrorVariant, InputLocation};
+use pest::RuleType;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+impl<R: RuleType> From<&Error<R>> for SyntaxError {
+    fn from(err: &Error<R>) -> Self {
+        let (start, end) = match err.location {
+            InputLocation::Pos(pos) => (pos, pos),
+            InputLocation::Span(span) => span,
+        };
+        let message = match &err.variant {
+            ErrorVariant::ParsingError { positives, .. } => format!(
+                "expected {}",
+                positives
+                    .iter()
+                    .map(|rule| format!("{:?}", rule))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ErrorVariant::CustomError { message } => message.clone(),
+        };
+
+        SyntaxError {
+            start,
+            end,
+            message,
+        }
+    }
+}
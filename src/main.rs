@@ -1,3 +1,4 @@
+extern crate causal_relation_graphs;
 extern crate processor;
 extern crate prose_parser;
 
@@ -5,7 +6,52 @@ use processor::Processor;
 use prose_parser::Node;
 
 fn main() {
-    println!("{}", process("text").unwrap());
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.as_slice() {
+        [command, selector, file] if command == "query" => query(selector, file),
+        _ => process("text"),
+    };
+
+    match result {
+        Ok(output) => println!("{}", output),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `ciska query SELECTOR FILE` prints the span, name and source of each match.
+/// `.crg` files are queried by the names of their grammar rules, such as
+/// `letStmt > varSymbol`.
+fn query(selector: &str, file: &str) -> Result<String, String> {
+    let text = std::fs::read_to_string(file).map_err(|err| format!("{}: {}", file, err))?;
+    let matches = match file.ends_with(".crg") {
+        true => {
+            let tree = causal_relation_graphs::syntax_tree(&text)
+                .map_err(|err| format!("{}: {}", file, err.render(&text)))?;
+
+            causal_relation_graphs::query(&tree, &text, selector)?
+                .into_iter()
+                .map(|item| (item.span.start, item.span.end, item.name))
+                .collect::<Vec<_>>()
+        }
+        false => prose_parser::parse(&text)?
+            .query(selector)?
+            .iter()
+            .map(|item| {
+                let name = item.node.name().unwrap_or_default().to_string();
+
+                (item.span.start, item.span.end, name)
+            })
+            .collect(),
+    };
+
+    Ok(matches
+        .iter()
+        .map(|(start, end, name)| format!("{}..{}\t{}\t{}", start, end, name, &text[*start..*end]))
+        .collect::<Vec<_>>()
+        .join("\n"))
 }
 
 fn process(text: &str) -> Result<String, String> {
@@ -18,26 +64,7 @@ fn process(text: &str) -> Result<String, String> {
 }
 
 fn transform_text(node: &Node, append_text: &str) -> Node {
-    match node {
-        Node::Parent(parent) => {
-            let mut ret = parent.clone();
-            ret.children = parent
-                .children
-                .iter()
-                .map(|item| transform_text(item, append_text))
-                .collect::<Vec<_>>();
-
-            Node::Parent(ret)
-        }
-        Node::Token(token) => Node::Token(token.clone()),
-        Node::Empty => Node::Empty,
-        Node::Value(value) => {
-            let mut ret = value.clone();
-            ret.value = value.value.to_string() + append_text;
-
-            Node::Value(ret)
-        }
-    }
+    node.map_text(|text| text.to_string() + append_text)
 }
 
 #[cfg(test)]